@@ -0,0 +1,567 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::inventory::Inventory;
+use crate::scanner;
+use crate::system_info::SystemInfo;
+use crate::webhook;
+use pnet::util::MacAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Global CPU usage in percent.
+    Cpu,
+    /// Used RAM in percent.
+    Memory,
+    /// Used swap in percent.
+    Swap,
+    /// Available space on `mount` in percent.
+    DiskAvailable,
+    /// Used space on `mount` in percent.
+    DiskUsed,
+    /// Number of running processes.
+    Processes,
+    /// A MAC address that is neither allow-listed nor seen before.
+    NewMac,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+}
+
+impl Comparison {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Above => value > threshold,
+            Comparison::AtLeast => value >= threshold,
+            Comparison::Below => value < threshold,
+            Comparison::AtMost => value <= threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub metric: Metric,
+    /// Mount point for the disk metrics.
+    pub mount: Option<String>,
+    #[serde(default = "default_comparison")]
+    pub op: Comparison,
+    #[serde(default)]
+    pub threshold: f64,
+    /// How long the condition must hold before the alert fires.
+    #[serde(default)]
+    pub for_secs: u64,
+    /// Hysteresis: a firing alert only resolves once the value is back past
+    /// this level. Defaults to `threshold`.
+    pub clear: Option<f64>,
+    /// Minimum time between two notifications of this rule.
+    #[serde(default)]
+    pub cooldown_secs: u64,
+}
+
+/// Devices found by the background sweep, drained on every evaluation.
+pub type ScanFeed = Arc<Mutex<Vec<(Ipv4Addr, MacAddr)>>>;
+
+fn default_comparison() -> Comparison {
+    Comparison::Above
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Stdout,
+    Log {
+        path: String,
+    },
+    Webhook {
+        url: String,
+    },
+    /// Only delivered when running under `--gui`.
+    Desktop,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanConfig {
+    pub range: String,
+    pub interface: String,
+    #[serde(default = "default_scan_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_scan_interval() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertConfig {
+    #[serde(default = "default_interval")]
+    pub interval_secs: u64,
+    pub rules: Vec<Rule>,
+    #[serde(default = "default_sinks")]
+    pub sinks: Vec<SinkConfig>,
+    /// Network to sweep for the `new_mac` rule.
+    pub scan: Option<ScanConfig>,
+    /// MAC addresses that never raise `new_mac`.
+    #[serde(default)]
+    pub known_macs: Vec<String>,
+    /// Inventory written by `--daemon`; every device in it counts as known.
+    pub inventory: Option<String>,
+}
+
+fn default_interval() -> u64 {
+    10
+}
+
+fn default_sinks() -> Vec<SinkConfig> {
+    vec![SinkConfig::Stdout]
+}
+
+impl AlertConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let config: AlertConfig =
+            serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+        for rule in &config.rules {
+            if matches!(rule.metric, Metric::DiskAvailable | Metric::DiskUsed)
+                && rule.mount.is_none()
+            {
+                return Err(format!("Rule {} needs a \"mount\"", rule.name));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub state: AlertState,
+    pub message: String,
+    pub value: Option<f64>,
+    pub timestamp: DateTime<Local>,
+}
+
+/// One reading of everything the threshold rules can look at.
+pub struct Sample {
+    pub cpu: f64,
+    pub memory: f64,
+    pub swap: f64,
+    /// (mount point, total bytes, available bytes)
+    pub mounts: Vec<(String, u64, u64)>,
+    pub processes: usize,
+}
+
+impl Sample {
+    pub fn from_system(system_info: &SystemInfo) -> Self {
+        let (used_memory, total_memory) = system_info.get_memory_usage();
+        let (used_swap, total_swap) = system_info.get_swap_usage();
+        Sample {
+            cpu: system_info.get_cpu_usage() as f64,
+            memory: percent(used_memory, total_memory),
+            swap: percent(used_swap, total_swap),
+            mounts: system_info.get_mount_usage(),
+            processes: system_info.get_processes().len(),
+        }
+    }
+
+    fn value(&self, rule: &Rule) -> Option<f64> {
+        let mount = || {
+            self.mounts
+                .iter()
+                .find(|(mount, _, _)| Some(mount) == rule.mount.as_ref())
+        };
+        match rule.metric {
+            Metric::Cpu => Some(self.cpu),
+            Metric::Memory => Some(self.memory),
+            Metric::Swap => Some(self.swap),
+            Metric::DiskAvailable => {
+                mount().map(|(_, total, available)| percent(*available, *total))
+            }
+            Metric::DiskUsed => {
+                mount().map(|(_, total, available)| 100.0 - percent(*available, *total))
+            }
+            Metric::Processes => Some(self.processes as f64),
+            Metric::NewMac => None,
+        }
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+struct RuleState {
+    rule: Rule,
+    pending_since: Option<Instant>,
+    firing: bool,
+    /// Whether the current firing was announced; a suppressed firing is resolved silently.
+    notified: bool,
+    last_notified: Option<Instant>,
+}
+
+impl RuleState {
+    fn in_cooldown(&self, now: Instant) -> bool {
+        let cooldown = Duration::from_secs(self.rule.cooldown_secs);
+        self.last_notified
+            .is_some_and(|last| now.duration_since(last) < cooldown)
+    }
+}
+
+pub struct AlertEngine {
+    rules: Vec<RuleState>,
+    known_macs: HashSet<String>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<Rule>, known_macs: impl IntoIterator<Item = String>) -> Self {
+        AlertEngine {
+            rules: rules
+                .into_iter()
+                .map(|rule| RuleState {
+                    rule,
+                    pending_since: None,
+                    firing: false,
+                    notified: false,
+                    last_notified: None,
+                })
+                .collect(),
+            known_macs: known_macs
+                .into_iter()
+                .map(|mac| mac.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Runs every threshold rule against a fresh sample.
+    pub fn evaluate(&mut self, sample: &Sample, now: Instant) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for state in &mut self.rules {
+            let value = match sample.value(&state.rule) {
+                Some(value) => value,
+                None => continue,
+            };
+            let rule = &state.rule;
+
+            if !state.firing {
+                if !rule.op.holds(value, rule.threshold) {
+                    state.pending_since = None;
+                    continue;
+                }
+                let since = *state.pending_since.get_or_insert(now);
+                if now.duration_since(since) < Duration::from_secs(rule.for_secs) {
+                    continue;
+                }
+                state.firing = true;
+                state.notified = !state.in_cooldown(now);
+                if state.notified {
+                    state.last_notified = Some(now);
+                    alerts.push(alert(rule, AlertState::Firing, value));
+                }
+            } else {
+                let clear = rule.clear.unwrap_or(rule.threshold);
+                // Still on the alerting side of the clear level: keep firing
+                if rule.op.holds(value, clear) || value == clear {
+                    continue;
+                }
+                state.firing = false;
+                state.pending_since = None;
+                if state.notified {
+                    alerts.push(alert(rule, AlertState::Resolved, value));
+                }
+            }
+        }
+        alerts
+    }
+
+    /// Raises `new_mac` once for every MAC address not seen before. Cooldowns
+    /// do not apply, since each address is reported only once anyway.
+    pub fn observe_devices(&mut self, devices: &[(Ipv4Addr, MacAddr)]) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (ip, mac) in devices {
+            if !self.known_macs.insert(mac.to_string().to_lowercase()) {
+                continue;
+            }
+            for state in &self.rules {
+                if state.rule.metric == Metric::NewMac {
+                    alerts.push(Alert {
+                        rule: state.rule.name.clone(),
+                        state: AlertState::Firing,
+                        message: format!("Unknown device {} at {}", mac, ip),
+                        value: None,
+                        timestamp: Local::now(),
+                    });
+                }
+            }
+        }
+        alerts
+    }
+
+    fn watches_devices(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|state| state.rule.metric == Metric::NewMac)
+    }
+}
+
+fn alert(rule: &Rule, state: AlertState, value: f64) -> Alert {
+    let subject = match (&rule.metric, &rule.mount) {
+        (Metric::DiskAvailable, Some(mount)) => format!("{} available", mount),
+        (Metric::DiskUsed, Some(mount)) => format!("{} used", mount),
+        (metric, _) => format!("{:?}", metric).to_lowercase(),
+    };
+    let message = match state {
+        AlertState::Firing => format!(
+            "{} is {:.1} (threshold {:.1}{})",
+            subject,
+            value,
+            rule.threshold,
+            if rule.for_secs > 0 {
+                format!(" for {}s", rule.for_secs)
+            } else {
+                String::new()
+            }
+        ),
+        AlertState::Resolved => format!("{} back to {:.1}", subject, value),
+    };
+    Alert {
+        rule: rule.name.clone(),
+        state,
+        message,
+        value: Some(value),
+        timestamp: Local::now(),
+    }
+}
+
+/// Delivers an alert to every configured sink. `desktop` is provided by the
+/// GTK front end; elsewhere desktop sinks are skipped.
+pub fn notify(alert: &Alert, sinks: &[SinkConfig], desktop: Option<&dyn Fn(&Alert)>) {
+    for sink in sinks {
+        match sink {
+            SinkConfig::Stdout => {
+                let marker = match alert.state {
+                    AlertState::Firing => "[!]",
+                    AlertState::Resolved => "[+]",
+                };
+                println!(
+                    "{} {} {} {:?}: {}",
+                    alert.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    marker,
+                    alert.rule,
+                    alert.state,
+                    alert.message
+                );
+            }
+            SinkConfig::Log { path } => {
+                let line = serde_json::to_string(alert).unwrap_or_default();
+                let written = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut file| writeln!(file, "{}", line));
+                if let Err(e) = written {
+                    eprintln!("[-] Alert log {}: {}", path, e);
+                }
+            }
+            SinkConfig::Webhook { url } => {
+                let body = serde_json::to_string(alert).unwrap_or_default();
+                if let Err(e) = webhook::post_json(url, &body) {
+                    eprintln!("[-] {}", e);
+                }
+            }
+            SinkConfig::Desktop => {
+                if let Some(show) = desktop {
+                    show(alert);
+                }
+            }
+        }
+    }
+}
+
+/// Builds the engine from a config, seeding known MACs from the allow-list and inventory.
+pub fn engine_from_config(config: &AlertConfig) -> AlertEngine {
+    let mut known = config.known_macs.clone();
+    if let Some(path) = &config.inventory {
+        match Inventory::load(path) {
+            Ok(inventory) => known.extend(inventory.devices.into_keys()),
+            Err(e) => eprintln!("[-] Could not load inventory: {}", e),
+        }
+    }
+    AlertEngine::new(config.rules.clone(), known)
+}
+
+/// Sweeps the configured network in the background and collects what answered.
+pub fn spawn_scanner(config: &AlertConfig) -> Option<ScanFeed> {
+    let scan = config.scan.clone()?;
+    let found = Arc::new(Mutex::new(Vec::new()));
+    let sink = found.clone();
+    thread::spawn(move || {
+        loop {
+            match scanner::scan(&scan.range, &scan.interface) {
+                Ok(devices) => sink.lock().unwrap().extend(devices),
+                Err(e) => eprintln!("[-] Scan failed: {}", e),
+            }
+            thread::sleep(Duration::from_secs(scan.interval_secs));
+        }
+    });
+    Some(found)
+}
+
+/// Evaluates the rules on an interval until killed.
+pub fn run(config_path: &str) {
+    let config = match AlertConfig::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("[-] {}", e);
+            return;
+        }
+    };
+    let mut engine = engine_from_config(&config);
+    let scanned = if engine.watches_devices() {
+        if config.scan.is_none() {
+            println!("[-] new_mac rules need a \"scan\" section; they will never fire");
+        }
+        spawn_scanner(&config)
+    } else {
+        None
+    };
+
+    println!(
+        "[+] Evaluating {} rules every {}s",
+        config.rules.len(),
+        config.interval_secs
+    );
+    let mut system_info = SystemInfo::new();
+    loop {
+        thread::sleep(Duration::from_secs(config.interval_secs));
+        system_info.refresh();
+
+        let mut alerts = engine.evaluate(&Sample::from_system(&system_info), Instant::now());
+        if let Some(scanned) = &scanned {
+            let devices: Vec<_> = scanned.lock().unwrap().drain(..).collect();
+            alerts.extend(engine.observe_devices(&devices));
+        }
+        for alert in &alerts {
+            notify(alert, &config.sinks, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_rule() -> Rule {
+        Rule {
+            name: "cpu-high".to_string(),
+            metric: Metric::Cpu,
+            mount: None,
+            op: Comparison::Above,
+            threshold: 90.0,
+            for_secs: 300,
+            clear: Some(80.0),
+            cooldown_secs: 3600,
+        }
+    }
+
+    fn sample(cpu: f64) -> Sample {
+        Sample {
+            cpu,
+            memory: 0.0,
+            swap: 0.0,
+            mounts: vec![("/var".to_string(), 100, 5)],
+            processes: 1,
+        }
+    }
+
+    #[test]
+    fn fires_only_after_duration_and_clears_with_hysteresis() {
+        let mut engine = AlertEngine::new(vec![cpu_rule()], Vec::new());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(engine.evaluate(&sample(95.0), at(0)).is_empty());
+        assert!(engine.evaluate(&sample(95.0), at(200)).is_empty());
+        let fired = engine.evaluate(&sample(95.0), at(300));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].state, AlertState::Firing);
+
+        // Between clear and threshold the alert keeps firing
+        assert!(engine.evaluate(&sample(85.0), at(310)).is_empty());
+        let resolved = engine.evaluate(&sample(70.0), at(320));
+        assert_eq!(resolved[0].state, AlertState::Resolved);
+    }
+
+    #[test]
+    fn cooldown_suppresses_repeat_notifications() {
+        let mut rule = cpu_rule();
+        rule.for_secs = 0;
+        let mut engine = AlertEngine::new(vec![rule], Vec::new());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(engine.evaluate(&sample(95.0), at(0)).len(), 1);
+        assert_eq!(engine.evaluate(&sample(50.0), at(10)).len(), 1);
+        assert!(engine.evaluate(&sample(95.0), at(20)).is_empty());
+        assert!(engine.evaluate(&sample(50.0), at(30)).is_empty());
+        assert_eq!(engine.evaluate(&sample(95.0), at(3700)).len(), 1);
+    }
+
+    #[test]
+    fn disk_rule_and_new_mac() {
+        let disk = Rule {
+            name: "var-full".to_string(),
+            metric: Metric::DiskAvailable,
+            mount: Some("/var".to_string()),
+            op: Comparison::Below,
+            threshold: 10.0,
+            for_secs: 0,
+            clear: None,
+            cooldown_secs: 0,
+        };
+        let mac = Rule {
+            name: "new-mac".to_string(),
+            metric: Metric::NewMac,
+            ..disk.clone()
+        };
+        let mut engine = AlertEngine::new(vec![disk, mac], vec!["AA:BB:CC:DD:EE:01".to_string()]);
+
+        assert_eq!(engine.evaluate(&sample(0.0), Instant::now()).len(), 1);
+
+        let known = MacAddr::new(0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01);
+        let stranger = MacAddr::new(0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02);
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        let alerts = engine.observe_devices(&[(ip, known), (ip, stranger)]);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].message.contains("aa:bb:cc:dd:ee:02"));
+        assert!(engine.observe_devices(&[(ip, stranger)]).is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::system_info::format_bytes;
+
+/// cgroup v1 reports "no limit" as a page-aligned `i64::MAX`.
+const V1_UNLIMITED: u64 = 1 << 62;
+const SAMPLE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+/// Limits and usage of the cgroup we run in. `None` means unlimited or not exposed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupLimits {
+    pub version: Version,
+    pub path: String,
+    /// Bytes.
+    pub memory_max: Option<u64>,
+    pub memory_current: Option<u64>,
+    /// `(quota, period)` in microseconds.
+    pub cpu_max: Option<(u64, u64)>,
+    /// Cumulative CPU time in microseconds.
+    pub cpu_usage_usec: Option<u64>,
+    pub pids_max: Option<u64>,
+    pub pids_current: Option<u64>,
+}
+
+impl CgroupLimits {
+    /// CPU quota expressed as a number of cores.
+    pub fn cpu_cores(&self) -> Option<f64> {
+        self.cpu_max
+            .filter(|(_, period)| *period > 0)
+            .map(|(quota, period)| quota as f64 / period as f64)
+    }
+}
+
+/// Usage of one group in the host hierarchy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupUsage {
+    pub path: String,
+    pub memory: Option<u64>,
+    pub cpu_usage_usec: Option<u64>,
+    pub pids: Option<u64>,
+}
+
+/// Reads a cgroup control file holding a number or `max`.
+pub fn parse_limit(contents: &str) -> Option<u64> {
+    contents.trim().parse().ok()
+}
+
+/// Parses v2 `cpu.max`, e.g. `50000 100000` or `max 100000`.
+pub fn parse_cpu_max(contents: &str) -> Option<(u64, u64)> {
+    let mut fields = contents.split_whitespace();
+    let quota = fields.next()?.parse().ok()?;
+    let period = fields.next()?.parse().ok()?;
+    Some((quota, period))
+}
+
+/// `usage_usec` from v2 `cpu.stat`.
+pub fn parse_cpu_stat(contents: &str) -> Option<u64> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// The path of `controller` from `/proc/<pid>/cgroup`. An empty controller
+/// selects the unified (v2) hierarchy.
+pub fn parse_proc_cgroup(contents: &str, controller: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let mut fields = line.splitn(3, ':');
+        let (_, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+        let matches = if controller.is_empty() {
+            controllers.is_empty()
+        } else {
+            controllers.split(',').any(|name| name == controller)
+        };
+        matches.then(|| path.trim().to_string())
+    })
+}
+
+/// Names the container runtime we are running under, if any.
+pub fn detect_container(root: &Path) -> Option<&'static str> {
+    if root.join("var/run/secrets/kubernetes.io").exists() {
+        return Some("kubernetes");
+    }
+    if root.join(".dockerenv").exists() {
+        return Some("docker");
+    }
+    if root.join("run/.containerenv").exists() {
+        return Some("podman");
+    }
+    // Without a cgroup namespace the init process shows the runtime's group
+    let init = fs::read_to_string(root.join("proc/1/cgroup")).unwrap_or_default();
+    [
+        ("kubepods", "kubernetes"),
+        ("docker", "docker"),
+        ("libpod", "podman"),
+        ("lxc", "lxc"),
+    ]
+    .iter()
+    .find(|(marker, _)| init.contains(marker))
+    .map(|(_, runtime)| *runtime)
+}
+
+fn read(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+/// The group directory under `mount`. Inside a cgroup namespace the listed
+/// path does not exist and our group is mounted at the root instead.
+fn group_dir(mount: &Path, path: &str) -> PathBuf {
+    let dir = mount.join(path.trim_start_matches('/'));
+    if dir.exists() {
+        dir
+    } else {
+        mount.to_path_buf()
+    }
+}
+
+/// Reads the limits of our own cgroup below `root` (normally `/`).
+pub fn read_limits(root: &Path) -> Option<CgroupLimits> {
+    let self_cgroup = read(&root.join("proc/self/cgroup"))?;
+    let mount = root.join("sys/fs/cgroup");
+
+    // Only the unified hierarchy has cgroup.controllers at the top
+    if mount.join("cgroup.controllers").exists() {
+        let path = parse_proc_cgroup(&self_cgroup, "")?;
+        let dir = group_dir(&mount, &path);
+        let file = |name: &str| read(&dir.join(name));
+        return Some(CgroupLimits {
+            version: Version::V2,
+            memory_max: file("memory.max").and_then(|c| parse_limit(&c)),
+            memory_current: file("memory.current").and_then(|c| parse_limit(&c)),
+            cpu_max: file("cpu.max").and_then(|c| parse_cpu_max(&c)),
+            cpu_usage_usec: file("cpu.stat").and_then(|c| parse_cpu_stat(&c)),
+            pids_max: file("pids.max").and_then(|c| parse_limit(&c)),
+            pids_current: file("pids.current").and_then(|c| parse_limit(&c)),
+            path,
+        });
+    }
+
+    let controller_file = |controller: &str, name: &str| {
+        let path = parse_proc_cgroup(&self_cgroup, controller)?;
+        read(&group_dir(&mount.join(controller), &path).join(name))
+    };
+    let memory_path = parse_proc_cgroup(&self_cgroup, "memory")?;
+    let quota = controller_file("cpu", "cpu.cfs_quota_us")
+        .and_then(|c| c.trim().parse::<i64>().ok())
+        .filter(|quota| *quota > 0);
+    let period = controller_file("cpu", "cpu.cfs_period_us").and_then(|c| parse_limit(&c));
+    Some(CgroupLimits {
+        version: Version::V1,
+        memory_max: controller_file("memory", "memory.limit_in_bytes")
+            .and_then(|c| parse_limit(&c))
+            .filter(|limit| *limit < V1_UNLIMITED),
+        memory_current: controller_file("memory", "memory.usage_in_bytes")
+            .and_then(|c| parse_limit(&c)),
+        cpu_max: quota
+            .zip(period)
+            .map(|(quota, period)| (quota as u64, period)),
+        cpu_usage_usec: controller_file("cpuacct", "cpuacct.usage")
+            .and_then(|c| parse_limit(&c))
+            .map(|nanos| nanos / 1000),
+        pids_max: controller_file("pids", "pids.max").and_then(|c| parse_limit(&c)),
+        pids_current: controller_file("pids", "pids.current").and_then(|c| parse_limit(&c)),
+        path: memory_path,
+    })
+}
+
+/// Usage of every group up to `depth` levels below the hierarchy root.
+pub fn breakdown(root: &Path, depth: usize) -> Vec<CgroupUsage> {
+    let mount = root.join("sys/fs/cgroup");
+    let v2 = mount.join("cgroup.controllers").exists();
+    // On v1 the memory hierarchy decides which groups exist
+    let base = if v2 {
+        mount.clone()
+    } else {
+        mount.join("memory")
+    };
+
+    let mut groups = Vec::new();
+    let mut pending = vec![(base.clone(), 0)];
+    while let Some((dir, level)) = pending.pop() {
+        if level > 0 {
+            let relative = dir.strip_prefix(&base).unwrap_or(&dir);
+            let path = format!("/{}", relative.display());
+            groups.push(if v2 {
+                CgroupUsage {
+                    memory: read(&dir.join("memory.current")).and_then(|c| parse_limit(&c)),
+                    cpu_usage_usec: read(&dir.join("cpu.stat")).and_then(|c| parse_cpu_stat(&c)),
+                    pids: read(&dir.join("pids.current")).and_then(|c| parse_limit(&c)),
+                    path,
+                }
+            } else {
+                CgroupUsage {
+                    memory: read(&dir.join("memory.usage_in_bytes")).and_then(|c| parse_limit(&c)),
+                    cpu_usage_usec: read(
+                        &mount.join("cpuacct").join(relative).join("cpuacct.usage"),
+                    )
+                    .and_then(|c| parse_limit(&c))
+                    .map(|nanos| nanos / 1000),
+                    pids: read(&mount.join("pids").join(relative).join("pids.current"))
+                        .and_then(|c| parse_limit(&c)),
+                    path,
+                }
+            });
+        }
+        if level < depth
+            && let Ok(entries) = fs::read_dir(&dir)
+        {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                    pending.push((entry.path(), level + 1));
+                }
+            }
+        }
+    }
+    groups.sort_by(|a, b| a.path.cmp(&b.path));
+    groups
+}
+
+/// Prints per-cgroup memory, CPU and task counts, sampling CPU over a short delay.
+pub fn run_cgroups(depth: usize) {
+    let root = Path::new("/");
+    if let Some(runtime) = detect_container(root) {
+        println!(
+            "[+] Running inside {}; only our own subtree is visible",
+            runtime
+        );
+    }
+
+    let before = breakdown(root, depth);
+    let started = Instant::now();
+    thread::sleep(SAMPLE_DELAY);
+    let mut groups = breakdown(root, depth);
+    let elapsed = started.elapsed().as_micros() as f64;
+    if groups.is_empty() {
+        println!("[-] No cgroups found under /sys/fs/cgroup");
+        return;
+    }
+
+    groups.sort_by_key(|group| std::cmp::Reverse(group.memory));
+    println!("{:>10} {:>7} {:>6}  CGROUP", "MEMORY", "CPU%", "TASKS");
+    for group in &groups {
+        let cpu = before
+            .iter()
+            .find(|old| old.path == group.path)
+            .and_then(|old| Some(group.cpu_usage_usec?.saturating_sub(old.cpu_usage_usec?)))
+            .map_or("-".to_string(), |used| {
+                format!("{:.1}", used as f64 * 100.0 / elapsed)
+            });
+        println!(
+            "{:>10} {:>7} {:>6}  {}",
+            group.memory.map_or("-".to_string(), format_bytes),
+            cpu,
+            group.pids.map_or("-".to_string(), |pids| pids.to_string()),
+            group.path
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn parses_control_files() {
+        assert_eq!(parse_limit("max\n"), None);
+        assert_eq!(parse_limit("536870912\n"), Some(536870912));
+        assert_eq!(parse_cpu_max("150000 100000\n"), Some((150000, 100000)));
+        assert_eq!(parse_cpu_max("max 100000\n"), None);
+        assert_eq!(
+            parse_cpu_stat("usage_usec 9876543\nuser_usec 6000000\n"),
+            Some(9876543)
+        );
+
+        let proc_cgroup = "12:pids:/docker/abc\n4:cpu,cpuacct:/docker/abc\n0::/system.slice\n";
+        assert_eq!(
+            parse_proc_cgroup(proc_cgroup, "cpuacct").as_deref(),
+            Some("/docker/abc")
+        );
+        assert_eq!(
+            parse_proc_cgroup(proc_cgroup, "").as_deref(),
+            Some("/system.slice")
+        );
+        assert_eq!(parse_proc_cgroup(proc_cgroup, "memory"), None);
+    }
+
+    #[test]
+    fn detects_containers() {
+        assert_eq!(detect_container(&fixture("host")), None);
+        assert_eq!(detect_container(&fixture("docker-v1")), Some("docker"));
+        assert_eq!(detect_container(&fixture("k8s-v2")), Some("kubernetes"));
+    }
+
+    #[test]
+    fn reads_v1_limits_inside_namespace() {
+        let limits = read_limits(&fixture("docker-v1")).unwrap();
+        assert_eq!(limits.version, Version::V1);
+        assert_eq!(limits.path, "/docker/3f2a9c");
+        assert_eq!(limits.memory_max, Some(268435456));
+        assert_eq!(limits.memory_current, Some(104857600));
+        assert_eq!(limits.cpu_cores(), Some(0.5));
+        assert_eq!(limits.cpu_usage_usec, Some(123456789));
+        assert_eq!(limits.pids_max, Some(100));
+    }
+
+    #[test]
+    fn reads_v2_limits() {
+        let limits = read_limits(&fixture("k8s-v2")).unwrap();
+        assert_eq!(limits.version, Version::V2);
+        assert_eq!(limits.memory_max, Some(1073741824));
+        assert_eq!(limits.cpu_cores(), Some(2.0));
+        assert_eq!(limits.cpu_usage_usec, Some(9876543));
+        assert_eq!(limits.pids_current, Some(33));
+
+        let host = read_limits(&fixture("host")).unwrap();
+        assert_eq!(host.path, "/system.slice/app.service");
+        assert_eq!(host.cpu_cores(), Some(1.5));
+        assert_eq!(host.pids_max, None);
+    }
+
+    #[test]
+    fn breaks_down_host_hierarchy() {
+        let groups = breakdown(&fixture("host"), 2);
+        let paths: Vec<&str> = groups.iter().map(|group| group.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["/system.slice", "/system.slice/app.service", "/user.slice"]
+        );
+        assert_eq!(groups[1].memory, Some(123456789));
+        assert_eq!(groups[2].cpu_usage_usec, Some(5551212));
+
+        let top = breakdown(&fixture("host"), 1);
+        assert_eq!(top.len(), 2);
+    }
+
+    #[test]
+    fn breaks_down_v1_hierarchy() {
+        let groups = breakdown(&fixture("docker-v1"), 1);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].path, "/docker");
+        assert_eq!(groups[0].memory, Some(209715200));
+        assert_eq!(groups[0].cpu_usage_usec, Some(2000000));
+        assert_eq!(groups[0].pids, None);
+    }
+}
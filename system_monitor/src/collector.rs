@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cgroup::{self, CgroupLimits};
+use crate::sockets::{self, Socket};
+
+/// Raw kernel counters from one point in time. Rates and percentages are
+/// computed by comparing two snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// `cpu` (all cores) followed by `cpu0`, `cpu1`, ...
+    pub cpus: Vec<(String, CpuTimes)>,
+    pub memory: MemInfo,
+    /// Counters from `/proc/net/dev`, in file order.
+    pub interfaces: Vec<InterfaceCounters>,
+    /// Keyed by resource: `cpu`, `memory` or `io`. Empty when PSI is disabled.
+    pub pressure: Vec<(String, Pressure)>,
+    /// Limits of the cgroup we run in, if any.
+    pub cgroup: Option<CgroupLimits>,
+    /// Container runtime we run under, e.g. `docker`.
+    pub container: Option<&'static str>,
+}
+
+/// Produces snapshots for `SystemInfo`. Implemented by `ProcCollector` on
+/// Linux; tests point it at fixture trees.
+pub trait Collector {
+    fn collect(&mut self) -> Result<Snapshot, String>;
+
+    /// TCP and UDP sockets. Walking every process's fds is costly, so this
+    /// is only done on demand rather than on each refresh.
+    fn sockets(&self) -> Vec<Socket> {
+        Vec::new()
+    }
+}
+
+/// Jiffies spent in each mode, as listed in `/proc/stat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+/// Share of the interval spent in each mode, in percent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuUsage {
+    pub user: f64,
+    pub system: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+    pub idle: f64,
+}
+
+impl CpuTimes {
+    fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    pub fn usage_since(&self, previous: &CpuTimes) -> CpuUsage {
+        let total = self.total().saturating_sub(previous.total());
+        if total == 0 {
+            return CpuUsage::default();
+        }
+        let share =
+            |now: u64, before: u64| now.saturating_sub(before) as f64 * 100.0 / total as f64;
+        CpuUsage {
+            user: share(self.user + self.nice, previous.user + previous.nice),
+            system: share(self.system, previous.system),
+            iowait: share(self.iowait, previous.iowait),
+            irq: share(self.irq, previous.irq),
+            softirq: share(self.softirq, previous.softirq),
+            steal: share(self.steal, previous.steal),
+            idle: share(self.idle, previous.idle),
+        }
+    }
+}
+
+/// Selected `/proc/meminfo` fields in KB.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemInfo {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+/// Cumulative counters for one interface from `/proc/net/dev`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InterfaceCounters {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+/// One line of a `/proc/pressure/*` file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressureLine {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    /// Total stall time in microseconds.
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pressure {
+    pub some: PressureLine,
+    /// Not reported for `cpu` on older kernels.
+    pub full: Option<PressureLine>,
+}
+
+pub fn parse_stat(contents: &str) -> Vec<(String, CpuTimes)> {
+    contents
+        .lines()
+        .filter(|line| line.starts_with("cpu"))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?.to_string();
+            let values: Vec<u64> = fields.filter_map(|field| field.parse().ok()).collect();
+            // Columns after steal (guest, guest_nice) are already counted in user and nice
+            let value = |i: usize| values.get(i).copied().unwrap_or(0);
+            if values.len() < 4 {
+                return None;
+            }
+            Some((
+                name,
+                CpuTimes {
+                    user: value(0),
+                    nice: value(1),
+                    system: value(2),
+                    idle: value(3),
+                    iowait: value(4),
+                    irq: value(5),
+                    softirq: value(6),
+                    steal: value(7),
+                },
+            ))
+        })
+        .collect()
+}
+
+pub fn parse_meminfo(contents: &str) -> MemInfo {
+    let fields: HashMap<&str, u64> = contents
+        .lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let value = rest.split_whitespace().next()?.parse().ok()?;
+            Some((key, value))
+        })
+        .collect();
+    let field = |key: &str| fields.get(key).copied().unwrap_or(0);
+    let free = field("MemFree");
+    MemInfo {
+        total: field("MemTotal"),
+        free,
+        // MemAvailable is missing before Linux 3.14
+        available: fields.get("MemAvailable").copied().unwrap_or(free),
+        buffers: field("Buffers"),
+        cached: field("Cached"),
+        swap_total: field("SwapTotal"),
+        swap_free: field("SwapFree"),
+    }
+}
+
+pub fn parse_net_dev(contents: &str) -> Vec<InterfaceCounters> {
+    contents
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, counters) = line.split_once(':')?;
+            let values: Vec<u64> = counters
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect();
+            if values.len() < 16 {
+                return None;
+            }
+            Some(InterfaceCounters {
+                name: name.trim().to_string(),
+                rx_bytes: values[0],
+                rx_packets: values[1],
+                rx_errors: values[2],
+                rx_dropped: values[3],
+                tx_bytes: values[8],
+                tx_packets: values[9],
+                tx_errors: values[10],
+                tx_dropped: values[11],
+            })
+        })
+        .collect()
+}
+
+pub fn parse_pressure(contents: &str) -> Option<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next()?;
+        let mut parsed = PressureLine::default();
+        for field in fields {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "avg10" => parsed.avg10 = value.parse().ok()?,
+                "avg60" => parsed.avg60 = value.parse().ok()?,
+                "avg300" => parsed.avg300 = value.parse().ok()?,
+                "total" => parsed.total = value.parse().ok()?,
+                _ => {}
+            }
+        }
+        match kind {
+            "some" => some = Some(parsed),
+            "full" => full = Some(parsed),
+            _ => {}
+        }
+    }
+    Some(Pressure { some: some?, full })
+}
+
+/// Reads `/proc` and `/sys/fs/cgroup`. The root is configurable so the
+/// collector can run against captured trees.
+pub struct ProcCollector {
+    root: PathBuf,
+}
+
+impl ProcCollector {
+    pub fn new() -> Self {
+        ProcCollector::with_root("/")
+    }
+
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        ProcCollector { root: root.into() }
+    }
+
+    fn read(&self, relative: &str) -> Result<String, String> {
+        let path = self.root.join(relative);
+        fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl Collector for ProcCollector {
+    fn collect(&mut self) -> Result<Snapshot, String> {
+        let stat = self.read("proc/stat")?;
+        let meminfo = self.read("proc/meminfo")?;
+        let net_dev = self.read("proc/net/dev").unwrap_or_default();
+        let pressure = ["cpu", "memory", "io"]
+            .iter()
+            .filter_map(|resource| {
+                let contents = self.read(&format!("proc/pressure/{}", resource)).ok()?;
+                Some((resource.to_string(), parse_pressure(&contents)?))
+            })
+            .collect();
+
+        Ok(Snapshot {
+            cpus: parse_stat(&stat),
+            memory: parse_meminfo(&meminfo),
+            interfaces: parse_net_dev(&net_dev),
+            pressure,
+            cgroup: cgroup::read_limits(&self.root),
+            container: cgroup::detect_container(&self.root),
+        })
+    }
+
+    fn sockets(&self) -> Vec<Socket> {
+        sockets::read_sockets(&self.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn parses_stat() {
+        let cpus = parse_stat(include_str!("../tests/fixtures/host/proc/stat"));
+        let names: Vec<&str> = cpus.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["cpu", "cpu0", "cpu1"]);
+        assert_eq!(cpus[1].1.iowait, 1830);
+        assert_eq!(cpus[1].1.steal, 214);
+        assert_eq!(cpus[2].1.softirq, 391);
+    }
+
+    #[test]
+    fn computes_mode_shares() {
+        let before = CpuTimes {
+            user: 100,
+            idle: 800,
+            ..Default::default()
+        };
+        let after = CpuTimes {
+            user: 150,
+            idle: 1100,
+            iowait: 40,
+            steal: 10,
+            ..Default::default()
+        };
+        let usage = after.usage_since(&before);
+        assert_eq!(usage.user, 12.5);
+        assert_eq!(usage.idle, 75.0);
+        assert_eq!(usage.iowait, 10.0);
+        assert_eq!(usage.steal, 2.5);
+    }
+
+    #[test]
+    fn parses_meminfo() {
+        let memory = parse_meminfo(include_str!("../tests/fixtures/host/proc/meminfo"));
+        assert_eq!(memory.total, 16303412);
+        assert_eq!(memory.available, 11052920);
+        assert_eq!(memory.swap_free, 2097148);
+        assert_eq!(memory.cached, 4702112);
+
+        // Kernels before 3.14 have no MemAvailable
+        let legacy = parse_meminfo("MemTotal: 1000 kB\nMemFree: 400 kB\n");
+        assert_eq!(legacy.available, 400);
+    }
+
+    #[test]
+    fn parses_net_dev() {
+        let interfaces = parse_net_dev(include_str!("../tests/fixtures/host/proc/net/dev"));
+        assert_eq!(interfaces.len(), 2);
+        let eth0 = &interfaces[1];
+        assert_eq!(eth0.name, "eth0");
+        assert_eq!(eth0.rx_bytes, 9182736455);
+        assert_eq!(eth0.rx_dropped, 48);
+        assert_eq!(eth0.tx_packets, 2210394);
+        assert_eq!(eth0.tx_dropped, 3);
+    }
+
+    #[test]
+    fn parses_pressure() {
+        let io = parse_pressure(include_str!("../tests/fixtures/host/proc/pressure/io")).unwrap();
+        assert_eq!(io.some.avg10, 1.52);
+        assert_eq!(io.full.unwrap().total, 2262954);
+
+        let cpu =
+            parse_pressure("some avg10=0.55 avg60=0.75 avg300=1.54 total=38071415\n").unwrap();
+        assert!(cpu.full.is_none());
+        assert!(parse_pressure("").is_none());
+    }
+
+    #[test]
+    fn collects_fixture_tree() {
+        let mut collector = ProcCollector::with_root(format!("{}/host", FIXTURES));
+        let snapshot = collector.collect().unwrap();
+        assert_eq!(snapshot.cpus.len(), 3);
+        assert_eq!(snapshot.pressure.len(), 3);
+
+        assert_eq!(snapshot.container, None);
+
+        let cgroup = snapshot.cgroup.unwrap();
+        assert_eq!(cgroup.memory_max, Some(536870912));
+        assert_eq!(cgroup.memory_current, Some(123456789));
+    }
+}
@@ -0,0 +1,319 @@
+use glib::ControlFlow;
+use gtk::prelude::*;
+use gtk::{
+    Application, ApplicationWindow, DrawingArea, Frame, Grid, Label, Orientation, ProgressBar,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::alerts::{self, Alert, AlertConfig, Sample};
+use crate::history::{self, HistoryStore};
+use crate::system_info::{SystemInfo, format_bytes};
+
+const REFRESH_SECS: u32 = 1;
+const HISTORY_LEN: usize = 60;
+const CORES_PER_ROW: usize = 4;
+
+type Rgb = (f64, f64, f64);
+
+/// Samples kept for the graphs, newest last.
+#[derive(Default)]
+struct History {
+    cores: Vec<VecDeque<f64>>,
+    networks: HashMap<String, (VecDeque<f64>, VecDeque<f64>)>,
+}
+
+/// The last `HISTORY_LEN` seconds of a recorded metric, so graphs survive restarts.
+fn seed(store: &HistoryStore, metric: &str) -> VecDeque<f64> {
+    let since = chrono::Local::now().timestamp() - HISTORY_LEN as i64;
+    let points = store.query(metric, since);
+    // The query includes both ends, so it can return one point too many
+    let skip = points.len().saturating_sub(HISTORY_LEN);
+    points.iter().skip(skip).map(|point| point.v).collect()
+}
+
+fn push_sample(series: &mut VecDeque<f64>, value: f64) {
+    while series.len() >= HISTORY_LEN {
+        series.pop_front();
+    }
+    series.push_back(value);
+}
+
+pub fn run(alert_config: Option<AlertConfig>, history_path: String) {
+    let application = Application::new(Some("com.example.system_monitor"), Default::default());
+    application.connect_activate(move |application| {
+        build_ui(application, alert_config.clone(), history_path.clone())
+    });
+    // Our own flags are not GTK options, so only pass the program name on
+    let program = std::env::args().next().unwrap_or_default();
+    application.run_with_args(&[program]);
+}
+
+fn build_ui(application: &Application, alert_config: Option<AlertConfig>, history_path: String) {
+    let window = ApplicationWindow::new(application);
+    window.set_title("System Monitor");
+    window.set_default_size(720, 760);
+
+    let system_info = Rc::new(RefCell::new(SystemInfo::new()));
+    let history = Rc::new(RefCell::new(History::default()));
+    let mut store = HistoryStore::load(&history_path).unwrap_or_else(|e| {
+        eprintln!("[-] Could not load history: {}", e);
+        HistoryStore::default()
+    });
+
+    let vbox = gtk::Box::new(Orientation::Vertical, 10);
+    vbox.set_border_width(10);
+
+    // Per-core CPU graphs
+    let cpu_label = Label::new(Some("CPU Usage:"));
+    cpu_label.set_xalign(0.0);
+    let cpu_grid = Grid::new();
+    cpu_grid.set_row_spacing(6);
+    cpu_grid.set_column_spacing(6);
+    cpu_grid.set_column_homogeneous(true);
+    let core_count = system_info.borrow().get_cpu_usage_per_core().len();
+    history.borrow_mut().cores = (0..core_count)
+        .map(|core| seed(&store, &format!("cpu.core{}", core)))
+        .collect();
+    let mut core_labels = Vec::new();
+    let mut graphs = Vec::new();
+    for core in 0..core_count {
+        let cell = gtk::Box::new(Orientation::Vertical, 2);
+        let label = Label::new(Some(&format!("cpu{}", core)));
+        let area = DrawingArea::new();
+        area.set_size_request(-1, 60);
+        let graph_history = history.clone();
+        area.connect_draw(move |area, cr| {
+            let history = graph_history.borrow();
+            draw_graph(area, cr, &[(&history.cores[core], (0.2, 0.6, 1.0))], 100.0);
+            glib::Propagation::Proceed
+        });
+        cell.pack_start(&label, false, false, 0);
+        cell.pack_start(&area, true, true, 0);
+        cpu_grid.attach(
+            &cell,
+            (core % CORES_PER_ROW) as i32,
+            (core / CORES_PER_ROW) as i32,
+            1,
+            1,
+        );
+        core_labels.push(label);
+        graphs.push(area);
+    }
+    let cpu_box = gtk::Box::new(Orientation::Vertical, 6);
+    cpu_box.set_border_width(6);
+    cpu_box.pack_start(&cpu_label, false, false, 0);
+    cpu_box.pack_start(&cpu_grid, false, false, 0);
+    vbox.pack_start(&framed("CPU", &cpu_box), false, false, 0);
+
+    // Memory and swap gauges
+    let memory_bar = gauge();
+    let swap_bar = gauge();
+    let memory_box = gtk::Box::new(Orientation::Vertical, 6);
+    memory_box.set_border_width(6);
+    memory_box.pack_start(&memory_bar, false, false, 0);
+    memory_box.pack_start(&swap_bar, false, false, 0);
+    vbox.pack_start(&framed("Memory", &memory_box), false, false, 0);
+
+    // Per-disk usage bars
+    let disk_box = gtk::Box::new(Orientation::Vertical, 6);
+    disk_box.set_border_width(6);
+    let disk_bars: Vec<ProgressBar> = system_info
+        .borrow()
+        .get_disk_usage()
+        .iter()
+        .map(|_| {
+            let bar = gauge();
+            disk_box.pack_start(&bar, false, false, 0);
+            bar
+        })
+        .collect();
+    vbox.pack_start(&framed("Disks", &disk_box), false, false, 0);
+
+    // Per-interface throughput graphs
+    let network_box = gtk::Box::new(Orientation::Vertical, 6);
+    network_box.set_border_width(6);
+    let mut network_labels = HashMap::new();
+    for (name, _, _) in system_info.borrow().get_network_throughput() {
+        history.borrow_mut().networks.insert(
+            name.clone(),
+            (
+                seed(&store, &format!("net_rx.{}", name)),
+                seed(&store, &format!("net_tx.{}", name)),
+            ),
+        );
+        let label = Label::new(Some(&name));
+        label.set_xalign(0.0);
+        let area = DrawingArea::new();
+        area.set_size_request(-1, 50);
+        let graph_history = history.clone();
+        let interface = name.clone();
+        area.connect_draw(move |area, cr| {
+            let history = graph_history.borrow();
+            let (rx, tx) = &history.networks[&interface];
+            let peak = rx.iter().chain(tx.iter()).cloned().fold(1024.0, f64::max);
+            draw_graph(
+                area,
+                cr,
+                &[(rx, (0.2, 0.8, 0.3)), (tx, (1.0, 0.5, 0.1))],
+                peak,
+            );
+            glib::Propagation::Proceed
+        });
+        network_box.pack_start(&label, false, false, 0);
+        network_box.pack_start(&area, false, false, 0);
+        network_labels.insert(name, label);
+        graphs.push(area);
+    }
+    vbox.pack_start(&framed("Network", &network_box), false, false, 0);
+
+    let scrolled = gtk::ScrolledWindow::new(gtk::Adjustment::NONE, gtk::Adjustment::NONE);
+    scrolled.add(&vbox);
+    window.add(&scrolled);
+    window.show_all();
+
+    let mut alert_engine = alert_config.as_ref().map(alerts::engine_from_config);
+    let scanned = alert_config.as_ref().and_then(alerts::spawn_scanner);
+    let application = application.clone();
+    let show_desktop = move |alert: &Alert| {
+        let notification =
+            gtk::gio::Notification::new(&format!("{} {:?}", alert.rule, alert.state));
+        notification.set_body(Some(&alert.message));
+        application.send_notification(None, &notification);
+    };
+
+    let mut ticks = 0;
+    glib::timeout_add_seconds_local(REFRESH_SECS, move || {
+        let mut system_info = system_info.borrow_mut();
+        system_info.refresh();
+        let mut history = history.borrow_mut();
+
+        store.record_system(&system_info, chrono::Local::now().timestamp());
+        ticks += REFRESH_SECS as u64;
+        if ticks % history::SAVE_EVERY_SECS == 0
+            && let Err(e) = store.save(&history_path)
+        {
+            eprintln!("[-] Could not save history: {}", e);
+        }
+
+        cpu_label.set_text(&format!("CPU Usage: {:.2}%", system_info.get_cpu_usage()));
+        for (core, usage) in system_info.get_cpu_usage_per_core().into_iter().enumerate() {
+            if let (Some(series), Some(label)) =
+                (history.cores.get_mut(core), core_labels.get(core))
+            {
+                push_sample(series, usage as f64);
+                label.set_text(&format!("cpu{} {:.0}%", core, usage));
+            }
+        }
+
+        let (used_memory, total_memory) = system_info.get_memory_usage();
+        set_gauge(
+            &memory_bar,
+            "Memory",
+            used_memory * 1024,
+            total_memory * 1024,
+        );
+        let (used_swap, total_swap) = system_info.get_swap_usage();
+        set_gauge(&swap_bar, "Swap", used_swap * 1024, total_swap * 1024);
+
+        for (bar, (name, total, available)) in disk_bars.iter().zip(system_info.get_disk_usage()) {
+            set_gauge(bar, &name, total.saturating_sub(available), total);
+        }
+
+        for (name, rx, tx) in system_info.get_network_throughput() {
+            if let (Some((rx_series, tx_series)), Some(label)) =
+                (history.networks.get_mut(&name), network_labels.get(&name))
+            {
+                push_sample(rx_series, rx);
+                push_sample(tx_series, tx);
+                label.set_text(&format!(
+                    "{}  rx {}/s  tx {}/s",
+                    name,
+                    format_bytes(rx as u64),
+                    format_bytes(tx as u64)
+                ));
+            }
+        }
+
+        for graph in &graphs {
+            graph.queue_draw();
+        }
+
+        if let (Some(engine), Some(config)) = (alert_engine.as_mut(), alert_config.as_ref()) {
+            let mut fired = engine.evaluate(&Sample::from_system(&system_info), Instant::now());
+            if let Some(scanned) = &scanned {
+                let devices: Vec<_> = scanned.lock().unwrap().drain(..).collect();
+                fired.extend(engine.observe_devices(&devices));
+            }
+            for alert in &fired {
+                alerts::notify(alert, &config.sinks, Some(&show_desktop));
+            }
+        }
+        ControlFlow::Continue
+    });
+}
+
+fn framed(title: &str, child: &impl IsA<gtk::Widget>) -> Frame {
+    let frame = Frame::new(Some(title));
+    frame.add(child);
+    frame
+}
+
+fn gauge() -> ProgressBar {
+    let bar = ProgressBar::new();
+    bar.set_show_text(true);
+    bar
+}
+
+fn set_gauge(bar: &ProgressBar, name: &str, used: u64, total: u64) {
+    let fraction = if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64
+    };
+    bar.set_fraction(fraction);
+    bar.set_text(Some(&format!(
+        "{}: {} / {} ({:.0}%)",
+        name,
+        format_bytes(used),
+        format_bytes(total),
+        fraction * 100.0
+    )));
+}
+
+/// Draws each series as a line scaled to `max`, oldest sample on the left.
+fn draw_graph(
+    area: &DrawingArea,
+    cr: &gtk::cairo::Context,
+    series: &[(&VecDeque<f64>, Rgb)],
+    max: f64,
+) {
+    let width = area.allocated_width() as f64;
+    let height = area.allocated_height() as f64;
+
+    cr.set_source_rgb(0.1, 0.1, 0.1);
+    cr.rectangle(0.0, 0.0, width, height);
+    cr.fill().ok();
+
+    let step = width / (HISTORY_LEN - 1) as f64;
+    for (samples, (r, g, b)) in series {
+        if samples.is_empty() {
+            continue;
+        }
+        cr.set_source_rgb(*r, *g, *b);
+        cr.set_line_width(1.5);
+        let offset = HISTORY_LEN.saturating_sub(samples.len());
+        for (i, value) in samples.iter().enumerate() {
+            let x = (offset + i) as f64 * step;
+            let y = height - (value / max).min(1.0) * height;
+            if i == 0 {
+                cr.move_to(x, y);
+            } else {
+                cr.line_to(x, y);
+            }
+        }
+        cr.stroke().ok();
+    }
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::report::csv_field;
+use crate::system_info::SystemInfo;
+
+pub const DEFAULT_HISTORY: &str = "history.json";
+
+/// (resolution in seconds, points kept): 1h of seconds, 24h of minutes, 30d of hours.
+const TIERS: [(i64, usize); 3] = [(1, 3600), (60, 1440), (3600, 720)];
+pub const SAVE_EVERY_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Point {
+    /// Unix time of the start of the bucket.
+    pub t: i64,
+    pub v: f64,
+}
+
+/// A bounded ring buffer at one resolution. Samples are averaged into the
+/// current bucket and pushed once a sample for a later bucket arrives.
+#[derive(Debug, Serialize, Deserialize)]
+struct Tier {
+    resolution: i64,
+    capacity: usize,
+    points: VecDeque<Point>,
+    bucket: Option<i64>,
+    sum: f64,
+    count: u32,
+}
+
+impl Tier {
+    fn new(resolution: i64, capacity: usize) -> Self {
+        Tier {
+            resolution,
+            capacity,
+            points: VecDeque::new(),
+            bucket: None,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, t: i64, v: f64) {
+        let bucket = t - t.rem_euclid(self.resolution);
+        match self.bucket {
+            Some(current) if current == bucket => {}
+            Some(current) if bucket < current => return, // clock went backwards
+            _ => {
+                self.flush();
+                self.bucket = Some(bucket);
+            }
+        }
+        self.sum += v;
+        self.count += 1;
+    }
+
+    fn flush(&mut self) {
+        if let Some(bucket) = self.bucket.take()
+            && self.count > 0
+        {
+            if self.points.len() == self.capacity {
+                self.points.pop_front();
+            }
+            self.points.push_back(Point {
+                t: bucket,
+                v: self.sum / self.count as f64,
+            });
+        }
+        self.sum = 0.0;
+        self.count = 0;
+    }
+
+    /// Completed points plus the partially filled bucket.
+    fn points_since(&self, since: i64) -> Vec<Point> {
+        let mut points: Vec<Point> = self
+            .points
+            .iter()
+            .filter(|point| point.t >= since)
+            .copied()
+            .collect();
+        if let Some(bucket) = self.bucket
+            && self.count > 0
+            && bucket >= since
+        {
+            points.push(Point {
+                t: bucket,
+                v: self.sum / self.count as f64,
+            });
+        }
+        points
+    }
+
+    fn oldest(&self) -> Option<i64> {
+        self.points.front().map(|point| point.t).or(self.bucket)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Series {
+    tiers: Vec<Tier>,
+}
+
+impl Series {
+    fn new() -> Self {
+        Series {
+            tiers: TIERS
+                .iter()
+                .map(|(resolution, capacity)| Tier::new(*resolution, *capacity))
+                .collect(),
+        }
+    }
+
+    /// Uses the finest tier that still reaches back to `since`.
+    fn query(&self, since: i64) -> Vec<Point> {
+        let tier = self
+            .tiers
+            .iter()
+            .find(|tier| tier.oldest().is_some_and(|oldest| oldest <= since))
+            .unwrap_or(&self.tiers[self.tiers.len() - 1]);
+        tier.points_since(since)
+    }
+}
+
+/// Per-metric time series, e.g. `cpu`, `cpu.core0`, `memory`, `disk_used./var`, `net_rx.eth0`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HistoryStore {
+    series: BTreeMap<String, Series>,
+}
+
+impl HistoryStore {
+    pub fn load(path: &str) -> Result<Self, String> {
+        if !Path::new(path).exists() {
+            return Ok(HistoryStore::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, contents).map_err(|e| format!("{}: {}", tmp, e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn record(&mut self, metric: &str, t: i64, v: f64) {
+        let series = self
+            .series
+            .entry(metric.to_string())
+            .or_insert_with(Series::new);
+        for tier in &mut series.tiers {
+            tier.add(t, v);
+        }
+    }
+
+    pub fn record_system(&mut self, system_info: &SystemInfo, t: i64) {
+        self.record("cpu", t, system_info.get_cpu_usage() as f64);
+        for (core, usage) in system_info.get_cpu_usage_per_core().iter().enumerate() {
+            self.record(&format!("cpu.core{}", core), t, *usage as f64);
+        }
+        if let Some((_, usage)) = system_info.get_cpu_breakdown().first() {
+            self.record("cpu.iowait", t, usage.iowait);
+            self.record("cpu.steal", t, usage.steal);
+        }
+        let (used_memory, total_memory) = system_info.get_memory_usage();
+        self.record("memory", t, percent(used_memory, total_memory));
+        let (used_swap, total_swap) = system_info.get_swap_usage();
+        self.record("swap", t, percent(used_swap, total_swap));
+        for (mount, total, available) in system_info.get_mount_usage() {
+            let used = total.saturating_sub(available);
+            self.record(&format!("disk_used.{}", mount), t, percent(used, total));
+        }
+        let interfaces = system_info.get_interface_stats();
+        if interfaces.is_empty() {
+            for (name, rx, tx) in system_info.get_network_throughput() {
+                self.record(&format!("net_rx.{}", name), t, rx);
+                self.record(&format!("net_tx.{}", name), t, tx);
+            }
+        }
+        for stats in interfaces {
+            let name = &stats.counters.name;
+            self.record(&format!("net_rx.{}", name), t, stats.rx_bytes_per_sec);
+            self.record(&format!("net_tx.{}", name), t, stats.tx_bytes_per_sec);
+            self.record(
+                &format!("net_rx_packets.{}", name),
+                t,
+                stats.rx_packets_per_sec,
+            );
+            self.record(
+                &format!("net_tx_packets.{}", name),
+                t,
+                stats.tx_packets_per_sec,
+            );
+        }
+        self.record("processes", t, system_info.get_processes().len() as f64);
+    }
+
+    pub fn query(&self, metric: &str, since: i64) -> Vec<Point> {
+        self.series
+            .get(metric)
+            .map(|series| series.query(since))
+            .unwrap_or_default()
+    }
+
+    pub fn metrics(&self) -> impl Iterator<Item = &String> {
+        self.series.keys()
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Parses `90s`, `15m`, `1h` or `7d` into seconds.
+pub fn parse_since(text: &str) -> Option<i64> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    Some(number * unit)
+}
+
+/// Samples every second and saves the store every minute.
+pub fn run_recorder(path: &str) {
+    let mut store = match HistoryStore::load(path) {
+        Ok(store) => store,
+        Err(e) => {
+            println!("[-] Could not load history: {}", e);
+            return;
+        }
+    };
+    println!("[+] Recording history to {}", path);
+
+    let mut system_info = SystemInfo::new();
+    let mut ticks = 0;
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        system_info.refresh();
+        store.record_system(&system_info, Local::now().timestamp());
+        ticks += 1;
+        if ticks % SAVE_EVERY_SECS == 0
+            && let Err(e) = store.save(path)
+        {
+            eprintln!("[-] Could not save history: {}", e);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    timestamp: DateTime<Local>,
+    metric: &'a str,
+    value: f64,
+}
+
+pub fn export(
+    path: &str,
+    since: i64,
+    format: &str,
+    metric: Option<&str>,
+) -> Result<String, String> {
+    let store = HistoryStore::load(path)?;
+    let start = Local::now().timestamp() - since;
+
+    let mut rows = Vec::new();
+    for name in store.metrics() {
+        if metric.is_some_and(|wanted| wanted != name) {
+            continue;
+        }
+        for point in store.query(name, start) {
+            if let Some(timestamp) = Local.timestamp_opt(point.t, 0).single() {
+                rows.push(ExportRow {
+                    timestamp,
+                    metric: name,
+                    value: point.v,
+                });
+            }
+        }
+    }
+
+    match format {
+        "json" => serde_json::to_string_pretty(&rows).map_err(|e| e.to_string()),
+        "csv" => {
+            let mut out = String::from("timestamp,metric,value\n");
+            for row in rows {
+                out.push_str(&format!(
+                    "{},{},{}\n",
+                    row.timestamp.to_rfc3339(),
+                    csv_field(row.metric),
+                    row.value
+                ));
+            }
+            Ok(out)
+        }
+        _ => Err(format!("Unknown format {}. Use csv or json", format)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsamples_into_minute_and_hour_tiers() {
+        let mut store = HistoryStore::default();
+        for t in 0..120 {
+            store.record("cpu", t, if t < 60 { 10.0 } else { 30.0 });
+        }
+
+        let series = &store.series["cpu"];
+        assert_eq!(series.tiers[0].points.len(), 119);
+        let minutes = series.tiers[1].points_since(0);
+        assert_eq!(minutes.len(), 2);
+        assert_eq!(minutes[0].v, 10.0);
+        assert_eq!(minutes[1].v, 30.0);
+        assert_eq!(series.tiers[2].points_since(0)[0].v, 20.0);
+    }
+
+    #[test]
+    fn second_tier_is_bounded() {
+        let mut store = HistoryStore::default();
+        for t in 0..5000 {
+            store.record("cpu", t, 1.0);
+        }
+        assert_eq!(store.series["cpu"].tiers[0].points.len(), 3600);
+        // Older than the second tier reaches, so minutes are used
+        assert_eq!(store.query("cpu", 0).len(), 84);
+    }
+
+    #[test]
+    fn parses_since() {
+        assert_eq!(parse_since("90s"), Some(90));
+        assert_eq!(parse_since("1h"), Some(3600));
+        assert_eq!(parse_since("7d"), Some(604800));
+        assert_eq!(parse_since("h"), None);
+        assert_eq!(parse_since("10"), None);
+    }
+}
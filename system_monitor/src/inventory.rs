@@ -0,0 +1,350 @@
+use chrono::{DateTime, Local};
+use pnet::util::MacAddr;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::probe::{self, ProbeConfig, Service};
+use crate::scanner;
+use crate::webhook;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownDevice {
+    pub mac: String,
+    pub ip: Ipv4Addr,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    pub online: bool,
+    /// Open TCP ports from the last probe, if probing is enabled.
+    #[serde(default)]
+    pub services: Vec<Service>,
+}
+
+/// Devices we have ever seen, keyed by MAC address.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    pub devices: BTreeMap<String, KnownDevice>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    NewDevice {
+        mac: String,
+        ip: Ipv4Addr,
+        timestamp: DateTime<Local>,
+    },
+    DeviceDisappeared {
+        mac: String,
+        ip: Ipv4Addr,
+        last_seen: DateTime<Local>,
+        timestamp: DateTime<Local>,
+    },
+    DeviceReturned {
+        mac: String,
+        ip: Ipv4Addr,
+        timestamp: DateTime<Local>,
+    },
+    /// A known MAC answered from a different IP (DHCP churn).
+    IpChanged {
+        mac: String,
+        old_ip: Ipv4Addr,
+        new_ip: Ipv4Addr,
+        timestamp: DateTime<Local>,
+    },
+    /// A known IP is answered by a different MAC (possible ARP spoofing).
+    MacChanged {
+        ip: Ipv4Addr,
+        old_mac: String,
+        new_mac: String,
+        timestamp: DateTime<Local>,
+    },
+    /// The set of open ports or their banners changed.
+    ServicesChanged {
+        mac: String,
+        ip: Ipv4Addr,
+        services: Vec<Service>,
+        timestamp: DateTime<Local>,
+    },
+}
+
+pub struct DaemonConfig {
+    pub range: String,
+    pub interface: String,
+    pub interval: Duration,
+    pub store: String,
+    pub webhook: Option<String>,
+    pub probe: Option<ProbeConfig>,
+}
+
+impl Inventory {
+    pub fn load(path: &str) -> Result<Self, String> {
+        if !Path::new(path).exists() {
+            return Ok(Inventory::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        // Write next to the store and rename so a crash never leaves half a file
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, contents).map_err(|e| format!("{}: {}", tmp, e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Merges one scan into the inventory and returns what changed.
+    pub fn apply_scan(&mut self, seen: &[(Ipv4Addr, MacAddr)], now: DateTime<Local>) -> Vec<Event> {
+        let mut events = Vec::new();
+        // Where each MAC answered in this sweep, so devices that swapped or
+        // were reassigned addresses are not mistaken for spoofing
+        let scanned: BTreeMap<String, Ipv4Addr> = seen
+            .iter()
+            .map(|(ip, mac)| (mac.to_string(), *ip))
+            .collect();
+
+        for (ip, mac) in seen {
+            let mac = mac.to_string();
+
+            let previous_owner = self
+                .devices
+                .values()
+                .find(|device| {
+                    device.online
+                        && device.ip == *ip
+                        && device.mac != mac
+                        && !scanned.contains_key(&device.mac)
+                })
+                .map(|device| device.mac.clone());
+            if let Some(old_mac) = previous_owner {
+                events.push(Event::MacChanged {
+                    ip: *ip,
+                    old_mac: old_mac.clone(),
+                    new_mac: mac.clone(),
+                    timestamp: now,
+                });
+                // The old owner no longer holds this address
+                if let Some(old) = self.devices.get_mut(&old_mac) {
+                    old.online = false;
+                }
+            }
+
+            match self.devices.get_mut(&mac) {
+                Some(device) => {
+                    if device.ip != *ip {
+                        events.push(Event::IpChanged {
+                            mac: mac.clone(),
+                            old_ip: device.ip,
+                            new_ip: *ip,
+                            timestamp: now,
+                        });
+                        device.ip = *ip;
+                    }
+                    if !device.online {
+                        events.push(Event::DeviceReturned {
+                            mac: mac.clone(),
+                            ip: *ip,
+                            timestamp: now,
+                        });
+                        device.online = true;
+                    }
+                    device.last_seen = now;
+                }
+                None => {
+                    events.push(Event::NewDevice {
+                        mac: mac.clone(),
+                        ip: *ip,
+                        timestamp: now,
+                    });
+                    self.devices.insert(
+                        mac.clone(),
+                        KnownDevice {
+                            mac,
+                            ip: *ip,
+                            first_seen: now,
+                            last_seen: now,
+                            online: true,
+                            services: Vec::new(),
+                        },
+                    );
+                }
+            }
+        }
+
+        for device in self.devices.values_mut() {
+            if device.online && device.last_seen != now {
+                device.online = false;
+                events.push(Event::DeviceDisappeared {
+                    mac: device.mac.clone(),
+                    ip: device.ip,
+                    last_seen: device.last_seen,
+                    timestamp: now,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Probes every online device and records its services.
+    pub fn apply_probe(&mut self, config: &ProbeConfig, now: DateTime<Local>) -> Vec<Event> {
+        let online: Vec<(String, IpAddr)> = self
+            .devices
+            .values()
+            .filter(|device| device.online)
+            .map(|device| (device.mac.clone(), IpAddr::V4(device.ip)))
+            .collect();
+        let hosts: Vec<IpAddr> = online.iter().map(|(_, ip)| *ip).collect();
+
+        let mut events = Vec::new();
+        for ((mac, _), (_, services)) in online.iter().zip(probe::probe_hosts(&hosts, config)) {
+            if let Some(device) = self.devices.get_mut(mac)
+                && device.services != services
+            {
+                events.push(Event::ServicesChanged {
+                    mac: mac.clone(),
+                    ip: device.ip,
+                    services: services.clone(),
+                    timestamp: now,
+                });
+                device.services = services;
+            }
+        }
+        events
+    }
+}
+
+/// Rescans forever, persisting the inventory and emitting one JSON event per line.
+pub fn run_daemon(config: &DaemonConfig) {
+    let mut inventory = match Inventory::load(&config.store) {
+        Ok(inventory) => inventory,
+        Err(e) => {
+            eprintln!("[-] Could not load inventory: {}", e);
+            return;
+        }
+    };
+
+    eprintln!(
+        "[+] Monitoring {} on {} every {}s, store: {}",
+        config.range,
+        config.interface,
+        config.interval.as_secs(),
+        config.store
+    );
+
+    loop {
+        match scanner::scan(&config.range, &config.interface) {
+            Ok(seen) => {
+                let now = Local::now();
+                let mut events = inventory.apply_scan(&seen, now);
+                if let Some(probe) = &config.probe {
+                    events.extend(inventory.apply_probe(probe, now));
+                }
+                for event in events {
+                    emit(&event, config.webhook.as_deref());
+                }
+                if let Err(e) = inventory.save(&config.store) {
+                    eprintln!("[-] Could not save inventory: {}", e);
+                }
+            }
+            Err(e) => eprintln!("[-] Scan failed: {}", e),
+        }
+        thread::sleep(config.interval);
+    }
+}
+
+fn emit(event: &Event, webhook: Option<&str>) {
+    let json = match serde_json::to_string(event) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("[-] Could not encode event: {}", e);
+            return;
+        }
+    };
+    println!("{}", json);
+
+    if let Some(url) = webhook
+        && let Err(e) = webhook::post_json(url, &json)
+    {
+        eprintln!("[-] {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const A: MacAddr = MacAddr(2, 0, 0, 0, 0, 0xa);
+    const B: MacAddr = MacAddr(2, 0, 0, 0, 0, 0xb);
+    const C: MacAddr = MacAddr(2, 0, 0, 0, 0, 0xc);
+
+    fn ip(last: u8) -> Ipv4Addr {
+        Ipv4Addr::new(192, 168, 1, last)
+    }
+
+    /// The event names, for comparing without timestamps.
+    fn kinds(events: &[Event]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap()["event"].to_string())
+            .map(|kind| kind.trim_matches('"').to_string())
+            .collect()
+    }
+
+    fn scanned(start: DateTime<Local>) -> Inventory {
+        let mut inventory = Inventory::default();
+        let events = inventory.apply_scan(&[(ip(10), A), (ip(11), B)], start);
+        assert_eq!(kinds(&events), ["new_device", "new_device"]);
+        inventory
+    }
+
+    #[test]
+    fn follows_swapped_addresses() {
+        let start = Local::now();
+        let mut inventory = scanned(start);
+        let events =
+            inventory.apply_scan(&[(ip(10), B), (ip(11), A)], start + Duration::minutes(1));
+        assert_eq!(kinds(&events), ["ip_changed", "ip_changed"]);
+        assert_eq!(inventory.devices[&A.to_string()].ip, ip(11));
+        assert!(inventory.devices.values().all(|device| device.online));
+    }
+
+    #[test]
+    fn reports_new_departed_and_returning_devices() {
+        let start = Local::now();
+        let mut inventory = scanned(start);
+        let events = inventory.apply_scan(&[(ip(10), A)], start + Duration::minutes(1));
+        assert_eq!(kinds(&events), ["device_disappeared"]);
+        assert!(!inventory.devices[&B.to_string()].online);
+
+        let later = start + Duration::minutes(2);
+        let events = inventory.apply_scan(&[(ip(10), A), (ip(11), B), (ip(12), C)], later);
+        assert_eq!(kinds(&events), ["device_returned", "new_device"]);
+        assert_eq!(inventory.devices[&C.to_string()].first_seen, later);
+        assert_eq!(inventory.devices[&B.to_string()].first_seen, start);
+    }
+
+    #[test]
+    fn reports_an_address_taken_by_another_mac() {
+        let start = Local::now();
+        let mut inventory = scanned(start);
+        let events =
+            inventory.apply_scan(&[(ip(10), C), (ip(11), B)], start + Duration::minutes(1));
+        assert_eq!(kinds(&events), ["mac_changed", "new_device"]);
+        match &events[0] {
+            Event::MacChanged {
+                old_mac, new_mac, ..
+            } => {
+                assert_eq!((old_mac, new_mac), (&A.to_string(), &C.to_string()));
+            }
+            _ => unreachable!(),
+        }
+        assert!(!inventory.devices[&A.to_string()].online);
+    }
+}
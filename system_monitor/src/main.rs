@@ -1,222 +1,358 @@
-use pnet::datalink::Channel::Ethernet;
-use pnet::datalink::{self, NetworkInterface};
-use pnet::packet::arp::{ArpHardwareTypes, ArpOperation, ArpPacket, MutableArpPacket};
-use pnet::packet::ethernet::{EtherTypes, MutableEthernetPacket};
-use pnet::packet::{MutablePacket, Packet};
-use pnet::util::MacAddr;
+mod alerts;
+mod cgroup;
+mod collector;
+#[cfg(feature = "gui")]
+mod gui;
+mod history;
+mod inventory;
+mod metrics;
+mod oui;
+mod passive;
+mod probe;
+mod processes;
+mod report;
+mod resolve;
+mod scanner;
+mod sockets;
+mod system_info;
+#[cfg(feature = "tui")]
+mod tui;
+mod watcher;
+mod webhook;
+
+use inventory::DaemonConfig;
+use metrics::ExporterConfig;
+use probe::ProbeConfig;
+use report::OutputFormat;
+use resolve::ResolveConfig;
 use std::env;
-use std::net::{IpAddr, Ipv4Addr};
-use std::process::Command;
-use std::str::FromStr;
-use std::thread;
+use std::process;
 use std::time::Duration;
 
+const DEFAULT_INTERVAL_SECS: u64 = 300;
+const DEFAULT_STORE: &str = "inventory.json";
+const DEFAULT_LISTEN: &str = "0.0.0.0:9100";
+const DEFAULT_CGROUP_DEPTH: usize = 2;
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 3 {
-        println!("Usage: {} <ip_range> <interface>", args[0]);
-        println!("Example: {} 192.168.1.0/24 eth0", args[0]);
+    if args.len() >= 2 && args[1] == "--gui" {
+        #[cfg(feature = "gui")]
+        {
+            let alert_config =
+                match option_value(&args, "--rules").map(|path| alerts::AlertConfig::load(&path)) {
+                    None => None,
+                    Some(Ok(config)) => Some(config),
+                    Some(Err(e)) => {
+                        println!("[-] {}", e);
+                        return;
+                    }
+                };
+            let history_path = option_value(&args, "--history")
+                .unwrap_or_else(|| history::DEFAULT_HISTORY.to_string());
+            gui::run(alert_config, history_path);
+        }
+        #[cfg(not(feature = "gui"))]
+        println!("[-] Built without the gui feature. Rebuild with --features gui");
         return;
     }
 
-    let range = &args[1];
-    let interface_name = &args[2];
+    if args.len() == 3 && args[1] == "--alerts" {
+        alerts::run(&args[2]);
+        return;
+    }
 
-    // Parse CIDR notation
-    let parts: Vec<&str> = range.split('/').collect();
-    if parts.len() != 2 {
-        println!("[-] Invalid CIDR notation. Example: 192.168.1.0/24");
+    if args.len() >= 2 && args[1] == "--record" {
+        let path = option_value(&args, "--history")
+            .unwrap_or_else(|| history::DEFAULT_HISTORY.to_string());
+        history::run_recorder(&path);
         return;
     }
 
-    let base_ip = parts[0];
-    let mask: u8 = match parts[1].parse() {
-        Ok(m) => m,
-        Err(_) => {
-            println!("[-] Invalid subnet mask");
-            return;
+    if args.len() >= 2 && args[1] == "--export" {
+        let path = option_value(&args, "--history")
+            .unwrap_or_else(|| history::DEFAULT_HISTORY.to_string());
+        let since = option_value(&args, "--since").unwrap_or_else(|| "1h".to_string());
+        let since = match history::parse_since(&since) {
+            Some(since) => since,
+            None => {
+                println!("[-] Invalid --since {}. Use e.g. 90s, 15m, 1h or 7d", since);
+                return;
+            }
+        };
+        let format = option_value(&args, "--format").unwrap_or_else(|| "csv".to_string());
+        match history::export(
+            &path,
+            since,
+            &format,
+            option_value(&args, "--metric").as_deref(),
+        ) {
+            Ok(output) => print!("{}", output),
+            Err(e) => println!("[-] {}", e),
         }
-    };
-
-    if mask != 24 {
-        println!("[-] This simple scanner only supports /24 networks");
         return;
     }
 
-    // Get network interface
-    let interfaces = datalink::interfaces();
-    let interface = match interfaces
-        .iter()
-        .find(|iface| iface.name == *interface_name)
-    {
-        Some(i) => i.clone(),
-        None => {
-            println!("[-] Interface {} not found", interface_name);
-            return;
+    if args.len() >= 2 && args[1] == "--cgroups" {
+        match option_value(&args, "--depth").map(|depth| depth.parse::<usize>()) {
+            None => cgroup::run_cgroups(DEFAULT_CGROUP_DEPTH),
+            Some(Ok(depth)) => cgroup::run_cgroups(depth),
+            Some(Err(_)) => println!("[-] Invalid depth"),
         }
-    };
-
-    println!("[+] Scanning network range: {}", range);
+        return;
+    }
 
-    // Extract network base for /24
-    let ip_parts: Vec<&str> = base_ip.split('.').collect();
-    if ip_parts.len() != 4 {
-        println!("[-] Invalid IP format");
+    if args.len() >= 2 && args[1] == "--ps" {
+        let filter = processes::ProcessFilter {
+            name: option_value(&args, "--name"),
+            user: option_value(&args, "--user"),
+        };
+        processes::run_ps(args.iter().any(|arg| arg == "--tree"), &filter);
         return;
     }
 
-    let network_base = format!("{}.{}.{}", ip_parts[0], ip_parts[1], ip_parts[2]);
+    if args.len() >= 2 && args[1] == "--watch" {
+        let paths: Vec<String> = match option_value(&args, "--paths") {
+            Some(paths) => paths.split(',').map(str::to_string).collect(),
+            None => watcher::DEFAULT_PATHS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+        };
+        watcher::run_watch(&paths, option_value(&args, "--webhook").as_deref());
+        return;
+    }
 
-    println!("\nDiscovered devices:");
-    println!("IP Address\t\tMAC Address");
-    println!("----------------------------------------");
+    if args.len() >= 2 && args[1] == "--sockets" {
+        let flag = |name: &str| args.iter().any(|arg| arg == name);
+        sockets::run_sockets(&sockets::SocketFilter {
+            tcp: flag("--tcp"),
+            udp: flag("--udp"),
+            listening: flag("--listening"),
+        });
+        return;
+    }
 
-    // Create a channel to send/receive packets
-    let (mut tx, _) = match datalink::channel(&interface, Default::default()) {
-        Ok(Ethernet(tx, rx)) => (tx, rx),
-        Ok(_) => {
-            println!("[-] Unhandled channel type");
-            return;
-        }
-        Err(e) => {
-            println!("[-] Error creating datalink channel: {}", e);
-            return;
+    if args.len() >= 3 && args[1] == "--kill" {
+        let pid = match args[2].parse::<u32>() {
+            Ok(pid) => pid,
+            Err(_) => {
+                println!("[-] Invalid PID");
+                return;
+            }
+        };
+        let signal_name = option_value(&args, "--signal").unwrap_or_else(|| "TERM".to_string());
+        match processes::parse_signal(&signal_name) {
+            Some(signal) => processes::run_kill(pid, signal),
+            None => println!("[-] Unknown signal {}", signal_name),
         }
-    };
+        return;
+    }
 
-    // Get source MAC address
-    let source_mac = match interface.mac {
-        Some(mac) => mac,
-        None => {
-            println!("[-] Could not get MAC address for interface");
-            return;
-        }
-    };
+    if args.len() >= 2 && args[1] == "--tui" {
+        run_tui(&args);
+        return;
+    }
 
-    // Get source IP address
-    let source_ip = match interface.ips.iter().find(|ip| ip.is_ipv4()) {
-        Some(ip) => match ip.ip() {
-            IpAddr::V4(ipv4) => ipv4,
-            _ => {
-                println!("[-] Could not get IPv4 address for interface");
+    if args.len() >= 2 && args[1] == "--metrics" {
+        let scan_interval = match interval_option(&args) {
+            Some(interval) => interval,
+            None => {
+                println!("[-] Invalid interval");
                 return;
             }
-        },
-        None => {
-            println!("[-] No IPv4 address found for interface");
-            return;
-        }
-    };
-
-    // Scan range
-    for i in 1..255 {
-        let target_ip_str = format!("{}.{}", network_base, i);
-        let target_ip = match Ipv4Addr::from_str(&target_ip_str) {
-            Ok(ip) => ip,
-            Err(_) => continue,
         };
+        metrics::run_exporter(ExporterConfig {
+            listen: option_value(&args, "--listen").unwrap_or_else(|| DEFAULT_LISTEN.to_string()),
+            scan: scan_target(&args),
+            scan_interval,
+        });
+        return;
+    }
 
-        // Send an ARP request
-        let mut ethernet_buffer = [0u8; 42]; // Ethernet + ARP
-        let mut ethernet_packet = MutableEthernetPacket::new(&mut ethernet_buffer).unwrap();
-
-        ethernet_packet.set_destination(MacAddr::broadcast());
-        ethernet_packet.set_source(source_mac);
-        ethernet_packet.set_ethertype(EtherTypes::Arp);
-
-        let mut arp_buffer = [0u8; 28];
-        let mut arp_packet = MutableArpPacket::new(&mut arp_buffer).unwrap();
-
-        arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
-        arp_packet.set_protocol_type(EtherTypes::Ipv4);
-        arp_packet.set_hw_addr_len(6);
-        arp_packet.set_proto_addr_len(4);
-        arp_packet.set_operation(ArpOperation::new(1));
-        arp_packet.set_sender_hw_addr(source_mac);
-        arp_packet.set_sender_proto_addr(source_ip);
-        arp_packet.set_target_hw_addr(MacAddr::zero());
-        arp_packet.set_target_proto_addr(target_ip);
-
-        ethernet_packet.set_payload(arp_packet.packet());
+    if args.len() == 3 && args[1] == "--passive" {
+        passive::run(&args[2]);
+        return;
+    }
 
-        match tx.send_to(ethernet_packet.packet(), None) {
-            Some(_) => {}
+    if args.len() >= 4 && args[1] == "--daemon" {
+        let interval = match interval_option(&args) {
+            Some(interval) => interval,
             None => {
-                println!("[-] Error sending ARP request to {}", target_ip_str);
-                continue;
+                println!("[-] Invalid interval");
+                return;
             }
+        };
+        let probe = match probe_option(&args) {
+            Ok(probe) => probe,
+            Err(e) => {
+                println!("[-] {}", e);
+                return;
+            }
+        };
+        let config = DaemonConfig {
+            range: args[2].clone(),
+            interface: args[3].clone(),
+            interval,
+            store: option_value(&args, "--store").unwrap_or_else(|| DEFAULT_STORE.to_string()),
+            webhook: option_value(&args, "--webhook"),
+            probe,
+        };
+        inventory::run_daemon(&config);
+        return;
+    }
+
+    if args.len() < 3 || args[1].starts_with("--") {
+        print_usage(&args[0]);
+        process::exit(scanner::EXIT_ERROR);
+    }
+
+    let format = option_value(&args, "--output").unwrap_or_else(|| "table".to_string());
+    let Some(format) = OutputFormat::parse(&format) else {
+        println!(
+            "[-] Unknown output format {}. Use json, csv or table",
+            format
+        );
+        process::exit(scanner::EXIT_ERROR);
+    };
+    let probe = match probe_option(&args) {
+        Ok(probe) => probe,
+        Err(e) => {
+            println!("[-] {}", e);
+            process::exit(scanner::EXIT_ERROR);
+        }
+    };
+    let resolve = match resolve_option(&args) {
+        Ok(resolve) => resolve,
+        Err(e) => {
+            println!("[-] {}", e);
+            process::exit(scanner::EXIT_ERROR);
         }
+    };
+    process::exit(scanner::run(
+        &args[1],
+        &args[2],
+        probe.as_ref(),
+        resolve.as_ref(),
+        format,
+    ));
+}
 
-        // We need to use an external command to check the ARP table
-        // since we're not setting up a proper receiver in this example
-        thread::sleep(Duration::from_millis(10));
+fn print_usage(program: &str) {
+    println!(
+        "Usage: {} <ip_range> <interface> [--output <table|json|csv>] [--probe [--ports <22,80,8000-8010>] [--concurrency <n>] [--rate <per_sec>]]",
+        program
+    );
+    println!("         [--resolver <ip[:port]>] [--no-resolve]");
+    println!(
+        "       {} --gui [--rules <rules.json>] [--history <file.json>]",
+        program
+    );
+    println!("       {} --record [--history <file.json>]", program);
+    println!(
+        "       {} --export [--since <1h>] [--format <csv|json>] [--metric <name>] [--history <file.json>]",
+        program
+    );
+    println!("       {} --alerts <rules.json>", program);
+    println!(
+        "       {} --ps [--tree] [--name <pattern>] [--user <name>]",
+        program
+    );
+    println!("       {} --cgroups [--depth <levels>]", program);
+    println!("       {} --sockets [--tcp] [--udp] [--listening]", program);
+    println!(
+        "       {} --watch [--paths </tmp,/dev/shm,~/.local/share>] [--webhook <http://url>]",
+        program
+    );
+    println!(
+        "       {} --kill <pid> [--signal <TERM|KILL|HUP|INT|STOP|CONT|USR1|USR2>]",
+        program
+    );
+    println!(
+        "       {} --tui [--scan <ip_range> <interface>] [--store <file.json>]",
+        program
+    );
+    println!(
+        "       {} --metrics [--listen <addr:port>] [--scan <ip_range> <interface>] [--interval <secs>]",
+        program
+    );
+    println!("       {} --passive <interface>", program);
+    println!(
+        "       {} --daemon <ip_range> <interface> [--interval <secs>] [--store <file.json>] [--webhook <http://url>] [--probe ...]",
+        program
+    );
+    println!("Example: {} 192.168.1.0/24 eth0", program);
+    println!("Scans exit with 0 if hosts were found, 1 if none answered and 2 on errors.");
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(args: &[String]) {
+    let options = tui::TuiOptions {
+        scan: scan_target(args),
+        store: option_value(args, "--store"),
+    };
+    if let Err(e) = tui::run(options) {
+        println!("[-] Terminal error: {}", e);
     }
+}
 
-    // Give time for ARP responses to populate the ARP cache
-    thread::sleep(Duration::from_secs(1));
+#[cfg(not(feature = "tui"))]
+fn run_tui(_args: &[String]) {
+    println!("[-] Built without the tui feature. Rebuild with --features tui");
+}
 
-    // Check ARP table
-    let output = Command::new("arp")
-        .arg("-a")
-        .output()
-        .expect("Failed to execute ARP command");
+/// `--scan <ip_range> <interface>` for modes that sweep in the background.
+fn scan_target(args: &[String]) -> Option<(String, String)> {
+    let i = args.iter().position(|arg| arg == "--scan")?;
+    Some((args.get(i + 1)?.clone(), args.get(i + 2)?.clone()))
+}
+
+fn interval_option(args: &[String]) -> Option<Duration> {
+    match option_value(args, "--interval").map(|v| v.parse::<u64>()) {
+        None => Some(Duration::from_secs(DEFAULT_INTERVAL_SECS)),
+        Some(Ok(secs)) if secs > 0 => Some(Duration::from_secs(secs)),
+        Some(_) => None,
+    }
+}
 
-    let arp_table = String::from_utf8_lossy(&output.stdout);
+/// `--probe` with optional `--ports`, `--concurrency` and `--rate`.
+fn probe_option(args: &[String]) -> Result<Option<ProbeConfig>, String> {
+    if !args.iter().any(|arg| arg == "--probe") {
+        return Ok(None);
+    }
+    let mut config = ProbeConfig::default();
+    if let Some(ports) = option_value(args, "--ports") {
+        config.ports = probe::parse_ports(&ports)?;
+    }
+    if let Some(concurrency) = option_value(args, "--concurrency") {
+        config.concurrency = concurrency
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or("Invalid concurrency")?;
+    }
+    if let Some(rate) = option_value(args, "--rate") {
+        config.rate = rate.parse().ok().filter(|n| *n > 0).ok_or("Invalid rate")?;
+    }
+    Ok(Some(config))
+}
 
-    // Filter and display results for our network
-    for line in arp_table.lines() {
-        if line.contains(&network_base) {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 3 {
-                let ip = parts[1].trim_matches(|c| c == '(' || c == ')');
-                let mac = parts[3];
-                if mac != "<incomplete>" {
-                    println!("{}\t\t{}", ip, mac);
-                }
-            }
-        }
+/// Hostname lookups are on unless `--no-resolve` is given.
+fn resolve_option(args: &[String]) -> Result<Option<ResolveConfig>, String> {
+    if args.iter().any(|arg| arg == "--no-resolve") {
+        return Ok(None);
     }
+    let mut config = ResolveConfig::default();
+    if let Some(resolver) = option_value(args, "--resolver") {
+        config.resolver = Some(resolve::parse_resolver(&resolver)?);
+    }
+    Ok(Some(config))
 }
 
-// extern crate glib;
-// extern crate gtk;
-// extern crate sysinfo;
-
-// mod system_info;
-// use glib::{ControlFlow, clone};
-// use gtk::prelude::*;
-// use gtk::{Application, ApplicationWindow, Box, Label};
-// use std::cell::RefCell;
-// use std::rc::Rc;
-// use system_info::SystemInfo;
-
-// fn build_ui(application: &gtk::Application) {
-//     let window = ApplicationWindow::new(application);
-//     window.set_title("System Monitor");
-//     window.set_default_size(350, 70);
-//     let vbox = Box::new(gtk::Orientation::Vertical, 10);
-//     let cpu_label = Label::new(Some("CPU Usage:"));
-//     let memory_label = Label::new(Some("Memory Usage:"));
-//     vbox.add(&cpu_label);
-//     vbox.add(&memory_label);
-//     window.add(&vbox);
-//     window.show_all();
-//     let system_info = Rc::new(RefCell::new(SystemInfo::new()));
-//     glib::timeout_add_seconds_local(
-//         1,
-//         clone!(@strong system_info, @strong cpu_label, @strong memory_label => move || {
-//             let system_info = system_info.borrow();
-//             cpu_label.set_text(&format!("CPU Usage: {:.2}%", system_info.get_cpu_usage()));
-//             let (used_memory, total_memory) = system_info.get_memory_usage();
-//             memory_label.set_text(&format!("Memory Usage: {} / {}", used_memory, total_memory));
-//             ControlFlow::Continue
-//         }),
-//     );
-// }
-
-// fn main() {
-//     let application = Application::new(Some("com.example.system_monitor"), Default::default());
-//     application.connect_activate(build_ui);
-//     application.run();
-// }
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
@@ -0,0 +1,570 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::collector::InterfaceCounters;
+use crate::scanner;
+use crate::system_info::SystemInfo;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Metric name, help text and the `/proc/net/dev` field it exports.
+type InterfaceCounter = (&'static str, &'static str, fn(&InterfaceCounters) -> u64);
+
+pub struct ExporterConfig {
+    pub listen: String,
+    /// Rescan `(range, interface)` every `scan_interval` to export device counts.
+    pub scan: Option<(String, String)>,
+    pub scan_interval: Duration,
+}
+
+#[derive(Default)]
+struct ScanStats {
+    devices_seen: usize,
+    last_duration: Duration,
+    last_success: Option<SystemTime>,
+    scans: u64,
+    failures: u64,
+}
+
+/// Writes metric families in the OpenMetrics text format.
+struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        writeln!(self.out, "# HELP {} {}", name, help).ok();
+        writeln!(self.out, "# TYPE {} {}", name, kind).ok();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            write!(self.out, "{{{}}}", labels.join(",")).ok();
+        }
+        writeln!(self.out, " {}", value).ok();
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    fn finish(mut self) -> String {
+        self.out.push_str("# EOF\n");
+        self.out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn run_exporter(config: ExporterConfig) {
+    let listener = match TcpListener::bind(&config.listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("[-] Could not listen on {}: {}", config.listen, e);
+            return;
+        }
+    };
+
+    let stats = Arc::new(Mutex::new(None));
+    if let Some((range, interface)) = config.scan.clone() {
+        let stats = stats.clone();
+        let interval = config.scan_interval;
+        thread::spawn(move || scan_loop(&range, &interface, interval, &stats));
+    }
+
+    println!("[+] Serving metrics on http://{}/metrics", config.listen);
+    let mut system_info = SystemInfo::new();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle(stream, &mut system_info, &stats) {
+                    eprintln!("[-] {}", e);
+                }
+            }
+            Err(e) => eprintln!("[-] Connection failed: {}", e),
+        }
+    }
+}
+
+fn scan_loop(range: &str, interface: &str, interval: Duration, stats: &Mutex<Option<ScanStats>>) {
+    loop {
+        let started = Instant::now();
+        let result = scanner::scan(range, interface);
+        {
+            let mut stats = stats.lock().unwrap();
+            let stats = stats.get_or_insert_with(ScanStats::default);
+            stats.scans += 1;
+            stats.last_duration = started.elapsed();
+            match result {
+                Ok(devices) => {
+                    stats.devices_seen = devices.len();
+                    stats.last_success = Some(SystemTime::now());
+                }
+                Err(e) => {
+                    stats.failures += 1;
+                    eprintln!("[-] Scan failed: {}", e);
+                }
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    system_info: &mut SystemInfo,
+    stats: &Mutex<Option<ScanStats>>,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    if method != "GET" || path.split('?').next() != Some("/metrics") {
+        let body = "Not found. Metrics are served at /metrics\n";
+        return write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+    }
+
+    // CPU and rates are averaged over the time since the previous scrape
+    system_info.refresh();
+    let body = render(system_info, stats.lock().unwrap().as_ref());
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        CONTENT_TYPE,
+        body.len(),
+        body
+    )
+}
+
+fn render(system_info: &SystemInfo, scan: Option<&ScanStats>) -> String {
+    let mut metrics = MetricsWriter { out: String::new() };
+
+    metrics.gauge(
+        "system_cpu_usage_percent",
+        "Global CPU usage since the previous scrape.",
+        system_info.get_cpu_usage() as f64,
+    );
+    metrics.family(
+        "system_cpu_core_usage_percent",
+        "gauge",
+        "Per-core CPU usage since the previous scrape.",
+    );
+    for (core, usage) in system_info.get_cpu_usage_per_core().iter().enumerate() {
+        metrics.sample(
+            "system_cpu_core_usage_percent",
+            &[("cpu", &core.to_string())],
+            *usage as f64,
+        );
+    }
+
+    let (used_memory, total_memory) = system_info.get_memory_usage();
+    let (used_swap, total_swap) = system_info.get_swap_usage();
+    metrics.gauge(
+        "system_memory_used_bytes",
+        "Used RAM.",
+        (used_memory * 1024) as f64,
+    );
+    metrics.gauge(
+        "system_memory_total_bytes",
+        "Installed RAM.",
+        (total_memory * 1024) as f64,
+    );
+    metrics.gauge(
+        "system_swap_used_bytes",
+        "Used swap.",
+        (used_swap * 1024) as f64,
+    );
+    metrics.gauge(
+        "system_swap_total_bytes",
+        "Total swap.",
+        (total_swap * 1024) as f64,
+    );
+
+    let breakdown = system_info.get_cpu_breakdown();
+    if !breakdown.is_empty() {
+        metrics.family(
+            "system_cpu_mode_percent",
+            "gauge",
+            "Share of time per CPU mode since the previous scrape.",
+        );
+        for (cpu, usage) in &breakdown {
+            for (mode, value) in [
+                ("user", usage.user),
+                ("system", usage.system),
+                ("iowait", usage.iowait),
+                ("irq", usage.irq),
+                ("softirq", usage.softirq),
+                ("steal", usage.steal),
+                ("idle", usage.idle),
+            ] {
+                metrics.sample(
+                    "system_cpu_mode_percent",
+                    &[("cpu", cpu), ("mode", mode)],
+                    value,
+                );
+            }
+        }
+    }
+
+    if let Some(memory) = system_info.get_memory_info() {
+        metrics.gauge(
+            "system_memory_buffers_bytes",
+            "Memory used for block device buffers.",
+            (memory.buffers * 1024) as f64,
+        );
+        metrics.gauge(
+            "system_memory_cached_bytes",
+            "Memory used for the page cache.",
+            (memory.cached * 1024) as f64,
+        );
+    }
+
+    let pressure = system_info.get_pressure();
+    if !pressure.is_empty() {
+        metrics.family(
+            "system_pressure_percent",
+            "gauge",
+            "Share of time tasks stalled on a resource, averaged over a window.",
+        );
+        for (resource, stall) in &pressure {
+            for (kind, line) in [("some", Some(stall.some)), ("full", stall.full)] {
+                let Some(line) = line else { continue };
+                for (window, value) in [
+                    ("10s", line.avg10),
+                    ("60s", line.avg60),
+                    ("300s", line.avg300),
+                ] {
+                    metrics.sample(
+                        "system_pressure_percent",
+                        &[("resource", resource), ("kind", kind), ("window", window)],
+                        value,
+                    );
+                }
+            }
+        }
+        metrics.family(
+            "system_pressure_stall_seconds",
+            "counter",
+            "Total time tasks stalled on a resource.",
+        );
+        for (resource, stall) in &pressure {
+            for (kind, line) in [("some", Some(stall.some)), ("full", stall.full)] {
+                if let Some(line) = line {
+                    metrics.sample(
+                        "system_pressure_stall_seconds_total",
+                        &[("resource", resource), ("kind", kind)],
+                        line.total as f64 / 1_000_000.0,
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(runtime) = system_info.get_container() {
+        metrics.family(
+            "system_container_info",
+            "gauge",
+            "Container runtime we run under. CPU and memory are reported against its limits.",
+        );
+        metrics.sample("system_container_info", &[("runtime", runtime)], 1.0);
+    }
+
+    if let Some(cgroup) = system_info.get_cgroup_limits() {
+        if let Some(max) = cgroup.memory_max {
+            metrics.gauge(
+                "system_cgroup_memory_max_bytes",
+                "Memory limit of our cgroup.",
+                max as f64,
+            );
+        }
+        if let Some(current) = cgroup.memory_current {
+            metrics.gauge(
+                "system_cgroup_memory_current_bytes",
+                "Memory charged to our cgroup.",
+                current as f64,
+            );
+        }
+        if let Some(cores) = cgroup.cpu_cores() {
+            metrics.gauge(
+                "system_cgroup_cpu_limit_cores",
+                "CPU quota of our cgroup in cores.",
+                cores,
+            );
+        }
+        if let Some(usage) = cgroup.cpu_usage_usec {
+            metrics.family(
+                "system_cgroup_cpu_usage_seconds",
+                "counter",
+                "CPU time used by our cgroup.",
+            );
+            metrics.sample(
+                "system_cgroup_cpu_usage_seconds_total",
+                &[],
+                usage as f64 / 1_000_000.0,
+            );
+        }
+        if let Some(max) = cgroup.pids_max {
+            metrics.gauge(
+                "system_cgroup_pids_max",
+                "Task limit of our cgroup.",
+                max as f64,
+            );
+        }
+        if let Some(current) = cgroup.pids_current {
+            metrics.gauge(
+                "system_cgroup_pids_current",
+                "Tasks in our cgroup.",
+                current as f64,
+            );
+        }
+    }
+
+    // One device can be mounted several times, so label samples by mount too.
+    // Both lists come from the same disk list, in the same order.
+    let disks: Vec<(String, String, u64, u64)> = system_info
+        .get_disk_usage()
+        .into_iter()
+        .zip(system_info.get_mount_usage())
+        .map(|((name, total, available), (mount, _, _))| (name, mount, total, available))
+        .collect();
+    metrics.family("system_disk_total_bytes", "gauge", "Disk capacity.");
+    for (name, mount, total, _) in &disks {
+        metrics.sample(
+            "system_disk_total_bytes",
+            &[("disk", name), ("mount", mount)],
+            *total as f64,
+        );
+    }
+    metrics.family(
+        "system_disk_available_bytes",
+        "gauge",
+        "Disk space available to unprivileged users.",
+    );
+    for (name, mount, _, available) in &disks {
+        metrics.sample(
+            "system_disk_available_bytes",
+            &[("disk", name), ("mount", mount)],
+            *available as f64,
+        );
+    }
+
+    let networks = system_info.get_network_totals();
+    metrics.family(
+        "system_network_receive_bytes",
+        "counter",
+        "Bytes received per interface.",
+    );
+    for (name, received, _) in &networks {
+        metrics.sample(
+            "system_network_receive_bytes_total",
+            &[("interface", name)],
+            *received as f64,
+        );
+    }
+    metrics.family(
+        "system_network_transmit_bytes",
+        "counter",
+        "Bytes transmitted per interface.",
+    );
+    for (name, _, transmitted) in &networks {
+        metrics.sample(
+            "system_network_transmit_bytes_total",
+            &[("interface", name)],
+            *transmitted as f64,
+        );
+    }
+
+    let interfaces = system_info.get_interface_stats();
+    let counters: [InterfaceCounter; 6] = [
+        (
+            "system_network_receive_packets",
+            "Packets received per interface.",
+            |c| c.rx_packets,
+        ),
+        (
+            "system_network_transmit_packets",
+            "Packets transmitted per interface.",
+            |c| c.tx_packets,
+        ),
+        (
+            "system_network_receive_errors",
+            "Receive errors per interface.",
+            |c| c.rx_errors,
+        ),
+        (
+            "system_network_transmit_errors",
+            "Transmit errors per interface.",
+            |c| c.tx_errors,
+        ),
+        (
+            "system_network_receive_drops",
+            "Received packets dropped per interface.",
+            |c| c.rx_dropped,
+        ),
+        (
+            "system_network_transmit_drops",
+            "Outgoing packets dropped per interface.",
+            |c| c.tx_dropped,
+        ),
+    ];
+    if !interfaces.is_empty() {
+        for (name, help, value) in counters {
+            metrics.family(name, "counter", help);
+            for stats in &interfaces {
+                metrics.sample(
+                    &format!("{}_total", name),
+                    &[("interface", &stats.counters.name)],
+                    value(&stats.counters) as f64,
+                );
+            }
+        }
+    }
+
+    metrics.gauge(
+        "system_processes",
+        "Number of running processes.",
+        system_info.get_processes().len() as f64,
+    );
+
+    if let Some(scan) = scan {
+        metrics.gauge(
+            "system_monitor_scan_devices",
+            "Devices that answered the last ARP sweep.",
+            scan.devices_seen as f64,
+        );
+        metrics.gauge(
+            "system_monitor_scan_duration_seconds",
+            "Duration of the last ARP sweep.",
+            scan.last_duration.as_secs_f64(),
+        );
+        metrics.family("system_monitor_scans", "counter", "ARP sweeps started.");
+        metrics.sample("system_monitor_scans_total", &[], scan.scans as f64);
+        metrics.family(
+            "system_monitor_scan_failures",
+            "counter",
+            "ARP sweeps that failed.",
+        );
+        metrics.sample(
+            "system_monitor_scan_failures_total",
+            &[],
+            scan.failures as f64,
+        );
+        if let Some(timestamp) = scan.last_success {
+            let seconds = timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            metrics.gauge(
+                "system_monitor_last_scan_timestamp_seconds",
+                "Unix time of the last successful ARP sweep.",
+                seconds,
+            );
+        }
+    }
+
+    metrics.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn writes_samples_with_labels() {
+        let mut metrics = MetricsWriter { out: String::new() };
+        metrics.family("disk_bytes", "gauge", "Disk capacity.");
+        metrics.sample(
+            "disk_bytes",
+            &[("disk", "sda"), ("mount", "/my \"data\"")],
+            2.5,
+        );
+        metrics.sample("disk_bytes", &[], 1.0);
+        assert_eq!(
+            metrics.finish(),
+            "# HELP disk_bytes Disk capacity.\n\
+             # TYPE disk_bytes gauge\n\
+             disk_bytes{disk=\"sda\",mount=\"/my \\\"data\\\"\"} 2.5\n\
+             disk_bytes 1\n\
+             # EOF\n"
+        );
+    }
+
+    #[test]
+    fn renders_valid_openmetrics() {
+        let scan = ScanStats {
+            devices_seen: 3,
+            scans: 2,
+            last_success: Some(SystemTime::now()),
+            ..ScanStats::default()
+        };
+        let body = render(&SystemInfo::new(), Some(&scan));
+        assert!(body.ends_with("\n# EOF\n"));
+        assert_eq!(body.matches("# EOF").count(), 1);
+
+        let mut families: Vec<(&str, &str)> = Vec::new();
+        let mut series = HashSet::new();
+        for line in body.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                let name = help.split(' ').next().unwrap();
+                assert!(
+                    families.iter().all(|(family, _)| *family != name),
+                    "{}",
+                    line
+                );
+                families.push((name, ""));
+            } else if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                let last = families.last_mut().unwrap();
+                assert_eq!(last.0, name, "TYPE must follow its HELP");
+                last.1 = kind;
+            } else if line != "# EOF" {
+                let (key, value) = line.rsplit_once(' ').unwrap();
+                assert!(value.parse::<f64>().is_ok(), "{}", line);
+                let name = key.split('{').next().unwrap();
+                // Samples belong to the family declared just before them
+                let (family, kind) = families.last().unwrap();
+                match *kind {
+                    "counter" => assert_eq!(name, format!("{}_total", family)),
+                    "gauge" => assert_eq!(name, *family),
+                    other => panic!("unexpected type {}", other),
+                }
+                assert!(series.insert(key.to_string()), "duplicate series {}", key);
+            }
+        }
+        for family in [
+            "system_cpu_usage_percent",
+            "system_monitor_scans",
+            "system_processes",
+        ] {
+            assert!(
+                families.iter().any(|(name, _)| *name == family),
+                "{}",
+                family
+            );
+        }
+        assert!(body.contains("system_monitor_scans_total 2\n"));
+    }
+}
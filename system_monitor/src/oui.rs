@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Vendor databases shipped by common packages, checked in order.
+const DATABASES: [&str; 4] = [
+    "/usr/share/nmap/nmap-mac-prefixes",
+    "/usr/share/arp-scan/ieee-oui.txt",
+    "/usr/share/ieee-data/oui.txt",
+    "/var/lib/ieee-data/oui.txt",
+];
+
+/// Prefixes worth recognising even without a database: hypervisors and boards.
+const BUILTIN: [(u32, &str); 10] = [
+    (0x000569, "VMware"),
+    (0x000C29, "VMware"),
+    (0x005056, "VMware"),
+    (0x080027, "VirtualBox"),
+    (0x00155D, "Microsoft Hyper-V"),
+    (0x00163E, "Xen"),
+    (0x525400, "QEMU/KVM"),
+    (0xB827EB, "Raspberry Pi Foundation"),
+    (0xDCA632, "Raspberry Pi Trading"),
+    (0xE45F01, "Raspberry Pi Trading"),
+];
+
+static TABLE: OnceLock<HashMap<u32, String>> = OnceLock::new();
+
+/// Reads `001122 Vendor`, `001122<TAB>Vendor` and the IEEE `00-11-22 (hex) Vendor`
+/// and `001122 (base 16) Vendor` lines.
+pub fn parse_database(contents: &str) -> HashMap<u32, String> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.trim();
+            let marked = line
+                .split_once("(hex)")
+                .or_else(|| line.split_once("(base 16)"));
+            let (prefix, vendor) = match marked {
+                Some((prefix, vendor)) => (prefix.trim().replace('-', ""), vendor),
+                None => {
+                    let split = line.find(char::is_whitespace)?;
+                    (line[..split].to_string(), &line[split..])
+                }
+            };
+            if prefix.len() != 6 {
+                return None;
+            }
+            let prefix = u32::from_str_radix(&prefix, 16).ok()?;
+            let vendor = vendor.trim();
+            (!vendor.is_empty()).then(|| (prefix, vendor.to_string()))
+        })
+        .collect()
+}
+
+fn table() -> &'static HashMap<u32, String> {
+    TABLE.get_or_init(|| {
+        let database = DATABASES
+            .iter()
+            .find_map(|path| fs::read_to_string(path).ok());
+        build_table(database.as_deref())
+    })
+}
+
+/// The built-in prefixes, overridden by a database file's contents if any.
+fn build_table(database: Option<&str>) -> HashMap<u32, String> {
+    let mut table: HashMap<u32, String> = BUILTIN
+        .iter()
+        .map(|(prefix, vendor)| (*prefix, vendor.to_string()))
+        .collect();
+    if let Some(contents) = database {
+        table.extend(parse_database(contents));
+    }
+    table
+}
+
+/// Best-effort vendor for a MAC such as `b8:27:eb:12:34:56`.
+pub fn lookup(mac: &str) -> Option<String> {
+    lookup_in(table(), mac)
+}
+
+fn lookup_in(table: &HashMap<u32, String>, mac: &str) -> Option<String> {
+    let octets: Vec<u8> = mac
+        .split([':', '-'])
+        .filter_map(|octet| u8::from_str_radix(octet, 16).ok())
+        .collect();
+    if octets.len() != 6 {
+        return None;
+    }
+    let prefix = u32::from_be_bytes([0, octets[0], octets[1], octets[2]]);
+    if let Some(vendor) = table.get(&prefix) {
+        return Some(vendor.clone());
+    }
+    // Randomised addresses (phones, containers) set the locally administered bit
+    (octets[0] & 0x02 != 0).then(|| "Locally administered".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_database_formats() {
+        let table = parse_database(
+            "# comment\n\
+             001A2B Ayecom Technology\n\
+             3C5AB4\tGoogle, Inc.\n\
+             00-00-0C   (hex)\t\tCisco Systems, Inc\n\
+             00000C     (base 16)\t\tCisco Systems, Inc\n\
+             bogus line\n",
+        );
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[&0x001A2B], "Ayecom Technology");
+        assert_eq!(table[&0x3C5AB4], "Google, Inc.");
+        assert_eq!(table[&0x00000C], "Cisco Systems, Inc");
+    }
+
+    #[test]
+    fn looks_up_builtin_and_local_addresses() {
+        let builtin = build_table(None);
+        assert_eq!(
+            lookup_in(&builtin, "b8:27:eb:12:34:56").as_deref(),
+            Some("Raspberry Pi Foundation")
+        );
+        assert_eq!(
+            lookup_in(&builtin, "02-42-AC-11-00-02").as_deref(),
+            Some("Locally administered")
+        );
+        assert_eq!(lookup_in(&builtin, "00:11:22:33:44:55"), None);
+        assert_eq!(lookup_in(&builtin, "not a mac"), None);
+
+        // A database adds prefixes and overrides the built-in names
+        let table = build_table(Some("001122 Acme\nB827EB Raspberry Pi\n"));
+        assert_eq!(
+            lookup_in(&table, "00:11:22:33:44:55").as_deref(),
+            Some("Acme")
+        );
+        assert_eq!(
+            lookup_in(&table, "b8:27:eb:12:34:56").as_deref(),
+            Some("Raspberry Pi")
+        );
+        assert_eq!(
+            lookup_in(&table, "00:0c:29:00:00:01").as_deref(),
+            Some("VMware")
+        );
+    }
+}
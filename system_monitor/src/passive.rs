@@ -0,0 +1,636 @@
+use chrono::{DateTime, Local};
+use pnet::datalink::NetworkInterface;
+use pnet::packet::Packet;
+use pnet::packet::arp::ArpPacket;
+use pnet::packet::dhcp::{DhcpOperations, DhcpPacket};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
+use pnet::packet::icmpv6::ndp::{NdpOptionTypes, NeighborAdvertPacket};
+use pnet::packet::icmpv6::{Icmpv6Packet, Icmpv6Types};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::udp::UdpPacket;
+use pnet::util::MacAddr;
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::resolve::read_name;
+use crate::scanner;
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const MDNS_PORT: u16 = 5353;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+/// One piece of evidence that a MAC address is present on the link.
+#[derive(Debug)]
+pub struct Observation {
+    pub mac: MacAddr,
+    pub ip: Option<IpAddr>,
+    pub hostname: Option<String>,
+    pub protocol: &'static str,
+}
+
+#[derive(Debug)]
+pub struct ObservedDevice {
+    pub mac: MacAddr,
+    pub ips: BTreeSet<IpAddr>,
+    pub hostname: Option<String>,
+    pub protocols: BTreeSet<&'static str>,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+}
+
+pub enum Change {
+    NewDevice,
+    NewAddress(IpAddr),
+    Unchanged,
+}
+
+#[derive(Default)]
+pub struct DeviceTable {
+    devices: HashMap<MacAddr, ObservedDevice>,
+}
+
+impl DeviceTable {
+    pub fn observe(&mut self, observation: Observation, now: DateTime<Local>) -> Change {
+        let mut change = Change::Unchanged;
+        let device = self.devices.entry(observation.mac).or_insert_with(|| {
+            change = Change::NewDevice;
+            ObservedDevice {
+                mac: observation.mac,
+                ips: BTreeSet::new(),
+                hostname: None,
+                protocols: BTreeSet::new(),
+                first_seen: now,
+                last_seen: now,
+            }
+        });
+
+        device.last_seen = now;
+        device.protocols.insert(observation.protocol);
+        if let Some(hostname) = observation.hostname {
+            device.hostname = Some(hostname);
+        }
+        if let Some(ip) = observation.ip
+            && device.ips.insert(ip)
+            && matches!(change, Change::Unchanged)
+        {
+            change = Change::NewAddress(ip);
+        }
+        change
+    }
+
+    pub fn get(&self, mac: &MacAddr) -> Option<&ObservedDevice> {
+        self.devices.get(mac)
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+}
+
+pub fn run(interface_name: &str) {
+    let interface = match scanner::find_interface(interface_name) {
+        Some(i) => i,
+        None => {
+            println!("[-] Interface {} not found", interface_name);
+            return;
+        }
+    };
+
+    if let Err(e) = listen(&interface) {
+        println!("[-] {}", e);
+    }
+}
+
+fn listen(interface: &NetworkInterface) -> Result<(), String> {
+    let (_, mut rx) = scanner::open_channel(interface, None)?;
+
+    println!(
+        "[+] Passive discovery on {} (ARP, DHCP, mDNS, NDP). No packets will be sent.",
+        interface.name
+    );
+    println!("\nFirst seen\t\tMAC Address\t\tIP Address\t\tVia\tHostname");
+    println!(
+        "------------------------------------------------------------------------------------"
+    );
+
+    let mut table = DeviceTable::default();
+    loop {
+        let frame = match rx.next() {
+            Ok(frame) => frame,
+            Err(e) => return Err(format!("Error receiving packet: {}", e)),
+        };
+
+        for observation in parse_frame(frame) {
+            let mac = observation.mac;
+            let protocol = observation.protocol;
+            let now = Local::now();
+            match table.observe(observation, now) {
+                Change::NewDevice => {
+                    let device = table.get(&mac).unwrap();
+                    println!(
+                        "{}\t{}\t{}\t\t{}\t{}",
+                        device.first_seen.format("%Y-%m-%d %H:%M:%S"),
+                        device.mac,
+                        format_ips(&device.ips),
+                        protocol,
+                        device.hostname.as_deref().unwrap_or("-"),
+                    );
+                }
+                Change::NewAddress(ip) => {
+                    let device = table.get(&mac).unwrap();
+                    println!(
+                        "[*] {} now also at {} (first seen {}, last seen {}, {} devices known)",
+                        mac,
+                        ip,
+                        device.first_seen.format("%H:%M:%S"),
+                        device.last_seen.format("%H:%M:%S"),
+                        table.len(),
+                    );
+                }
+                Change::Unchanged => {}
+            }
+        }
+    }
+}
+
+fn format_ips(ips: &BTreeSet<IpAddr>) -> String {
+    if ips.is_empty() {
+        return "-".to_string();
+    }
+    ips.iter()
+        .map(|ip| ip.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Extracts device observations from a single Ethernet frame. Only link-local
+/// discovery traffic is considered, so routed packets never attribute a remote
+/// IP to the gateway's MAC address.
+pub fn parse_frame(frame: &[u8]) -> Vec<Observation> {
+    let mut observations = Vec::new();
+    let ethernet = match EthernetPacket::new(frame) {
+        Some(packet) => packet,
+        None => return observations,
+    };
+    let source_mac = ethernet.get_source();
+
+    match ethernet.get_ethertype() {
+        EtherTypes::Arp => {
+            if let Some(arp) = ArpPacket::new(ethernet.payload()) {
+                let sender_ip = arp.get_sender_proto_addr();
+                observations.push(Observation {
+                    mac: arp.get_sender_hw_addr(),
+                    // ARP probes use 0.0.0.0 while checking for conflicts
+                    ip: (!sender_ip.is_unspecified()).then_some(IpAddr::V4(sender_ip)),
+                    hostname: None,
+                    protocol: "ARP",
+                });
+            }
+        }
+        EtherTypes::Ipv4 => {
+            if let Some(ipv4) = Ipv4Packet::new(ethernet.payload())
+                && ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Udp
+                && let Some(udp) = UdpPacket::new(ipv4.payload())
+            {
+                let source_ip = IpAddr::V4(ipv4.get_source());
+                parse_udp(source_mac, source_ip, &udp, &mut observations);
+            }
+        }
+        EtherTypes::Ipv6 => {
+            if let Some(ipv6) = Ipv6Packet::new(ethernet.payload()) {
+                let source_ip = IpAddr::V6(ipv6.get_source());
+                match ipv6.get_next_header() {
+                    IpNextHeaderProtocols::Icmpv6 => {
+                        parse_ndp(source_mac, &ipv6, &mut observations);
+                    }
+                    IpNextHeaderProtocols::Udp => {
+                        if let Some(udp) = UdpPacket::new(ipv6.payload()) {
+                            parse_udp(source_mac, source_ip, &udp, &mut observations);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    observations
+}
+
+fn parse_udp(
+    source_mac: MacAddr,
+    source_ip: IpAddr,
+    udp: &UdpPacket,
+    observations: &mut Vec<Observation>,
+) {
+    let ports = (udp.get_source(), udp.get_destination());
+    if ports == (DHCP_CLIENT_PORT, DHCP_SERVER_PORT)
+        || ports == (DHCP_SERVER_PORT, DHCP_CLIENT_PORT)
+    {
+        parse_dhcp(source_mac, source_ip, udp.payload(), observations);
+    } else if ports.0 == MDNS_PORT {
+        let ip = (!source_ip.is_unspecified()).then_some(source_ip);
+        observations.push(Observation {
+            mac: source_mac,
+            ip,
+            hostname: mdns_hostname(udp.payload()),
+            protocol: "mDNS",
+        });
+    }
+}
+
+fn parse_dhcp(
+    source_mac: MacAddr,
+    source_ip: IpAddr,
+    payload: &[u8],
+    observations: &mut Vec<Observation>,
+) {
+    let dhcp = match DhcpPacket::new(payload) {
+        Some(packet) => packet,
+        None => return,
+    };
+    let options = parse_dhcp_options(dhcp.payload());
+
+    if dhcp.get_op() == DhcpOperations::Request {
+        // Clients either renew from their current address or ask for one (option 50)
+        let ip = Some(dhcp.get_ciaddr())
+            .filter(|ip| !ip.is_unspecified())
+            .or(options.requested_ip)
+            .map(IpAddr::V4);
+        observations.push(Observation {
+            mac: dhcp.get_chaddr(),
+            ip,
+            hostname: options.hostname,
+            protocol: "DHCP",
+        });
+    } else {
+        // The server talks for itself and announces the client's lease
+        observations.push(Observation {
+            mac: source_mac,
+            ip: Some(source_ip),
+            hostname: None,
+            protocol: "DHCP",
+        });
+        let leased = dhcp.get_yiaddr();
+        if !leased.is_unspecified() {
+            observations.push(Observation {
+                mac: dhcp.get_chaddr(),
+                ip: Some(IpAddr::V4(leased)),
+                hostname: None,
+                protocol: "DHCP",
+            });
+        }
+    }
+}
+
+#[derive(Default)]
+struct DhcpOptions {
+    requested_ip: Option<Ipv4Addr>,
+    hostname: Option<String>,
+}
+
+fn parse_dhcp_options(data: &[u8]) -> DhcpOptions {
+    let mut options = DhcpOptions::default();
+    if !data.starts_with(&DHCP_MAGIC_COOKIE) {
+        return options;
+    }
+
+    let mut i = DHCP_MAGIC_COOKIE.len();
+    while i < data.len() {
+        let code = data[i];
+        match code {
+            0 => {
+                i += 1;
+                continue;
+            }
+            255 => break,
+            _ => {}
+        }
+        if i + 1 >= data.len() {
+            break;
+        }
+        let len = data[i + 1] as usize;
+        let value = match data.get(i + 2..i + 2 + len) {
+            Some(value) => value,
+            None => break,
+        };
+        match code {
+            12 => options.hostname = Some(String::from_utf8_lossy(value).into_owned()),
+            50 if len == 4 => {
+                options.requested_ip = Some(Ipv4Addr::new(value[0], value[1], value[2], value[3]))
+            }
+            _ => {}
+        }
+        i += 2 + len;
+    }
+    options
+}
+
+fn parse_ndp(source_mac: MacAddr, ipv6: &Ipv6Packet, observations: &mut Vec<Observation>) {
+    let icmpv6 = match Icmpv6Packet::new(ipv6.payload()) {
+        Some(packet) => packet,
+        None => return,
+    };
+    let source_ip = ipv6.get_source();
+
+    match icmpv6.get_icmpv6_type() {
+        Icmpv6Types::RouterSolicit | Icmpv6Types::RouterAdvert | Icmpv6Types::NeighborSolicit => {
+            // Duplicate address detection is sent from ::
+            observations.push(Observation {
+                mac: source_mac,
+                ip: (!source_ip.is_unspecified()).then_some(IpAddr::V6(source_ip)),
+                hostname: None,
+                protocol: "NDP",
+            });
+        }
+        Icmpv6Types::NeighborAdvert => {
+            if let Some(advert) = NeighborAdvertPacket::new(ipv6.payload()) {
+                let mac = advert
+                    .get_options_iter()
+                    .find(|option| option.get_option_type() == NdpOptionTypes::TargetLLAddr)
+                    .and_then(|option| {
+                        let data = option.payload();
+                        (data.len() >= 6).then(|| {
+                            MacAddr::new(data[0], data[1], data[2], data[3], data[4], data[5])
+                        })
+                    })
+                    .unwrap_or(source_mac);
+                observations.push(Observation {
+                    mac,
+                    ip: Some(IpAddr::V6(advert.get_target_addr())),
+                    hostname: None,
+                    protocol: "NDP",
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the owner name of the first A/AAAA answer in an mDNS response,
+/// which is how responders announce their `.local` hostname.
+fn mdns_hostname(payload: &[u8]) -> Option<String> {
+    const TYPE_A: u16 = 1;
+    const TYPE_AAAA: u16 = 28;
+
+    if payload.len() < 12 || payload[2] & 0x80 == 0 {
+        return None;
+    }
+    let questions = u16::from_be_bytes([payload[4], payload[5]]);
+    let answers = u16::from_be_bytes([payload[6], payload[7]]);
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        let (_, next) = read_name(payload, offset)?;
+        offset = next + 4;
+    }
+    for _ in 0..answers {
+        let (name, next) = read_name(payload, offset)?;
+        let header = payload.get(next..next + 10)?;
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+        if rtype == TYPE_A || rtype == TYPE_AAAA {
+            return Some(name);
+        }
+        offset = next + 10 + rdlength;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::encode_name;
+    use std::net::Ipv6Addr;
+
+    const CLIENT: [u8; 6] = [0x02, 0, 0, 0, 0, 0x0a];
+    const SERVER: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+
+    fn mac(bytes: [u8; 6]) -> MacAddr {
+        MacAddr::from(bytes)
+    }
+
+    fn ethernet(source: [u8; 6], ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&source);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn udp(source_port: u16, destination_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = source_port.to_be_bytes().to_vec();
+        packet.extend_from_slice(&destination_port.to_be_bytes());
+        packet.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// An IPv4 frame carrying a UDP datagram. Checksums are left at zero,
+    /// which the parsers never check.
+    fn ipv4_udp(source: [u8; 6], source_ip: [u8; 4], datagram: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0];
+        packet.extend_from_slice(&(20 + datagram.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0]);
+        packet.extend_from_slice(&source_ip);
+        packet.extend_from_slice(&[255, 255, 255, 255]);
+        packet.extend_from_slice(datagram);
+        ethernet(source, 0x0800, &packet)
+    }
+
+    fn ipv6(source: [u8; 6], source_ip: Ipv6Addr, next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x60, 0, 0, 0];
+        packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[next_header, 255]);
+        packet.extend_from_slice(&source_ip.octets());
+        packet.extend_from_slice(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).octets());
+        packet.extend_from_slice(payload);
+        ethernet(source, 0x86dd, &packet)
+    }
+
+    fn arp(sender: [u8; 6], sender_ip: [u8; 4]) -> Vec<u8> {
+        let mut packet = vec![0, 1, 8, 0, 6, 4, 0, 1];
+        packet.extend_from_slice(&sender);
+        packet.extend_from_slice(&sender_ip);
+        packet.extend_from_slice(&[0; 6]);
+        packet.extend_from_slice(&[192, 168, 1, 1]);
+        ethernet(sender, 0x0806, &packet)
+    }
+
+    /// A BOOTP header for `client` followed by the DHCP cookie and `options`.
+    fn dhcp(op: u8, client: [u8; 6], ciaddr: [u8; 4], yiaddr: [u8; 4], options: &[u8]) -> Vec<u8> {
+        let mut packet = vec![op, 1, 6, 0, 0x12, 0x34, 0x56, 0x78, 0, 0, 0, 0];
+        packet.extend_from_slice(&ciaddr);
+        packet.extend_from_slice(&yiaddr);
+        packet.extend_from_slice(&[0; 8]);
+        packet.extend_from_slice(&client);
+        packet.extend_from_slice(&[0; 10 + 64 + 128]);
+        packet.extend_from_slice(&DHCP_MAGIC_COOKIE);
+        packet.extend_from_slice(options);
+        packet
+    }
+
+    fn summary(observations: &[Observation]) -> Vec<(MacAddr, Option<IpAddr>, Option<&str>)> {
+        observations
+            .iter()
+            .map(|observation| {
+                (
+                    observation.mac,
+                    observation.ip,
+                    observation.hostname.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_arp_and_dhcp_frames() {
+        let observations = parse_frame(&arp(CLIENT, [192, 168, 1, 10]));
+        assert_eq!(
+            summary(&observations),
+            [(mac(CLIENT), Some("192.168.1.10".parse().unwrap()), None)]
+        );
+        // An ARP probe has no address yet
+        assert_eq!(parse_frame(&arp(CLIENT, [0; 4]))[0].ip, None);
+
+        let mut options = vec![0, 12, 6];
+        options.extend_from_slice(b"laptop");
+        options.extend_from_slice(&[50, 4, 192, 168, 1, 23, 255]);
+        let request = dhcp(1, CLIENT, [0; 4], [0; 4], &options);
+        let frame = ipv4_udp(CLIENT, [0; 4], &udp(68, 67, &request));
+        assert_eq!(
+            summary(&parse_frame(&frame)),
+            [(
+                mac(CLIENT),
+                Some("192.168.1.23".parse().unwrap()),
+                Some("laptop")
+            )]
+        );
+
+        let ack = dhcp(2, CLIENT, [0; 4], [192, 168, 1, 23], &[255]);
+        let frame = ipv4_udp(SERVER, [192, 168, 1, 1], &udp(67, 68, &ack));
+        assert_eq!(
+            summary(&parse_frame(&frame)),
+            [
+                (mac(SERVER), Some("192.168.1.1".parse().unwrap()), None),
+                (mac(CLIENT), Some("192.168.1.23".parse().unwrap()), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_dhcp_options_defensively() {
+        let mut data = DHCP_MAGIC_COOKIE.to_vec();
+        data.extend_from_slice(&[50, 3, 10, 0, 0, 12, 4, b'p', b'c']);
+        let options = parse_dhcp_options(&data);
+        // A requested address of the wrong length is ignored, and an option
+        // running past the end stops parsing
+        assert_eq!(options.requested_ip, None);
+        assert_eq!(options.hostname, None);
+
+        assert!(parse_dhcp_options(&[1, 2, 3]).hostname.is_none());
+        let mut data = DHCP_MAGIC_COOKIE.to_vec();
+        data.push(12);
+        assert!(parse_dhcp_options(&data).hostname.is_none());
+    }
+
+    #[test]
+    fn reads_ndp_and_mdns_announcements() {
+        let source_ip: Ipv6Addr = "fe80::a".parse().unwrap();
+        let target: Ipv6Addr = "2001:db8::a".parse().unwrap();
+        let mut advert = vec![136, 0, 0, 0, 0x60, 0, 0, 0];
+        advert.extend_from_slice(&target.octets());
+        advert.extend_from_slice(&[2, 1]);
+        advert.extend_from_slice(&CLIENT);
+        let frame = ipv6(SERVER, source_ip, 58, &advert);
+        assert_eq!(
+            summary(&parse_frame(&frame)),
+            [(mac(CLIENT), Some(IpAddr::V6(target)), None)]
+        );
+
+        // Duplicate address detection comes from ::
+        let solicit = [135, 0, 0, 0, 0, 0, 0, 0];
+        let mut frame = ipv6(CLIENT, Ipv6Addr::UNSPECIFIED, 58, &solicit);
+        frame.extend_from_slice(&target.octets());
+        assert_eq!(summary(&parse_frame(&frame)), [(mac(CLIENT), None, None)]);
+
+        let mut response = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        response.extend_from_slice(&encode_name("printer.local"));
+        response.extend_from_slice(&[0, 1, 0x80, 1, 0, 0, 0, 120, 0, 4, 192, 168, 1, 40]);
+        assert_eq!(mdns_hostname(&response).as_deref(), Some("printer.local"));
+        let frame = ipv4_udp(CLIENT, [192, 168, 1, 40], &udp(5353, 5353, &response));
+        assert_eq!(
+            parse_frame(&frame)[0].hostname.as_deref(),
+            Some("printer.local")
+        );
+        // Queries carry no answers
+        response[2] = 0;
+        assert_eq!(mdns_hostname(&response), None);
+    }
+
+    #[test]
+    fn survives_truncated_frames() {
+        let mut options = vec![12, 6];
+        options.extend_from_slice(b"laptop");
+        let request = dhcp(1, CLIENT, [0; 4], [0; 4], &options);
+        let mut advert = vec![136, 0, 0, 0, 0x60, 0, 0, 0];
+        advert.extend_from_slice(&[0x20; 16]);
+        advert.extend_from_slice(&[2, 1]);
+        advert.extend_from_slice(&CLIENT);
+        let mut response = vec![0, 0, 0x84, 0, 0, 1, 0, 1, 0, 0, 0, 0];
+        response.extend_from_slice(&encode_name("printer.local"));
+        response.extend_from_slice(&[0, 1, 0, 1, 0xC0, 12, 0, 1, 0x80, 1, 0, 0, 0, 120]);
+
+        for frame in [
+            arp(CLIENT, [192, 168, 1, 10]),
+            ipv4_udp(CLIENT, [0; 4], &udp(68, 67, &request)),
+            ipv6(SERVER, "fe80::1".parse().unwrap(), 58, &advert),
+            ipv4_udp(CLIENT, [192, 168, 1, 40], &udp(5353, 5353, &response)),
+        ] {
+            for len in 0..frame.len() {
+                parse_frame(&frame[..len]);
+            }
+        }
+        for len in 0..response.len() {
+            mdns_hostname(&response[..len]);
+        }
+        // An option claiming zero length must not stall the NDP option walk
+        let mut advert = advert.clone();
+        advert[21] = 0;
+        parse_frame(&ipv6(SERVER, "fe80::1".parse().unwrap(), 58, &advert));
+    }
+
+    #[test]
+    fn reports_new_devices_and_addresses() {
+        let mut table = DeviceTable::default();
+        let now = Local::now();
+        let seen = |ip: &str| Observation {
+            mac: mac(CLIENT),
+            ip: Some(ip.parse().unwrap()),
+            hostname: None,
+            protocol: "ARP",
+        };
+        assert!(matches!(
+            table.observe(seen("192.168.1.10"), now),
+            Change::NewDevice
+        ));
+        assert!(matches!(
+            table.observe(seen("192.168.1.10"), now),
+            Change::Unchanged
+        ));
+        let later = now + chrono::Duration::seconds(5);
+        match table.observe(seen("fe80::a"), later) {
+            Change::NewAddress(ip) => assert_eq!(ip, "fe80::a".parse::<IpAddr>().unwrap()),
+            _ => panic!("expected a new address"),
+        }
+        let device = table.get(&mac(CLIENT)).unwrap();
+        assert_eq!(device.ips.len(), 2);
+        assert_eq!((device.first_seen, device.last_seen), (now, later));
+        assert_eq!(table.len(), 1);
+    }
+}
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PORTS: &str = "21,22,23,25,53,80,110,143,443,445,3389,8080";
+pub const DEFAULT_CONCURRENCY: usize = 32;
+/// Connection attempts per second across all workers.
+pub const DEFAULT_RATE: u32 = 100;
+const TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_BANNER: usize = 120;
+
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    pub ports: Vec<u16>,
+    pub concurrency: usize,
+    pub rate: u32,
+    pub timeout: Duration,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        ProbeConfig {
+            ports: parse_ports(DEFAULT_PORTS).unwrap_or_default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            rate: DEFAULT_RATE,
+            timeout: TIMEOUT,
+        }
+    }
+}
+
+/// An open TCP port and what the service said about itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Service {
+    pub port: u16,
+    pub banner: Option<String>,
+}
+
+/// Parses `22,80,8000-8010`.
+pub fn parse_ports(list: &str) -> Result<Vec<u16>, String> {
+    let mut ports = Vec::new();
+    for part in list
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let invalid = || format!("Invalid port {}", part);
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: u16 = start.parse().map_err(|_| invalid())?;
+                let end: u16 = end.parse().map_err(|_| invalid())?;
+                if start > end {
+                    return Err(invalid());
+                }
+                ports.extend(start..=end);
+            }
+            None => ports.push(part.parse().map_err(|_| invalid())?),
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    if ports.contains(&0) {
+        return Err("Port 0 cannot be probed".to_string());
+    }
+    Ok(ports)
+}
+
+/// Hands out evenly spaced start times to any number of threads.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Connects to every port of every host and returns the open services per host,
+/// in the order the hosts were given.
+pub fn probe_hosts(hosts: &[IpAddr], config: &ProbeConfig) -> Vec<(IpAddr, Vec<Service>)> {
+    let mut targets: Vec<(usize, u16)> = (0..hosts.len())
+        .flat_map(|host| config.ports.iter().map(move |port| (host, *port)))
+        .collect();
+    targets.reverse();
+    let targets = Mutex::new(targets);
+    let limiter = RateLimiter::new(config.rate);
+    let found = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..config.concurrency.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let Some((host, port)) = targets.lock().unwrap().pop() else {
+                        break;
+                    };
+                    limiter.wait();
+                    if let Some(service) = probe_port(hosts[host], port, config.timeout) {
+                        found.lock().unwrap().push((host, service));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut results: Vec<(IpAddr, Vec<Service>)> =
+        hosts.iter().map(|host| (*host, Vec::new())).collect();
+    for (host, service) in found.into_inner().unwrap() {
+        results[host].1.push(service);
+    }
+    for (_, services) in &mut results {
+        services.sort_by_key(|service| service.port);
+    }
+    results
+}
+
+/// `None` when the port is closed or filtered.
+pub fn probe_port(host: IpAddr, port: u16, timeout: Duration) -> Option<Service> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::new(host, port), timeout).ok()?;
+    Some(Service {
+        port,
+        banner: grab_banner(&mut stream, host, timeout),
+    })
+}
+
+fn grab_banner(stream: &mut TcpStream, host: IpAddr, timeout: Duration) -> Option<String> {
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    let mut buffer = [0u8; 1024];
+
+    // SSH, SMTP and FTP greet first; HTTP waits for a request
+    let read = match stream.read(&mut buffer) {
+        Ok(read) if read > 0 => read,
+        _ => {
+            let request = format!("HEAD / HTTP/1.0\r\nHost: {}\r\n\r\n", host);
+            stream.write_all(request.as_bytes()).ok()?;
+            stream.read(&mut buffer).ok()?
+        }
+    };
+    summarize(&String::from_utf8_lossy(&buffer[..read]))
+}
+
+/// Reduces a raw response to one line: the HTTP `Server` header, or the first
+/// line of a greeting such as `SSH-2.0-OpenSSH_9.6` or `220 mail ESMTP Postfix`.
+pub fn summarize(raw: &str) -> Option<String> {
+    let line = if raw.starts_with("HTTP/") {
+        raw.lines()
+            .find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case("server").then_some(value)
+            })
+            .or_else(|| raw.lines().next())?
+    } else {
+        raw.lines().next()?
+    };
+    let line: String = line
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_BANNER)
+        .collect();
+    (!line.is_empty()).then_some(line)
+}
+
+/// One cell per service, e.g. `22 SSH-2.0-OpenSSH_9.6, 80 nginx`.
+pub fn describe(services: &[Service]) -> String {
+    services
+        .iter()
+        .map(|service| match &service.banner {
+            Some(banner) => format!("{} {}", service.port, banner),
+            None => service.port.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    /// Serves one connection with `respond` on a free port.
+    fn listener(respond: fn(TcpStream)) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            if let Ok((stream, _)) = listener.accept() {
+                respond(stream);
+            }
+        });
+        port
+    }
+
+    fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    fn config(ports: Vec<u16>) -> ProbeConfig {
+        ProbeConfig {
+            ports,
+            timeout: Duration::from_millis(300),
+            ..ProbeConfig::default()
+        }
+    }
+
+    #[test]
+    fn grabs_greetings_and_http_server_headers() {
+        let ssh = listener(|mut stream| {
+            stream
+                .write_all(b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3\r\n")
+                .unwrap();
+        });
+        let smtp = listener(|mut stream| {
+            stream
+                .write_all(b"220 mail.example.com ESMTP Postfix\r\n")
+                .unwrap();
+            thread::sleep(Duration::from_millis(100));
+        });
+        let http = listener(|mut stream| {
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            assert!(request.starts_with("HEAD / HTTP/1.0"));
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nServer: nginx/1.24.0\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+        let closed = closed_port();
+
+        let results = probe_hosts(&[LOCALHOST], &config(vec![ssh, smtp, http, closed]));
+        let mut services = results[0].1.clone();
+        services.sort_by_key(|service| [ssh, smtp, http].iter().position(|p| *p == service.port));
+        let banners: Vec<Option<&str>> = services
+            .iter()
+            .map(|service| service.banner.as_deref())
+            .collect();
+        assert_eq!(
+            banners,
+            [
+                Some("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3"),
+                Some("220 mail.example.com ESMTP Postfix"),
+                Some("nginx/1.24.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn silent_services_are_reported_without_banner() {
+        let silent = listener(|stream| {
+            thread::sleep(Duration::from_millis(800));
+            drop(stream);
+        });
+        let service = probe_port(LOCALHOST, silent, Duration::from_millis(200)).unwrap();
+        assert_eq!(service.banner, None);
+        assert!(probe_port(LOCALHOST, closed_port(), Duration::from_millis(200)).is_none());
+    }
+
+    #[test]
+    fn rate_limit_spaces_out_connections() {
+        let ports: Vec<u16> = (0..5).map(|_| closed_port()).collect();
+        let started = Instant::now();
+        probe_hosts(
+            &[LOCALHOST],
+            &ProbeConfig {
+                rate: 20,
+                ..config(ports)
+            },
+        );
+        // Five attempts at 20/s start 0, 50, 100, 150 and 200ms in
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn parses_port_lists() {
+        assert_eq!(
+            parse_ports("80,22, 8000-8002,22").unwrap(),
+            [22, 80, 8000, 8001, 8002]
+        );
+        assert!(parse_ports("90-80").is_err());
+        assert!(parse_ports("ssh").is_err());
+        assert!(parse_ports("0").is_err());
+    }
+
+    #[test]
+    fn summarizes_http_without_server_header() {
+        assert_eq!(
+            summarize("HTTP/1.0 404 Not Found\r\n\r\n").as_deref(),
+            Some("HTTP/1.0 404 Not Found")
+        );
+        assert_eq!(summarize("\r\n"), None);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+use sysinfo::Signal;
+
+use crate::system_info::{ProcessInfo, SystemInfo, format_bytes};
+
+/// CPU usage and I/O are deltas, so take a second sample before printing.
+const SAMPLE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+pub struct ProcessFilter {
+    /// Case-insensitive substring of the process name or command line.
+    pub name: Option<String>,
+    /// Exact user name.
+    pub user: Option<String>,
+}
+
+impl ProcessFilter {
+    pub fn matches(&self, process: &ProcessInfo) -> bool {
+        let name_matches = self.name.as_ref().is_none_or(|pattern| {
+            let pattern = pattern.to_lowercase();
+            process.name.to_lowercase().contains(&pattern)
+                || process.command.to_lowercase().contains(&pattern)
+        });
+        let user_matches = self.user.as_ref().is_none_or(|user| process.user == *user);
+        name_matches && user_matches
+    }
+}
+
+pub fn parse_signal(name: &str) -> Option<Signal> {
+    let name = name.to_uppercase();
+    let name = name.trim_start_matches("SIG");
+    let signal = match name {
+        "HUP" | "1" => Signal::Hangup,
+        "INT" | "2" => Signal::Interrupt,
+        "QUIT" | "3" => Signal::Quit,
+        "KILL" | "9" => Signal::Kill,
+        "USR1" | "10" => Signal::User1,
+        "USR2" | "12" => Signal::User2,
+        "TERM" | "15" => Signal::Term,
+        "CONT" | "18" => Signal::Continue,
+        "STOP" | "19" => Signal::Stop,
+        _ => return None,
+    };
+    Some(signal)
+}
+
+pub fn run_ps(tree: bool, filter: &ProcessFilter) {
+    let mut system_info = SystemInfo::new();
+    thread::sleep(SAMPLE_DELAY);
+    system_info.refresh();
+
+    let mut processes: Vec<ProcessInfo> = system_info
+        .get_processes()
+        .into_iter()
+        .filter(|process| filter.matches(process))
+        .collect();
+
+    if tree {
+        print_tree(&mut processes);
+    } else {
+        processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+        print_header();
+        for process in &processes {
+            print_row(process, &process.command);
+        }
+    }
+}
+
+fn print_header() {
+    println!(
+        "{:>7} {:>7} {:<12} {:>6} {:>10} {:>10} {:>10} {:>5}  COMMAND",
+        "PID", "PPID", "USER", "CPU%", "RSS", "READ/s", "WRITE/s", "FDS"
+    );
+}
+
+fn print_row(process: &ProcessInfo, command: &str) {
+    let ppid = process
+        .ppid
+        .map_or("-".to_string(), |ppid| ppid.to_string());
+    let fds = process
+        .open_files
+        .map_or("-".to_string(), |count| count.to_string());
+    println!(
+        "{:>7} {:>7} {:<12} {:>6.1} {:>10} {:>10} {:>10} {:>5}  {}",
+        process.pid,
+        ppid,
+        truncate(&process.user, 12),
+        process.cpu_usage,
+        format_bytes(process.memory * 1024),
+        format_bytes(process.disk_read),
+        format_bytes(process.disk_written),
+        fds,
+        command
+    );
+}
+
+/// Prints processes indented under their parents. A process whose parent was
+/// filtered out or has exited is shown as a root.
+fn print_tree(processes: &mut [ProcessInfo]) {
+    print_header();
+    for (i, depth) in tree_order(processes) {
+        let process = &processes[i];
+        let branch = if depth == 0 { "" } else { "└─ " };
+        let command = format!(
+            "{}{}{}",
+            "   ".repeat(depth.saturating_sub(1)),
+            branch,
+            process.command
+        );
+        print_row(process, &command);
+    }
+}
+
+/// Sorts `processes` by PID and returns the index and depth of each in
+/// depth-first order, children after their parent.
+fn tree_order(processes: &mut [ProcessInfo]) -> Vec<(usize, usize)> {
+    processes.sort_by_key(|process| process.pid);
+    let known: HashMap<u32, usize> = processes
+        .iter()
+        .enumerate()
+        .map(|(i, process)| (process.pid, i))
+        .collect();
+
+    let mut children: HashMap<u32, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (i, process) in processes.iter().enumerate() {
+        match process.ppid.filter(|ppid| known.contains_key(ppid)) {
+            Some(ppid) => children.entry(ppid).or_default().push(i),
+            None => roots.push(i),
+        }
+    }
+
+    // Iterative depth-first walk so deep trees cannot overflow the stack
+    let mut order = Vec::with_capacity(processes.len());
+    let mut stack: Vec<(usize, usize)> = roots.into_iter().rev().map(|i| (i, 0)).collect();
+    while let Some((i, depth)) = stack.pop() {
+        order.push((i, depth));
+        if let Some(kids) = children.get(&processes[i].pid) {
+            stack.extend(kids.iter().rev().map(|&child| (child, depth + 1)));
+        }
+    }
+    order
+}
+
+pub fn run_kill(pid: u32, signal: Signal) {
+    let system_info = SystemInfo::new();
+    let process = match system_info
+        .get_processes()
+        .into_iter()
+        .find(|process| process.pid == pid)
+    {
+        Some(process) => process,
+        None => {
+            println!("[-] No process with PID {}", pid);
+            return;
+        }
+    };
+
+    print!(
+        "Send {:?} to PID {} ({}, user {})? [y/N] ",
+        signal, pid, process.command, process.user
+    );
+    io::stdout().flush().ok();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() || !confirmed(&answer) {
+        println!("[-] Aborted");
+        return;
+    }
+
+    match system_info.send_signal(pid, signal) {
+        Ok(()) => println!("[+] Sent {:?} to PID {}", signal, pid),
+        Err(e) => println!("[-] {}", e),
+    }
+}
+
+pub fn confirmed(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(width - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, ppid: Option<u32>, name: &str, user: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid,
+            name: name.to_string(),
+            user: user.to_string(),
+            command: format!("/usr/bin/{} --serve", name),
+            ..ProcessInfo::default()
+        }
+    }
+
+    #[test]
+    fn parses_signal_names_in_any_case() {
+        for name in ["TERM", "SIGTERM", "sigterm", "term", "15"] {
+            assert_eq!(parse_signal(name), Some(Signal::Term), "{}", name);
+        }
+        assert_eq!(parse_signal("SigKill"), Some(Signal::Kill));
+        assert_eq!(parse_signal("usr1"), Some(Signal::User1));
+        assert_eq!(parse_signal("SIG"), None);
+        assert_eq!(parse_signal("WINCH"), None);
+    }
+
+    #[test]
+    fn orders_children_under_their_parents() {
+        // PID 7's parent is not in the list, so it is a root
+        let mut processes = vec![
+            process(30, Some(10), "worker", "www"),
+            process(10, Some(1), "nginx", "root"),
+            process(1, None, "init", "root"),
+            process(20, Some(10), "worker", "www"),
+            process(7, Some(99), "orphan", "root"),
+            process(40, Some(20), "helper", "www"),
+        ];
+        let order: Vec<(u32, usize)> = tree_order(&mut processes)
+            .into_iter()
+            .map(|(i, depth)| (processes[i].pid, depth))
+            .collect();
+        assert_eq!(order, [(1, 0), (10, 1), (20, 2), (40, 3), (30, 2), (7, 0)]);
+    }
+
+    #[test]
+    fn filters_by_name_command_and_user() {
+        let nginx = process(10, None, "nginx", "root");
+        let filter = |name: Option<&str>, user: Option<&str>| ProcessFilter {
+            name: name.map(str::to_string),
+            user: user.map(str::to_string),
+        };
+        assert!(filter(None, None).matches(&nginx));
+        assert!(filter(Some("NGINX"), None).matches(&nginx));
+        assert!(filter(Some("--serve"), Some("root")).matches(&nginx));
+        assert!(!filter(Some("nginx"), Some("www")).matches(&nginx));
+        assert!(!filter(Some("apache"), None).matches(&nginx));
+        // User names match exactly
+        assert!(!filter(None, Some("ROOT")).matches(&nginx));
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::net::Ipv4Addr;
+
+use crate::probe::{self, Service};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A host that answered a sweep.
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    pub ip: Ipv4Addr,
+    pub mac: String,
+    pub vendor: Option<String>,
+    pub hostname: Option<String>,
+    pub first_seen: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    /// ARP round trip in milliseconds. `None` when the host was only found in the ARP cache.
+    pub rtt_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<Service>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanReport {
+    pub range: String,
+    pub interface: String,
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub devices: Vec<Device>,
+}
+
+impl ScanReport {
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Table => self.to_table(),
+            OutputFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default() + "\n",
+            OutputFormat::Csv => self.to_csv(),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        let mut out = format!(
+            "\nDiscovered {} devices on {} in {:.1}s:\n",
+            self.devices.len(),
+            self.range,
+            (self.finished - self.started).num_milliseconds() as f64 / 1000.0
+        );
+        out.push_str(&format!(
+            "{:<15} {:<17} {:>8}  {:<24} {:<24} SERVICES\n",
+            "IP ADDRESS", "MAC ADDRESS", "RTT", "VENDOR", "HOSTNAME"
+        ));
+        for device in &self.devices {
+            out.push_str(&format!(
+                "{:<15} {:<17} {:>8}  {:<24} {:<24} {}\n",
+                device.ip.to_string(),
+                device.mac,
+                device
+                    .rtt_ms
+                    .map_or("-".to_string(), |rtt| format!("{:.1}ms", rtt)),
+                device.vendor.as_deref().unwrap_or("-"),
+                device.hostname.as_deref().unwrap_or("-"),
+                probe::describe(&device.services)
+            ));
+        }
+        out
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::from("ip,mac,vendor,hostname,first_seen,last_seen,rtt_ms,services\n");
+        for device in &self.devices {
+            let fields = [
+                device.ip.to_string(),
+                device.mac.clone(),
+                device.vendor.clone().unwrap_or_default(),
+                device.hostname.clone().unwrap_or_default(),
+                device.first_seen.to_rfc3339(),
+                device.last_seen.to_rfc3339(),
+                device
+                    .rtt_ms
+                    .map(|rtt| format!("{:.3}", rtt))
+                    .unwrap_or_default(),
+                probe::describe(&device.services),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Quotes a field that contains a separator, quote or newline.
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn report() -> ScanReport {
+        let seen = Local.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        ScanReport {
+            range: "192.168.1.0/24".to_string(),
+            interface: "eth0".to_string(),
+            started: seen,
+            finished: seen,
+            devices: vec![Device {
+                ip: Ipv4Addr::new(192, 168, 1, 10),
+                mac: "b8:27:eb:12:34:56".to_string(),
+                vendor: Some("Raspberry Pi Foundation".to_string()),
+                hostname: None,
+                first_seen: seen,
+                last_seen: seen,
+                rtt_ms: Some(1.25),
+                services: vec![
+                    Service {
+                        port: 22,
+                        banner: Some("SSH-2.0-OpenSSH_9.2".to_string()),
+                    },
+                    Service {
+                        port: 80,
+                        banner: None,
+                    },
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn renders_csv_with_quoting() {
+        let csv = report().render(OutputFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("192.168.1.10,b8:27:eb:12:34:56,Raspberry Pi Foundation,,"));
+        assert!(lines[1].ends_with(",1.250,\"22 SSH-2.0-OpenSSH_9.2, 80\""));
+    }
+
+    #[test]
+    fn renders_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&report().render(OutputFormat::Json)).unwrap();
+        let device = &json["devices"][0];
+        assert_eq!(device["ip"], "192.168.1.10");
+        assert_eq!(device["rtt_ms"], 1.25);
+        assert_eq!(device["hostname"], serde_json::Value::Null);
+        assert_eq!(device["services"][0]["port"], 22);
+    }
+}
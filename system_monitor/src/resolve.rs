@@ -0,0 +1,431 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_CONCURRENCY: usize = 32;
+const TIMEOUT: Duration = Duration::from_secs(1);
+const DNS_PORT: u16 = 53;
+const MDNS_PORT: u16 = 5353;
+const NBNS_PORT: u16 = 137;
+
+const TYPE_PTR: u16 = 12;
+const TYPE_NBSTAT: u16 = 33;
+const CLASS_IN: u16 = 1;
+/// Asks an mDNS responder to answer us directly rather than the multicast group.
+const CLASS_IN_UNICAST: u16 = 0x8001;
+
+#[derive(Debug, Clone)]
+pub struct ResolveConfig {
+    /// Server for PTR lookups. `None` skips them.
+    pub resolver: Option<SocketAddr>,
+    pub mdns_port: u16,
+    pub nbns_port: u16,
+    pub concurrency: usize,
+    pub timeout: Duration,
+}
+
+impl Default for ResolveConfig {
+    fn default() -> Self {
+        ResolveConfig {
+            resolver: fs::read_to_string("/etc/resolv.conf")
+                .ok()
+                .and_then(|contents| parse_resolv_conf(&contents)),
+            mdns_port: MDNS_PORT,
+            nbns_port: NBNS_PORT,
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout: TIMEOUT,
+        }
+    }
+}
+
+/// What each method said a host is called.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hostnames {
+    pub ptr: Option<String>,
+    pub mdns: Option<String>,
+    pub netbios: Option<String>,
+}
+
+impl Hostnames {
+    /// Reverse DNS is what the network admin configured, so it wins over
+    /// names the devices pick for themselves.
+    pub fn best(&self) -> Option<String> {
+        self.ptr
+            .clone()
+            .or_else(|| self.mdns.clone())
+            .or_else(|| self.netbios.clone())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Method {
+    Ptr,
+    Mdns,
+    Netbios,
+}
+
+/// First `nameserver` in resolv.conf syntax.
+pub fn parse_resolv_conf(contents: &str) -> Option<SocketAddr> {
+    contents.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        (fields.next()? == "nameserver")
+            .then(|| fields.next()?.parse().ok())
+            .flatten()
+            .map(|ip: IpAddr| SocketAddr::new(ip, DNS_PORT))
+    })
+}
+
+/// Accepts `1.1.1.1`, `1.1.1.1:5353` or `[::1]:53`.
+pub fn parse_resolver(text: &str) -> Result<SocketAddr, String> {
+    text.parse()
+        .or_else(|_| text.parse().map(|ip: IpAddr| SocketAddr::new(ip, DNS_PORT)))
+        .map_err(|_| format!("Invalid resolver {}", text))
+}
+
+/// Looks every host up with every method at once and returns the names in the
+/// order the hosts were given. Hosts that stay silent just get no name.
+pub fn resolve_hosts(hosts: &[Ipv4Addr], config: &ResolveConfig) -> Vec<Hostnames> {
+    let mut lookups: Vec<(usize, Method)> = (0..hosts.len())
+        .flat_map(|host| {
+            let ptr = config.resolver.map(|_| (host, Method::Ptr));
+            ptr.into_iter()
+                .chain([(host, Method::Mdns), (host, Method::Netbios)])
+        })
+        .collect();
+    lookups.reverse();
+    let lookups = Mutex::new(lookups);
+    let results = Mutex::new(vec![Hostnames::default(); hosts.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..config.concurrency.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let Some((host, method)) = lookups.lock().unwrap().pop() else {
+                        break;
+                    };
+                    let ip = hosts[host];
+                    let name = match method {
+                        Method::Ptr => config
+                            .resolver
+                            .and_then(|server| lookup_ptr(ip, server, config.timeout)),
+                        Method::Mdns => lookup_mdns(ip, config.mdns_port, config.timeout),
+                        Method::Netbios => lookup_netbios(ip, config.nbns_port, config.timeout),
+                    };
+                    let mut results = results.lock().unwrap();
+                    match method {
+                        Method::Ptr => results[host].ptr = name,
+                        Method::Mdns => results[host].mdns = name,
+                        Method::Netbios => results[host].netbios = name,
+                    }
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+/// `10.1.168.192.in-addr.arpa` for 192.168.1.10.
+pub fn reverse_name(ip: Ipv4Addr) -> String {
+    let [a, b, c, d] = ip.octets();
+    format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+}
+
+pub fn lookup_ptr(ip: Ipv4Addr, server: SocketAddr, timeout: Duration) -> Option<String> {
+    let id = transaction_id();
+    // Recursion desired
+    let query = build_query(id, 0x0100, &reverse_name(ip), TYPE_PTR, CLASS_IN);
+    let reply = exchange(&query, server, timeout)?;
+    parse_ptr_reply(&reply, id)
+}
+
+/// Sends the reverse query straight to the host's responder rather than the
+/// multicast group, so only that host answers.
+pub fn lookup_mdns(ip: Ipv4Addr, port: u16, timeout: Duration) -> Option<String> {
+    let id = transaction_id();
+    let query = build_query(id, 0, &reverse_name(ip), TYPE_PTR, CLASS_IN_UNICAST);
+    let reply = exchange(&query, SocketAddr::new(IpAddr::V4(ip), port), timeout)?;
+    parse_ptr_reply(&reply, id)
+        .map(|name| name.trim_end_matches('.').to_string())
+        .filter(|name| name.ends_with(".local"))
+}
+
+/// NetBIOS node status, which Windows machines and Samba servers answer with
+/// their computer name.
+pub fn lookup_netbios(ip: Ipv4Addr, port: u16, timeout: Duration) -> Option<String> {
+    let id = transaction_id();
+    let query = build_query(id, 0, &netbios_name("*"), TYPE_NBSTAT, CLASS_IN);
+    let reply = exchange(&query, SocketAddr::new(IpAddr::V4(ip), port), timeout)?;
+    parse_node_status(&reply, id)
+}
+
+fn transaction_id() -> u16 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    (nanos ^ (nanos >> 16)) as u16 ^ std::process::id() as u16
+}
+
+/// Sends one datagram and waits for a reply carrying the same transaction ID.
+fn exchange(query: &[u8], server: SocketAddr, timeout: Duration) -> Option<Vec<u8>> {
+    let bind = match server {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.send_to(query, server).ok()?;
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return None;
+        }
+        socket.set_read_timeout(Some(remaining)).ok()?;
+        let (read, _) = socket.recv_from(&mut buffer).ok()?;
+        if read >= 2 && buffer[..2] == query[..2] {
+            return Some(buffer[..read].to_vec());
+        }
+    }
+}
+
+pub fn build_query(id: u16, flags: u16, name: &str, qtype: u16, qclass: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(12 + name.len() + 6);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&flags.to_be_bytes());
+    // One question, no answer, authority or additional records
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    packet.extend_from_slice(&encode_name(name));
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&qclass.to_be_bytes());
+    packet
+}
+
+pub fn encode_name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        encoded.push(label.len().min(63) as u8);
+        encoded.extend_from_slice(&label.as_bytes()[..label.len().min(63)]);
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// First-level NetBIOS encoding: the name padded to 16 bytes, each byte split
+/// into two letters from `A`. The wildcard `*` pads with NULs, names with spaces.
+pub fn netbios_name(name: &str) -> String {
+    let pad = if name == "*" { 0 } else { b' ' };
+    let mut bytes = [pad; 16];
+    for (slot, byte) in bytes.iter_mut().zip(name.bytes().take(15)) {
+        *slot = byte.to_ascii_uppercase();
+    }
+    bytes
+        .iter()
+        .flat_map(|byte| [b'A' + (byte >> 4), b'A' + (byte & 0x0F)])
+        .map(char::from)
+        .collect()
+}
+
+/// The answer records of a reply to `id`, as `(type, rdata offset, rdata length)`.
+/// `None` for other IDs, queries and errors such as NXDOMAIN.
+fn answers(packet: &[u8], id: u16) -> Option<Vec<(u16, usize, usize)>> {
+    let header = packet.get(..12)?;
+    let is_reply = header[2] & 0x80 != 0;
+    let rcode = header[3] & 0x0F;
+    if u16::from_be_bytes([header[0], header[1]]) != id || !is_reply || rcode != 0 {
+        return None;
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let count = u16::from_be_bytes([header[6], header[7]]);
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        let (_, next) = read_name(packet, offset)?;
+        offset = next + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let (_, next) = read_name(packet, offset)?;
+        let fixed = packet.get(next..next + 10)?;
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let rdlength = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        records.push((rtype, next + 10, rdlength));
+        offset = next + 10 + rdlength;
+    }
+    Some(records)
+}
+
+pub fn parse_ptr_reply(packet: &[u8], id: u16) -> Option<String> {
+    answers(packet, id)?
+        .into_iter()
+        .filter(|(rtype, _, _)| *rtype == TYPE_PTR)
+        .find_map(|(_, offset, _)| read_name(packet, offset))
+        .map(|(name, _)| name)
+        .filter(|name| !name.is_empty())
+}
+
+/// The unique workstation name (suffix 0x00) from a node status reply.
+pub fn parse_node_status(packet: &[u8], id: u16) -> Option<String> {
+    let (_, offset, length) = answers(packet, id)?
+        .into_iter()
+        .find(|(rtype, _, _)| *rtype == TYPE_NBSTAT)?;
+    let rdata = packet.get(offset..offset + length)?;
+    let count = *rdata.first()? as usize;
+    rdata[1..]
+        .chunks_exact(18)
+        .take(count)
+        .find(|entry| entry[15] == 0x00 && entry[16] & 0x80 == 0)
+        .map(|entry| String::from_utf8_lossy(&entry[..15]).trim_end().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Reads a possibly compressed DNS name, returning it and the offset just past it.
+pub fn read_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bound the number of pointer jumps so a malicious loop cannot spin forever
+    for _ in 0..64 {
+        let len = *packet.get(offset)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(offset + 1)));
+        }
+        if len & 0xC0 == 0xC0 {
+            let pointer = ((len & 0x3F) << 8) | *packet.get(offset + 1)? as usize;
+            end.get_or_insert(offset + 2);
+            offset = pointer;
+            continue;
+        }
+        let label = packet.get(offset + 1..offset + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += 1 + len;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCALHOST: Ipv4Addr = Ipv4Addr::LOCALHOST;
+
+    /// Answers every query on a free UDP port with `respond`, or stays silent
+    /// when it returns `None`.
+    fn stub(respond: fn(&[u8]) -> Option<Vec<u8>>) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            while let Ok((read, peer)) = socket.recv_from(&mut buffer) {
+                if let Some(reply) = respond(&buffer[..read]) {
+                    socket.send_to(&reply, peer).unwrap();
+                }
+            }
+        });
+        port
+    }
+
+    /// Echoes the question and appends one answer pointing back at its name.
+    fn reply(query: &[u8], rcode: u8, rtype: u16, rdata: &[u8]) -> Vec<u8> {
+        let (_, question_end) = read_name(query, 12).unwrap();
+        let mut packet = query[..question_end + 4].to_vec();
+        packet[2] |= 0x80;
+        packet[3] = 0x80 | rcode;
+        if rcode == 0 {
+            packet[7] = 1;
+            packet.extend_from_slice(&[0xC0, 0x0C]);
+            packet.extend_from_slice(&rtype.to_be_bytes());
+            packet.extend_from_slice(&[0, 1, 0, 0, 0x0E, 0x10]);
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(rdata);
+        }
+        packet
+    }
+
+    fn config(resolver: Option<u16>, mdns_port: u16, nbns_port: u16) -> ResolveConfig {
+        ResolveConfig {
+            resolver: resolver.map(|port| SocketAddr::new(IpAddr::V4(LOCALHOST), port)),
+            mdns_port,
+            nbns_port,
+            concurrency: 4,
+            timeout: Duration::from_millis(300),
+        }
+    }
+
+    #[test]
+    fn resolves_ptr_records_from_the_configured_resolver() {
+        let dns = stub(|query| {
+            let (name, _) = read_name(query, 12)?;
+            assert_eq!(name, "1.0.0.127.in-addr.arpa");
+            assert_eq!(query[2] & 0x01, 0x01);
+            Some(reply(query, 0, TYPE_PTR, &encode_name("router.lan")))
+        });
+        let server = SocketAddr::new(IpAddr::V4(LOCALHOST), dns);
+        assert_eq!(
+            lookup_ptr(LOCALHOST, server, Duration::from_millis(300)).as_deref(),
+            Some("router.lan")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_mdns_and_netbios_names() {
+        let dns = stub(|query| Some(reply(query, 3, 0, &[])));
+        let mdns = stub(|query| {
+            let class = u16::from_be_bytes([query[query.len() - 2], query[query.len() - 1]]);
+            assert_eq!(class, CLASS_IN_UNICAST);
+            Some(reply(query, 0, TYPE_PTR, &encode_name("printer.local")))
+        });
+        let nbns = stub(|query| {
+            let (name, _) = read_name(query, 12)?;
+            assert_eq!(name, netbios_name("*"));
+            let mut rdata = vec![2];
+            // A group name first, which must be skipped
+            rdata.extend_from_slice(b"WORKGROUP      \x00\x84\x00");
+            rdata.extend_from_slice(b"FILESERVER     \x00\x04\x00");
+            rdata.extend_from_slice(&[0; 6]);
+            Some(reply(query, 0, TYPE_NBSTAT, &rdata))
+        });
+
+        let names = resolve_hosts(&[LOCALHOST], &config(Some(dns), mdns, nbns));
+        assert_eq!(
+            names[0],
+            Hostnames {
+                ptr: None,
+                mdns: Some("printer.local".to_string()),
+                netbios: Some("FILESERVER".to_string()),
+            }
+        );
+        assert_eq!(names[0].best().as_deref(), Some("printer.local"));
+    }
+
+    #[test]
+    fn silent_hosts_time_out_without_a_name() {
+        let silent = stub(|_| None);
+        let started = Instant::now();
+        let names = resolve_hosts(&[LOCALHOST, LOCALHOST], &config(None, silent, silent));
+        assert_eq!(names, vec![Hostnames::default(); 2]);
+        // All four lookups run concurrently rather than one after another
+        assert!(started.elapsed() < Duration::from_millis(900));
+    }
+
+    #[test]
+    fn parses_resolver_settings() {
+        assert_eq!(
+            parse_resolv_conf(
+                "# generated\nsearch lan\nnameserver 192.168.1.1\nnameserver 1.1.1.1\n"
+            ),
+            Some("192.168.1.1:53".parse().unwrap())
+        );
+        assert_eq!(parse_resolv_conf("search lan\n"), None);
+        assert_eq!(
+            parse_resolver("9.9.9.9").unwrap(),
+            "9.9.9.9:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_resolver("[::1]:5300").unwrap(),
+            "[::1]:5300".parse().unwrap()
+        );
+        assert!(parse_resolver("dns.example").is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use chrono::Local;
+use pnet::datalink::Channel::Ethernet;
+use pnet::datalink::{self, Config, DataLinkReceiver, DataLinkSender, NetworkInterface};
+use pnet::packet::Packet;
+use pnet::packet::arp::{
+    ArpHardwareTypes, ArpOperation, ArpOperations, ArpPacket, MutableArpPacket,
+};
+use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+use pnet::util::MacAddr;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::oui;
+use crate::probe::{self, ProbeConfig};
+use crate::report::{Device, OutputFormat, ScanReport};
+use crate::resolve::{self, ResolveConfig};
+
+/// Exit codes for one-shot scans, so scripts can branch on the result.
+pub const EXIT_FOUND: i32 = 0;
+pub const EXIT_NONE_FOUND: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+/// A host that answered, with the ARP round trip if we saw the reply ourselves.
+#[derive(Debug, Clone, Copy)]
+pub struct ArpReply {
+    pub ip: Ipv4Addr,
+    pub mac: MacAddr,
+    pub rtt: Option<Duration>,
+}
+
+pub type Channel = (Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>);
+
+pub fn find_interface(interface_name: &str) -> Option<NetworkInterface> {
+    datalink::interfaces()
+        .into_iter()
+        .find(|iface| iface.name == interface_name)
+}
+
+// Create a channel to send/receive packets. Without a timeout reads block until a frame arrives.
+pub fn open_channel(
+    interface: &NetworkInterface,
+    read_timeout: Option<Duration>,
+) -> Result<Channel, String> {
+    let config = Config {
+        read_timeout,
+        ..Default::default()
+    };
+    match datalink::channel(interface, config) {
+        Ok(Ethernet(tx, rx)) => Ok((tx, rx)),
+        Ok(_) => Err("Unhandled channel type".to_string()),
+        Err(e) => Err(format!("Error creating datalink channel: {}", e)),
+    }
+}
+
+/// Sweeps `range`, names and optionally probes the hosts, prints the report and returns
+/// the process exit code.
+pub fn run(
+    range: &str,
+    interface_name: &str,
+    probe: Option<&ProbeConfig>,
+    resolve: Option<&ResolveConfig>,
+    format: OutputFormat,
+) -> i32 {
+    // Keep stdout clean for machine-readable formats
+    let status = |message: String| {
+        if format == OutputFormat::Table {
+            println!("{}", message);
+        } else {
+            eprintln!("{}", message);
+        }
+    };
+    status(format!("[+] Scanning network range: {}", range));
+
+    let started = Local::now();
+    let replies = match sweep(range, interface_name) {
+        Ok(replies) => replies,
+        Err(e) => {
+            eprintln!("[-] {}", e);
+            return EXIT_ERROR;
+        }
+    };
+    let finished = Local::now();
+
+    let mut devices: Vec<Device> = replies
+        .iter()
+        .map(|reply| {
+            let mac = reply.mac.to_string();
+            Device {
+                ip: reply.ip,
+                vendor: oui::lookup(&mac),
+                mac,
+                hostname: None,
+                first_seen: finished,
+                last_seen: finished,
+                rtt_ms: reply.rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+                services: Vec::new(),
+            }
+        })
+        .collect();
+
+    if let Some(config) = resolve
+        && !devices.is_empty()
+    {
+        status(format!("[+] Resolving names for {} hosts", devices.len()));
+        let hosts: Vec<Ipv4Addr> = devices.iter().map(|device| device.ip).collect();
+        for (device, names) in devices
+            .iter_mut()
+            .zip(resolve::resolve_hosts(&hosts, config))
+        {
+            device.hostname = names.best();
+        }
+    }
+
+    if let Some(config) = probe {
+        status(format!(
+            "[+] Probing {} TCP ports on {} hosts",
+            config.ports.len(),
+            devices.len()
+        ));
+        let hosts: Vec<IpAddr> = devices.iter().map(|device| IpAddr::V4(device.ip)).collect();
+        for (device, (_, services)) in devices.iter_mut().zip(probe::probe_hosts(&hosts, config)) {
+            device.services = services;
+        }
+    }
+
+    let report = ScanReport {
+        range: range.to_string(),
+        interface: interface_name.to_string(),
+        started,
+        finished,
+        devices,
+    };
+    print!("{}", report.render(format));
+    if report.devices.is_empty() {
+        EXIT_NONE_FOUND
+    } else {
+        EXIT_FOUND
+    }
+}
+
+/// Sweeps a /24 with ARP requests and returns the (IP, MAC) pairs that answered.
+pub fn scan(range: &str, interface_name: &str) -> Result<Vec<(Ipv4Addr, MacAddr)>, String> {
+    Ok(sweep(range, interface_name)?
+        .into_iter()
+        .map(|reply| (reply.ip, reply.mac))
+        .collect())
+}
+
+/// `(sender IP, sender MAC)` if `frame` is an ARP reply addressed to `our_ip`.
+pub fn parse_arp_reply(frame: &[u8], our_ip: Ipv4Addr) -> Option<(Ipv4Addr, MacAddr)> {
+    let ethernet = EthernetPacket::new(frame)?;
+    if ethernet.get_ethertype() != EtherTypes::Arp {
+        return None;
+    }
+    let arp = ArpPacket::new(ethernet.payload())?;
+    (arp.get_operation() == ArpOperations::Reply && arp.get_target_proto_addr() == our_ip)
+        .then(|| (arp.get_sender_proto_addr(), arp.get_sender_hw_addr()))
+}
+
+/// Sweeps a /24 with ARP requests. Replies we capture carry a round-trip time;
+/// hosts that only show up in the kernel's ARP cache do not.
+pub fn sweep(range: &str, interface_name: &str) -> Result<Vec<ArpReply>, String> {
+    // Parse CIDR notation
+    let parts: Vec<&str> = range.split('/').collect();
+    if parts.len() != 2 {
+        return Err("Invalid CIDR notation. Example: 192.168.1.0/24".to_string());
+    }
+
+    let base_ip = parts[0];
+    let mask: u8 = parts[1]
+        .parse()
+        .map_err(|_| "Invalid subnet mask".to_string())?;
+
+    if mask != 24 {
+        return Err("This simple scanner only supports /24 networks".to_string());
+    }
+
+    // Get network interface
+    let interface = find_interface(interface_name)
+        .ok_or_else(|| format!("Interface {} not found", interface_name))?;
+
+    // Extract network base for /24
+    let ip_parts: Vec<&str> = base_ip.split('.').collect();
+    if ip_parts.len() != 4 {
+        return Err("Invalid IP format".to_string());
+    }
+
+    let network_base = format!("{}.{}.{}", ip_parts[0], ip_parts[1], ip_parts[2]);
+
+    let (mut tx, mut rx) = open_channel(&interface, Some(Duration::from_millis(100)))?;
+
+    // Get source MAC address
+    let source_mac = interface
+        .mac
+        .ok_or_else(|| "Could not get MAC address for interface".to_string())?;
+
+    // Get source IP address
+    let source_ip = match interface.ips.iter().find(|ip| ip.is_ipv4()) {
+        Some(ip) => match ip.ip() {
+            IpAddr::V4(ipv4) => ipv4,
+            _ => return Err("Could not get IPv4 address for interface".to_string()),
+        },
+        None => return Err("No IPv4 address found for interface".to_string()),
+    };
+
+    let sent: Mutex<HashMap<Ipv4Addr, Instant>> = Mutex::new(HashMap::new());
+    let replies: Mutex<Vec<ArpReply>> = Mutex::new(Vec::new());
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        // Timestamp replies as they arrive so we can report round trips
+        scope.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                let Ok(frame) = rx.next() else { continue };
+                let Some((ip, mac)) = parse_arp_reply(frame, source_ip) else {
+                    continue;
+                };
+                let rtt = sent.lock().unwrap().get(&ip).map(|sent| sent.elapsed());
+                let mut replies = replies.lock().unwrap();
+                if rtt.is_some() && !replies.iter().any(|reply| reply.ip == ip) {
+                    replies.push(ArpReply { ip, mac, rtt });
+                }
+            }
+        });
+
+        // Scan range
+        for i in 1..255 {
+            let target_ip_str = format!("{}.{}", network_base, i);
+            let target_ip = match Ipv4Addr::from_str(&target_ip_str) {
+                Ok(ip) => ip,
+                Err(_) => continue,
+            };
+
+            // Send an ARP request
+            let mut ethernet_buffer = [0u8; 42]; // Ethernet + ARP
+            let mut ethernet_packet = MutableEthernetPacket::new(&mut ethernet_buffer).unwrap();
+
+            ethernet_packet.set_destination(MacAddr::broadcast());
+            ethernet_packet.set_source(source_mac);
+            ethernet_packet.set_ethertype(EtherTypes::Arp);
+
+            let mut arp_buffer = [0u8; 28];
+            let mut arp_packet = MutableArpPacket::new(&mut arp_buffer).unwrap();
+
+            arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
+            arp_packet.set_protocol_type(EtherTypes::Ipv4);
+            arp_packet.set_hw_addr_len(6);
+            arp_packet.set_proto_addr_len(4);
+            arp_packet.set_operation(ArpOperation::new(1));
+            arp_packet.set_sender_hw_addr(source_mac);
+            arp_packet.set_sender_proto_addr(source_ip);
+            arp_packet.set_target_hw_addr(MacAddr::zero());
+            arp_packet.set_target_proto_addr(target_ip);
+
+            ethernet_packet.set_payload(arp_packet.packet());
+
+            sent.lock().unwrap().insert(target_ip, Instant::now());
+            if tx.send_to(ethernet_packet.packet(), None).is_none() {
+                eprintln!("[-] Error sending ARP request to {}", target_ip_str);
+                continue;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Give time for ARP responses to populate the ARP cache
+        thread::sleep(Duration::from_secs(1));
+        done.store(true, Ordering::Relaxed);
+    });
+
+    // Check ARP table. Hosts without net-tools have no `arp`, which leaves
+    // the captured replies.
+    let arp_table = match Command::new("arp").arg("-a").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(e) => {
+            eprintln!("[-] Failed to execute ARP command: {}", e);
+            String::new()
+        }
+    };
+
+    // Fill in hosts whose replies we missed from the ARP cache
+    let mut devices = replies.into_inner().unwrap();
+    for line in arp_table.lines() {
+        if line.contains(&network_base) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 4 {
+                let ip = parts[1].trim_matches(|c| c == '(' || c == ')');
+                if let (Ok(ip), Ok(mac)) = (Ipv4Addr::from_str(ip), MacAddr::from_str(parts[3]))
+                    && !devices.iter().any(|device| device.ip == ip)
+                {
+                    devices.push(ArpReply { ip, mac, rtt: None });
+                }
+            }
+        }
+    }
+    devices.sort_by_key(|device| device.ip);
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arp_frame(operation: ArpOperation, sender: Ipv4Addr, target: Ipv4Addr) -> Vec<u8> {
+        let mac = MacAddr::new(0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56);
+        let mut arp_buffer = [0u8; 28];
+        let mut arp = MutableArpPacket::new(&mut arp_buffer).unwrap();
+        arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+        arp.set_protocol_type(EtherTypes::Ipv4);
+        arp.set_hw_addr_len(6);
+        arp.set_proto_addr_len(4);
+        arp.set_operation(operation);
+        arp.set_sender_hw_addr(mac);
+        arp.set_sender_proto_addr(sender);
+        arp.set_target_proto_addr(target);
+
+        let mut frame = vec![0u8; 42];
+        let mut ethernet = MutableEthernetPacket::new(&mut frame).unwrap();
+        ethernet.set_ethertype(EtherTypes::Arp);
+        ethernet.set_source(mac);
+        ethernet.set_payload(arp.packet());
+        frame
+    }
+
+    #[test]
+    fn accepts_only_replies_to_us() {
+        let us = Ipv4Addr::new(192, 168, 1, 2);
+        let host = Ipv4Addr::new(192, 168, 1, 10);
+
+        let (ip, mac) = parse_arp_reply(&arp_frame(ArpOperations::Reply, host, us), us).unwrap();
+        assert_eq!(ip, host);
+        assert_eq!(mac.to_string(), "b8:27:eb:12:34:56");
+
+        assert!(parse_arp_reply(&arp_frame(ArpOperations::Request, host, us), us).is_none());
+        let elsewhere = Ipv4Addr::new(192, 168, 1, 3);
+        assert!(parse_arp_reply(&arp_frame(ArpOperations::Reply, host, elsewhere), us).is_none());
+    }
+}
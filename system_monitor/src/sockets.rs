@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+use crate::system_info::SystemInfo;
+
+/// One row of `/proc/net/{tcp,udp}{,6}`, like a line of `ss -tunap`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Socket {
+    /// `tcp`, `tcp6`, `udp` or `udp6`.
+    pub protocol: &'static str,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: &'static str,
+    pub send_queue: u64,
+    pub receive_queue: u64,
+    pub inode: u64,
+    /// `None` when the owner is not visible to us (other users without root).
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+const TABLES: [&str; 4] = ["tcp", "tcp6", "udp", "udp6"];
+
+/// State names as printed by `ss`. Unconnected UDP sockets report `CLOSE`,
+/// which `ss` shows as `UNCONN`.
+fn state_name(code: u8, tcp: bool) -> &'static str {
+    match code {
+        0x01 => "ESTAB",
+        0x02 => "SYN-SENT",
+        0x03 => "SYN-RECV",
+        0x04 => "FIN-WAIT-1",
+        0x05 => "FIN-WAIT-2",
+        0x06 => "TIME-WAIT",
+        0x07 if tcp => "CLOSE",
+        0x07 => "UNCONN",
+        0x08 => "CLOSE-WAIT",
+        0x09 => "LAST-ACK",
+        0x0A => "LISTEN",
+        0x0B => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Decodes `0100007F:0035` or the 32-digit IPv6 form. The kernel prints each
+/// 32-bit word of the address in host byte order.
+fn parse_address(text: &str) -> Option<SocketAddr> {
+    let (address, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for start in (0..address.len()).step_by(8) {
+        let word = u32::from_str_radix(address.get(start..start + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+pub fn parse_table(contents: &str, protocol: &'static str) -> Vec<Socket> {
+    let tcp = protocol.starts_with("tcp");
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (send_queue, receive_queue) = fields.get(4)?.split_once(':')?;
+            Some(Socket {
+                protocol,
+                local: parse_address(fields.get(1)?)?,
+                remote: parse_address(fields.get(2)?)?,
+                state: state_name(u8::from_str_radix(fields.get(3)?, 16).ok()?, tcp),
+                send_queue: u64::from_str_radix(send_queue, 16).ok()?,
+                receive_queue: u64::from_str_radix(receive_queue, 16).ok()?,
+                inode: fields.get(9)?.parse().ok()?,
+                pid: None,
+                process: None,
+            })
+        })
+        .collect()
+}
+
+/// Maps socket inodes to the PID and name of a process holding them open,
+/// by reading the `socket:[inode]` links under `/proc/<pid>/fd`.
+pub fn inode_owners(proc_root: &Path) -> HashMap<u64, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = fs::read_dir(proc_root) else {
+        return owners;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let name = fs::read_to_string(entry.path().join("comm"))
+            .map(|comm| comm.trim().to_string())
+            .unwrap_or_default();
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            if let Some(inode) = target
+                .to_str()
+                .and_then(|target| target.strip_prefix("socket:["))
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|inode| inode.parse().ok())
+            {
+                owners.entry(inode).or_insert_with(|| (pid, name.clone()));
+            }
+        }
+    }
+    owners
+}
+
+/// All TCP and UDP sockets below `root` (normally `/`), with owners where visible.
+pub fn read_sockets(root: &Path) -> Vec<Socket> {
+    let proc_root = root.join("proc");
+    let mut sockets: Vec<Socket> = TABLES
+        .iter()
+        .filter_map(|table| {
+            let contents = fs::read_to_string(proc_root.join("net").join(table)).ok()?;
+            Some(parse_table(&contents, table))
+        })
+        .flatten()
+        .collect();
+
+    let owners = inode_owners(&proc_root);
+    for socket in &mut sockets {
+        // Inode 0 belongs to sockets in TIME-WAIT that no process owns
+        if let Some((pid, name)) = owners.get(&socket.inode).filter(|_| socket.inode != 0) {
+            socket.pid = Some(*pid);
+            socket.process = Some(name.clone());
+        }
+    }
+    sockets
+}
+
+#[derive(Default)]
+pub struct SocketFilter {
+    pub tcp: bool,
+    pub udp: bool,
+    pub listening: bool,
+}
+
+impl SocketFilter {
+    pub fn matches(&self, socket: &Socket) -> bool {
+        // Like ss, no protocol flag means both
+        let any_protocol = !self.tcp && !self.udp;
+        let protocol = any_protocol
+            || (self.tcp && socket.protocol.starts_with("tcp"))
+            || (self.udp && socket.protocol.starts_with("udp"));
+        let listening = !self.listening || matches!(socket.state, "LISTEN" | "UNCONN");
+        protocol && listening
+    }
+}
+
+pub fn run_sockets(filter: &SocketFilter) {
+    let system_info = SystemInfo::new();
+    let mut sockets: Vec<Socket> = system_info
+        .get_sockets()
+        .into_iter()
+        .filter(|socket| filter.matches(socket))
+        .collect();
+    sockets.sort_by(|a, b| (a.protocol, a.local).cmp(&(b.protocol, b.local)));
+
+    println!(
+        "{:<6} {:<10} {:>7} {:>7} {:<40} {:<40} PROCESS",
+        "NETID", "STATE", "RECV-Q", "SEND-Q", "LOCAL", "PEER"
+    );
+    for socket in &sockets {
+        let process = match (&socket.process, socket.pid) {
+            (Some(name), Some(pid)) => format!("{}/{}", name, pid),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<6} {:<10} {:>7} {:>7} {:<40} {:<40} {}",
+            socket.protocol,
+            socket.state,
+            socket.receive_queue,
+            socket.send_queue,
+            socket.local.to_string(),
+            socket.remote.to_string(),
+            process
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/host")
+    }
+
+    #[test]
+    fn parses_ipv4_table() {
+        let sockets = parse_table(include_str!("../tests/fixtures/host/proc/net/tcp"), "tcp");
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(sockets[0].local, "0.0.0.0:22".parse().unwrap());
+        assert_eq!(sockets[0].state, "LISTEN");
+        assert_eq!(sockets[1].local, "192.168.1.20:22".parse().unwrap());
+        assert_eq!(sockets[1].remote, "192.168.1.5:51234".parse().unwrap());
+        assert_eq!(sockets[1].state, "ESTAB");
+        assert_eq!(sockets[1].send_queue, 0x24);
+        assert_eq!(sockets[2].state, "TIME-WAIT");
+    }
+
+    #[test]
+    fn parses_ipv6_and_udp_tables() {
+        let tcp6 = parse_table(include_str!("../tests/fixtures/host/proc/net/tcp6"), "tcp6");
+        assert_eq!(tcp6[0].local, "[::]:80".parse().unwrap());
+        assert_eq!(tcp6[1].local, "[::1]:631".parse().unwrap());
+
+        let udp = parse_table(include_str!("../tests/fixtures/host/proc/net/udp"), "udp");
+        assert_eq!(udp[0].local, "127.0.0.53:53".parse().unwrap());
+        assert_eq!(udp[0].state, "UNCONN");
+        assert_eq!(udp[0].receive_queue, 0x200);
+    }
+
+    #[test]
+    fn maps_inodes_to_processes() {
+        let sockets = read_sockets(&fixture());
+        assert_eq!(sockets.len(), 7);
+        let sshd = sockets.iter().find(|s| s.inode == 31337).unwrap();
+        assert_eq!(sshd.pid, Some(812));
+        assert_eq!(sshd.process.as_deref(), Some("sshd"));
+        let time_wait = sockets.iter().find(|s| s.state == "TIME-WAIT").unwrap();
+        assert_eq!(time_wait.pid, None);
+    }
+}
@@ -1,20 +1,167 @@
-use sysinfo::{CpuExt, DiskExt, System, SystemExt};
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::cgroup::CgroupLimits;
+use crate::collector::{
+    Collector, CpuUsage, InterfaceCounters, MemInfo, Pressure, ProcCollector, Snapshot,
+};
+use crate::sockets::Socket;
+use sysinfo::{
+    CpuExt, DiskExt, NetworkExt, NetworksExt, Pid, PidExt, ProcessExt, Signal, System, SystemExt,
+    UserExt,
+};
+
+#[derive(Clone, Default)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: Option<u32>,
+    pub name: String,
+    pub user: String,
+    pub command: String,
+    pub cpu_usage: f32,
+    /// Resident memory in KB.
+    pub memory: u64,
+    /// Bytes read and written per second since the previous refresh.
+    pub disk_read: u64,
+    pub disk_written: u64,
+    /// `None` when `/proc/<pid>/fd` is not readable by us.
+    pub open_files: Option<usize>,
+}
+
+/// Cumulative counters plus per-second rates since the previous refresh.
+#[derive(Debug, Clone)]
+pub struct InterfaceStats {
+    pub counters: InterfaceCounters,
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub rx_packets_per_sec: f64,
+    pub tx_packets_per_sec: f64,
+}
 
 pub struct SystemInfo {
     system: System,
+    last_refresh: Instant,
+    sample_window: Duration,
+    /// Kernel counters sysinfo does not expose. `None` where `/proc` is unavailable.
+    collector: Option<Box<dyn Collector>>,
+    previous: Option<Snapshot>,
+    current: Option<Snapshot>,
 }
 
 impl SystemInfo {
     pub fn new() -> Self {
+        SystemInfo::with_collector(Box::new(ProcCollector::new()))
+    }
+
+    pub fn with_collector(mut collector: Box<dyn Collector>) -> Self {
         let mut system = System::new_all();
         system.refresh_all();
-        SystemInfo { system }
+        let current = collector.collect().ok();
+        SystemInfo {
+            system,
+            last_refresh: Instant::now(),
+            sample_window: Duration::from_secs(1),
+            collector: current.is_some().then_some(collector),
+            previous: None,
+            current,
+        }
     }
+
+    /// Re-reads CPU, memory, disk, network and process counters. CPU percentages and
+    /// network deltas are computed against the previous refresh.
+    pub fn refresh(&mut self) {
+        self.system.refresh_cpu();
+        self.system.refresh_memory();
+        self.system.refresh_disks();
+        self.system.refresh_networks();
+        self.system.refresh_processes();
+        if let Some(collector) = self.collector.as_mut() {
+            match collector.collect() {
+                Ok(snapshot) => self.previous = self.current.replace(snapshot),
+                Err(e) => eprintln!("[-] {}", e),
+            }
+        }
+        let now = Instant::now();
+        self.sample_window = now - self.last_refresh;
+        self.last_refresh = now;
+    }
+
+    /// CPU usage in percent of the host, or of our quota inside a CPU-limited container.
     pub fn get_cpu_usage(&self) -> f32 {
-        self.system.global_cpu_info().cpu_usage()
+        self.container_cpu_usage()
+            .unwrap_or_else(|| self.system.global_cpu_info().cpu_usage())
+    }
+    fn container_cpu_usage(&self) -> Option<f32> {
+        let current = self.current.as_ref()?;
+        current.container?;
+        let limits = current.cgroup.as_ref()?;
+        let cores = limits.cpu_cores()?;
+        let before = self.previous.as_ref()?.cgroup.as_ref()?.cpu_usage_usec?;
+        let used = limits.cpu_usage_usec?.saturating_sub(before) as f64;
+        let window = self.sample_window.as_micros() as f64 * cores;
+        (window > 0.0).then(|| (used * 100.0 / window) as f32)
+    }
+    pub fn get_cpu_usage_per_core(&self) -> Vec<f32> {
+        self.system
+            .cpus()
+            .iter()
+            .map(|cpu| cpu.cpu_usage())
+            .collect()
     }
+    /// Used and total memory in KB. Inside a container with a memory limit this is
+    /// the cgroup's usage against that limit, not the host totals.
     pub fn get_memory_usage(&self) -> (u64, u64) {
-        (self.system.used_memory(), self.system.total_memory())
+        let host_total = self.system.total_memory();
+        let limited = self
+            .current
+            .as_ref()
+            .filter(|snapshot| snapshot.container.is_some())
+            .and_then(|snapshot| snapshot.cgroup.as_ref())
+            .and_then(|limits| Some((limits.memory_current? / 1024, limits.memory_max? / 1024)))
+            .filter(|(_, max)| *max < host_total);
+        limited.unwrap_or((self.system.used_memory(), host_total))
+    }
+    /// Used and total swap in KB.
+    pub fn get_swap_usage(&self) -> (u64, u64) {
+        (self.system.used_swap(), self.system.total_swap())
+    }
+    /// Time share per CPU mode since the previous refresh, `cpu` (all cores) first.
+    /// Empty without a collector or before the second sample.
+    pub fn get_cpu_breakdown(&self) -> Vec<(String, CpuUsage)> {
+        let (Some(previous), Some(current)) = (&self.previous, &self.current) else {
+            return Vec::new();
+        };
+        current
+            .cpus
+            .iter()
+            .filter_map(|(name, times)| {
+                let (_, before) = previous.cpus.iter().find(|(other, _)| other == name)?;
+                Some((name.clone(), times.usage_since(before)))
+            })
+            .collect()
+    }
+    /// Raw `/proc/meminfo` fields, including buffers and page cache.
+    pub fn get_memory_info(&self) -> Option<MemInfo> {
+        self.current.as_ref().map(|snapshot| snapshot.memory)
+    }
+    /// Pressure stall information per resource (`cpu`, `memory`, `io`).
+    pub fn get_pressure(&self) -> Vec<(String, Pressure)> {
+        self.current
+            .as_ref()
+            .map(|snapshot| snapshot.pressure.clone())
+            .unwrap_or_default()
+    }
+    /// Container runtime we run under, e.g. `docker` or `kubernetes`.
+    pub fn get_container(&self) -> Option<&'static str> {
+        self.current
+            .as_ref()
+            .and_then(|snapshot| snapshot.container)
+    }
+    /// Limits of the cgroup this process runs in.
+    pub fn get_cgroup_limits(&self) -> Option<CgroupLimits> {
+        self.current
+            .as_ref()
+            .and_then(|snapshot| snapshot.cgroup.clone())
     }
     pub fn get_disk_usage(&self) -> Vec<(String, u64, u64)> {
         self.system
@@ -29,4 +176,149 @@ impl SystemInfo {
             })
             .collect()
     }
+    /// Same as `get_disk_usage` but keyed by mount point, e.g. `/var`.
+    pub fn get_mount_usage(&self) -> Vec<(String, u64, u64)> {
+        self.system
+            .disks()
+            .iter()
+            .map(|disk| {
+                (
+                    disk.mount_point().to_string_lossy().into_owned(),
+                    disk.total_space(),
+                    disk.available_space(),
+                )
+            })
+            .collect()
+    }
+    pub fn get_processes(&self) -> Vec<ProcessInfo> {
+        let seconds = self.sample_window.as_secs_f64().max(f64::EPSILON);
+        self.system
+            .processes()
+            .iter()
+            .map(|(pid, process)| {
+                let user = process
+                    .user_id()
+                    .and_then(|uid| self.system.get_user_by_id(uid))
+                    .map(|user| user.name().to_string())
+                    .or_else(|| process.user_id().map(|uid| (**uid).to_string()))
+                    .unwrap_or_else(|| "?".to_string());
+                let command = if process.cmd().is_empty() {
+                    format!("[{}]", process.name())
+                } else {
+                    process.cmd().join(" ")
+                };
+                let disk_usage = process.disk_usage();
+                ProcessInfo {
+                    pid: pid.as_u32(),
+                    ppid: process.parent().map(|ppid| ppid.as_u32()),
+                    name: process.name().to_string(),
+                    user,
+                    command,
+                    cpu_usage: process.cpu_usage(),
+                    memory: process.memory(),
+                    disk_read: (disk_usage.read_bytes as f64 / seconds) as u64,
+                    disk_written: (disk_usage.written_bytes as f64 / seconds) as u64,
+                    open_files: fs::read_dir(format!("/proc/{}/fd", pid))
+                        .ok()
+                        .map(|entries| entries.count()),
+                }
+            })
+            .collect()
+    }
+    pub fn send_signal(&self, pid: u32, signal: Signal) -> Result<(), String> {
+        let process = self
+            .system
+            .process(Pid::from_u32(pid))
+            .ok_or_else(|| format!("No process with PID {}", pid))?;
+        match process.kill_with(signal) {
+            Some(true) => Ok(()),
+            Some(false) => Err(format!("Could not send {:?} to PID {}", signal, pid)),
+            None => Err(format!("{:?} is not supported on this platform", signal)),
+        }
+    }
+    /// Cumulative received and transmitted bytes for each interface.
+    pub fn get_network_totals(&self) -> Vec<(String, u64, u64)> {
+        let mut interfaces: Vec<(String, u64, u64)> = self
+            .system
+            .networks()
+            .iter()
+            .map(|(name, data)| {
+                (
+                    name.clone(),
+                    data.total_received(),
+                    data.total_transmitted(),
+                )
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.0.cmp(&b.0));
+        interfaces
+    }
+    /// Bytes, packets, errors and drops per interface from `/proc/net/dev`, with rates
+    /// since the previous refresh. Empty without a collector.
+    pub fn get_interface_stats(&self) -> Vec<InterfaceStats> {
+        let Some(current) = &self.current else {
+            return Vec::new();
+        };
+        let seconds = self.sample_window.as_secs_f64().max(f64::EPSILON);
+        current
+            .interfaces
+            .iter()
+            .map(|counters| {
+                let before = self.previous.as_ref().and_then(|previous| {
+                    previous
+                        .interfaces
+                        .iter()
+                        .find(|other| other.name == counters.name)
+                });
+                let rate = |now: u64, then: fn(&InterfaceCounters) -> u64| {
+                    before.map_or(0.0, |before| {
+                        now.saturating_sub(then(before)) as f64 / seconds
+                    })
+                };
+                InterfaceStats {
+                    rx_bytes_per_sec: rate(counters.rx_bytes, |c| c.rx_bytes),
+                    tx_bytes_per_sec: rate(counters.tx_bytes, |c| c.tx_bytes),
+                    rx_packets_per_sec: rate(counters.rx_packets, |c| c.rx_packets),
+                    tx_packets_per_sec: rate(counters.tx_packets, |c| c.tx_packets),
+                    counters: counters.clone(),
+                }
+            })
+            .collect()
+    }
+    /// TCP and UDP sockets with their owning process, like `ss -tunap`.
+    pub fn get_sockets(&self) -> Vec<Socket> {
+        self.collector
+            .as_ref()
+            .map(|collector| collector.sockets())
+            .unwrap_or_default()
+    }
+    /// Received and transmitted bytes per second for each interface since the previous refresh.
+    pub fn get_network_throughput(&self) -> Vec<(String, f64, f64)> {
+        let seconds = self.sample_window.as_secs_f64().max(f64::EPSILON);
+        let mut interfaces: Vec<(String, f64, f64)> = self
+            .system
+            .networks()
+            .iter()
+            .map(|(name, data)| {
+                (
+                    name.clone(),
+                    data.received() as f64 / seconds,
+                    data.transmitted() as f64 / seconds,
+                )
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.0.cmp(&b.0));
+        interfaces
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
 }
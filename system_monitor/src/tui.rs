@@ -0,0 +1,594 @@
+use chrono::Local;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Gauge, Paragraph, Row, Table, TableState};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::inventory::{Inventory, KnownDevice};
+use crate::probe;
+use crate::scanner;
+use crate::system_info::{ProcessInfo, SystemInfo, format_bytes};
+use sysinfo::Signal;
+
+const REFRESH: Duration = Duration::from_secs(1);
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+const PROCESS_COLUMNS: [&str; 7] = ["PID", "User", "CPU %", "RSS", "I/O /s", "FDs", "Command"];
+const DEVICE_COLUMNS: [&str; 5] = [
+    "IP Address",
+    "MAC Address",
+    "Last seen",
+    "State",
+    "Services",
+];
+const DISK_COLUMNS: [&str; 4] = ["Disk", "Total", "Available", "Used %"];
+
+pub struct TuiOptions {
+    /// Rescan `(range, interface)` in the background to fill the device table.
+    pub scan: Option<(String, String)>,
+    /// Inventory file written by `--daemon`, re-read on every refresh.
+    pub store: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Panel {
+    Disks,
+    Processes,
+    Devices,
+}
+
+/// Selection and sort order of one table.
+#[derive(Default)]
+struct TableView {
+    state: TableState,
+    sort_column: usize,
+    descending: bool,
+}
+
+impl TableView {
+    fn select_next(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let next = self.state.selected().map_or(0, |i| (i + 1).min(len - 1));
+        self.state.select(Some(next));
+    }
+
+    fn select_previous(&mut self) {
+        let previous = self.state.selected().map_or(0, |i| i.saturating_sub(1));
+        self.state.select(Some(previous));
+    }
+
+    fn cycle_sort(&mut self, columns: usize) {
+        self.sort_column = (self.sort_column + 1) % columns;
+    }
+}
+
+struct App {
+    system_info: SystemInfo,
+    options: TuiOptions,
+    devices: Arc<Mutex<Inventory>>,
+    focus: Panel,
+    disks: TableView,
+    processes: TableView,
+    device_view: TableView,
+    /// PIDs in the order the process table was last drawn.
+    visible_pids: Vec<u32>,
+    /// Process waiting for the user to confirm a SIGTERM.
+    pending_kill: Option<(u32, String)>,
+    status: Option<String>,
+}
+
+pub fn run(options: TuiOptions) -> io::Result<()> {
+    let devices = Arc::new(Mutex::new(Inventory::default()));
+    if let Some((range, interface)) = options.scan.clone() {
+        let devices = devices.clone();
+        thread::spawn(move || {
+            loop {
+                if let Ok(seen) = scanner::scan(&range, &interface) {
+                    devices.lock().unwrap().apply_scan(&seen, Local::now());
+                }
+                thread::sleep(SCAN_INTERVAL);
+            }
+        });
+    }
+
+    let mut app = App {
+        system_info: SystemInfo::new(),
+        options,
+        devices,
+        focus: Panel::Processes,
+        disks: TableView::default(),
+        // Busiest processes first
+        processes: TableView {
+            sort_column: 2,
+            descending: true,
+            ..TableView::default()
+        },
+        device_view: TableView::default(),
+        visible_pids: Vec::new(),
+        pending_kill: None,
+        status: None,
+    };
+    app.reload_store();
+
+    let mut terminal = ratatui::try_init()?;
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut last_refresh = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let timeout = REFRESH.saturating_sub(last_refresh.elapsed());
+            if event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                if let Some((pid, _)) = self.pending_kill.take() {
+                    self.status = Some(match key.code {
+                        KeyCode::Char('y') => match self.system_info.send_signal(pid, Signal::Term)
+                        {
+                            Ok(()) => format!("Sent SIGTERM to PID {}", pid),
+                            Err(e) => e,
+                        },
+                        _ => "Cancelled".to_string(),
+                    });
+                    continue;
+                }
+                self.status = None;
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Tab => self.focus_next(),
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let len = self.focused_len();
+                        self.focused_view().select_next(len);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => self.focused_view().select_previous(),
+                    KeyCode::Char('s') => {
+                        let columns = self.focused_columns();
+                        self.focused_view().cycle_sort(columns);
+                    }
+                    KeyCode::Char('K') if self.focus == Panel::Processes => {
+                        self.pending_kill = self.selected_process();
+                    }
+                    KeyCode::Char('r') => {
+                        let view = self.focused_view();
+                        view.descending = !view.descending;
+                    }
+                    _ => {}
+                }
+            }
+
+            if last_refresh.elapsed() >= REFRESH {
+                self.system_info.refresh();
+                self.reload_store();
+                last_refresh = Instant::now();
+            }
+        }
+    }
+
+    fn reload_store(&mut self) {
+        if let Some(path) = &self.options.store
+            && let Ok(inventory) = Inventory::load(path)
+        {
+            *self.devices.lock().unwrap() = inventory;
+        }
+    }
+
+    fn focus_next(&mut self) {
+        self.focus = match self.focus {
+            Panel::Disks => Panel::Processes,
+            Panel::Processes => Panel::Devices,
+            Panel::Devices => Panel::Disks,
+        };
+    }
+
+    fn focused_view(&mut self) -> &mut TableView {
+        match self.focus {
+            Panel::Disks => &mut self.disks,
+            Panel::Processes => &mut self.processes,
+            Panel::Devices => &mut self.device_view,
+        }
+    }
+
+    fn focused_columns(&self) -> usize {
+        match self.focus {
+            Panel::Disks => DISK_COLUMNS.len(),
+            Panel::Processes => PROCESS_COLUMNS.len(),
+            Panel::Devices => DEVICE_COLUMNS.len(),
+        }
+    }
+
+    fn selected_process(&self) -> Option<(u32, String)> {
+        let pid = *self.visible_pids.get(self.processes.state.selected()?)?;
+        self.system_info
+            .get_processes()
+            .into_iter()
+            .find(|process| process.pid == pid)
+            .map(|process| (pid, process.command))
+    }
+
+    fn focused_len(&self) -> usize {
+        match self.focus {
+            Panel::Disks => self.system_info.get_disk_usage().len(),
+            Panel::Processes => self.system_info.get_processes().len(),
+            Panel::Devices => self.devices.lock().unwrap().devices.len(),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let core_rows = self.system_info.get_cpu_usage_per_core().len().div_ceil(2);
+        let interface_count = self
+            .system_info
+            .get_interface_stats()
+            .len()
+            .max(self.system_info.get_network_throughput().len());
+        let memory_rows = 2 + interface_count;
+        let top_rows = core_rows.max(memory_rows) as u16;
+        let [top, disks, bottom, help] = Layout::vertical([
+            Constraint::Length(top_rows + 2),
+            Constraint::Length(6),
+            Constraint::Min(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [cpu, memory] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(top);
+        let [processes, devices] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(bottom);
+
+        self.draw_cpu(frame, cpu);
+        self.draw_memory(frame, memory);
+        self.draw_disks(frame, disks);
+        self.draw_processes(frame, processes);
+        self.draw_devices(frame, devices);
+
+        let help_line = match (&self.pending_kill, &self.status) {
+            (Some((pid, command)), _) => Paragraph::new(format!(
+                "Send SIGTERM to PID {} ({})? y to confirm, any other key cancels",
+                pid, command
+            ))
+            .style(Style::default().fg(Color::Yellow)),
+            (None, Some(status)) => Paragraph::new(status.as_str()),
+            (None, None) => Paragraph::new(
+                "q quit  Tab switch panel  ↑/↓ select  s sort column  r reverse sort  K terminate process",
+            )
+            .style(Style::default().fg(Color::DarkGray)),
+        };
+        frame.render_widget(help_line, help);
+    }
+
+    fn draw_cpu(&self, frame: &mut Frame, area: Rect) {
+        let mut title = format!(" CPU {:.1}% ", self.system_info.get_cpu_usage());
+        if let Some((_, usage)) = self.system_info.get_cpu_breakdown().first() {
+            title.push_str(&format!(
+                "iowait {:.1}% steal {:.1}% softirq {:.1}% ",
+                usage.iowait, usage.steal, usage.softirq
+            ));
+        }
+        let block = Block::bordered().title(title);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let cores = self.system_info.get_cpu_usage_per_core();
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(inner);
+        for (core, usage) in cores.iter().enumerate() {
+            let column = if core % 2 == 0 { left } else { right };
+            let row = (core / 2) as u16;
+            if row >= column.height {
+                break;
+            }
+            let line = Rect::new(column.x, column.y + row, column.width, 1);
+            frame.render_widget(
+                Gauge::default()
+                    .label(format!("cpu{:<2} {:5.1}%", core, usage))
+                    .ratio((*usage as f64 / 100.0).clamp(0.0, 1.0))
+                    .gauge_style(Style::default().fg(load_color(*usage as f64))),
+                line,
+            );
+        }
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let title = match self.system_info.get_container() {
+            Some(runtime) => format!(" Memory ({} limit) / Network ", runtime),
+            None => " Memory / Network ".to_string(),
+        };
+        let block = Block::bordered().title(title);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let [memory, swap, network] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+        let (used_memory, total_memory) = self.system_info.get_memory_usage();
+        let (used_swap, total_swap) = self.system_info.get_swap_usage();
+        frame.render_widget(
+            usage_gauge("Mem ", used_memory * 1024, total_memory * 1024),
+            memory,
+        );
+        frame.render_widget(
+            usage_gauge("Swap", used_swap * 1024, total_swap * 1024),
+            swap,
+        );
+
+        let stats = self.system_info.get_interface_stats();
+        let interfaces: Vec<Line> = if stats.is_empty() {
+            self.system_info
+                .get_network_throughput()
+                .into_iter()
+                .map(|(name, rx, tx)| {
+                    Line::from(format!(
+                        "{:<10} rx {:>10}/s  tx {:>10}/s",
+                        name,
+                        format_bytes(rx as u64),
+                        format_bytes(tx as u64)
+                    ))
+                })
+                .collect()
+        } else {
+            stats
+                .iter()
+                .map(|stats| {
+                    let counters = &stats.counters;
+                    Line::from(format!(
+                        "{:<10} rx {:>10}/s {:>6.0} pkt/s  tx {:>10}/s {:>6.0} pkt/s  err {}/{} drop {}/{}",
+                        counters.name,
+                        format_bytes(stats.rx_bytes_per_sec as u64),
+                        stats.rx_packets_per_sec,
+                        format_bytes(stats.tx_bytes_per_sec as u64),
+                        stats.tx_packets_per_sec,
+                        counters.rx_errors,
+                        counters.tx_errors,
+                        counters.rx_dropped,
+                        counters.tx_dropped
+                    ))
+                })
+                .collect()
+        };
+        frame.render_widget(Paragraph::new(interfaces), network);
+    }
+
+    fn draw_disks(&mut self, frame: &mut Frame, area: Rect) {
+        let mut disks = self.system_info.get_disk_usage();
+        let used_percent = |total: u64, available: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                (total - available.min(total)) as f64 * 100.0 / total as f64
+            }
+        };
+        let view = &self.disks;
+        disks.sort_by(|a, b| {
+            let ordering = match view.sort_column {
+                0 => a.0.cmp(&b.0),
+                1 => a.1.cmp(&b.1),
+                2 => a.2.cmp(&b.2),
+                _ => used_percent(a.1, a.2).total_cmp(&used_percent(b.1, b.2)),
+            };
+            if view.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        let rows = disks.iter().map(|(name, total, available)| {
+            Row::new(vec![
+                Cell::from(name.clone()),
+                Cell::from(format_bytes(*total)),
+                Cell::from(format_bytes(*available)),
+                Cell::from(format!("{:.1}", used_percent(*total, *available))),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(12),
+                Constraint::Length(12),
+                Constraint::Length(8),
+            ],
+        );
+        self.render_table(frame, area, Panel::Disks, " Disks ", &DISK_COLUMNS, table);
+    }
+
+    fn draw_processes(&mut self, frame: &mut Frame, area: Rect) {
+        let mut processes = self.system_info.get_processes();
+        sort_processes(&mut processes, &self.processes);
+        self.visible_pids = processes.iter().map(|process| process.pid).collect();
+
+        let rows = processes.iter().map(|process| {
+            Row::new(vec![
+                Cell::from(process.pid.to_string()),
+                Cell::from(process.user.clone()),
+                Cell::from(format!("{:.1}", process.cpu_usage)),
+                Cell::from(format_bytes(process.memory * 1024)),
+                Cell::from(format_bytes(process.disk_read + process.disk_written)),
+                Cell::from(
+                    process
+                        .open_files
+                        .map_or("-".to_string(), |n| n.to_string()),
+                ),
+                Cell::from(process.command.clone()),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(8),
+                Constraint::Length(10),
+                Constraint::Length(7),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(5),
+                Constraint::Fill(1),
+            ],
+        );
+        self.render_table(
+            frame,
+            area,
+            Panel::Processes,
+            " Processes ",
+            &PROCESS_COLUMNS,
+            table,
+        );
+    }
+
+    fn draw_devices(&mut self, frame: &mut Frame, area: Rect) {
+        let mut devices: Vec<KnownDevice> = self
+            .devices
+            .lock()
+            .unwrap()
+            .devices
+            .values()
+            .cloned()
+            .collect();
+        let view = &self.device_view;
+        devices.sort_by(|a, b| {
+            let ordering = match view.sort_column {
+                0 => a.ip.cmp(&b.ip),
+                1 => a.mac.cmp(&b.mac),
+                2 => a.last_seen.cmp(&b.last_seen),
+                3 => a.online.cmp(&b.online),
+                _ => a.services.len().cmp(&b.services.len()),
+            };
+            if view.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        let rows = devices.iter().map(|device| {
+            Row::new(vec![
+                Cell::from(device.ip.to_string()),
+                Cell::from(device.mac.clone()),
+                Cell::from(device.last_seen.format("%H:%M:%S").to_string()),
+                Cell::from(if device.online { "up" } else { "gone" }),
+                Cell::from(probe::describe(&device.services)),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(15),
+                Constraint::Length(17),
+                Constraint::Length(9),
+                Constraint::Length(5),
+                Constraint::Fill(1),
+            ],
+        );
+        let title = if self.options.scan.is_none() && self.options.store.is_none() {
+            " Devices (use --scan or --store) "
+        } else {
+            " Devices "
+        };
+        self.render_table(frame, area, Panel::Devices, title, &DEVICE_COLUMNS, table);
+    }
+
+    fn render_table(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        panel: Panel,
+        title: &str,
+        columns: &[&str],
+        table: Table,
+    ) {
+        let focused = self.focus == panel;
+        let view = match panel {
+            Panel::Disks => &mut self.disks,
+            Panel::Processes => &mut self.processes,
+            Panel::Devices => &mut self.device_view,
+        };
+
+        let header = Row::new(columns.iter().enumerate().map(|(i, name)| {
+            if i == view.sort_column {
+                let arrow = if view.descending { "▼" } else { "▲" };
+                Cell::from(format!("{}{}", name, arrow))
+            } else {
+                Cell::from(*name)
+            }
+        }))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+        let border = if focused {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        let table = table
+            .header(header)
+            .block(
+                Block::bordered()
+                    .title(Line::from(title))
+                    .border_style(border),
+            )
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut view.state);
+    }
+}
+
+fn sort_processes(processes: &mut [ProcessInfo], view: &TableView) {
+    processes.sort_by(|a, b| {
+        let ordering = match view.sort_column {
+            0 => a.pid.cmp(&b.pid),
+            1 => a.user.cmp(&b.user),
+            2 => a.cpu_usage.total_cmp(&b.cpu_usage),
+            3 => a.memory.cmp(&b.memory),
+            4 => (a.disk_read + a.disk_written).cmp(&(b.disk_read + b.disk_written)),
+            5 => a.open_files.cmp(&b.open_files),
+            _ => a.command.cmp(&b.command),
+        };
+        if view.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+fn usage_gauge(label: &str, used: u64, total: u64) -> Gauge<'static> {
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64
+    };
+    Gauge::default()
+        .label(format!(
+            "{} {} / {}",
+            label,
+            format_bytes(used),
+            format_bytes(total)
+        ))
+        .ratio(ratio.clamp(0.0, 1.0))
+        .gauge_style(Style::default().fg(load_color(ratio * 100.0)))
+}
+
+fn load_color(percent: f64) -> Color {
+    if percent >= 90.0 {
+        Color::Red
+    } else if percent >= 70.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
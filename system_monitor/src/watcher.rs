@@ -0,0 +1,487 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::webhook;
+
+/// World-writable or per-user places where droppers like to stage binaries.
+pub const DEFAULT_PATHS: [&str; 3] = ["/tmp", "/dev/shm", "~/.local/share"];
+/// How far below each watched path new directories are followed.
+const MAX_DEPTH: usize = 4;
+const EVENT_HEADER: usize = 16;
+
+const WATCH_MASK: u32 =
+    libc::IN_CREATE | libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_ATTRIB | libc::IN_DELETE;
+
+/// Process that had the file open for writing when we looked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Writer {
+    pub pid: u32,
+    pub name: String,
+    /// Ends in ` (deleted)` when the process removed its own binary.
+    pub exe: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecAlert {
+    pub timestamp: DateTime<Local>,
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    pub writer: Option<Writer>,
+    /// The file is byte-for-byte the binary of the process that wrote it.
+    pub self_copy: bool,
+}
+
+/// One raw `struct inotify_event`.
+#[derive(Debug, PartialEq)]
+struct RawEvent {
+    wd: i32,
+    mask: u32,
+    name: Vec<u8>,
+}
+
+fn parse_events(buffer: &[u8]) -> Vec<RawEvent> {
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = buffer.get(offset..offset + EVENT_HEADER) {
+        let field = |at: usize| <[u8; 4]>::try_from(&header[at..at + 4]).unwrap();
+        let len = u32::from_ne_bytes(field(12)) as usize;
+        let Some(name) = buffer.get(offset + EVENT_HEADER..offset + EVENT_HEADER + len) else {
+            break;
+        };
+        // The kernel pads names with NULs to keep events aligned
+        let end = name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len());
+        events.push(RawEvent {
+            wd: i32::from_ne_bytes(field(0)),
+            mask: u32::from_ne_bytes(field(4)),
+            name: name[..end].to_vec(),
+        });
+        offset += EVENT_HEADER + len;
+    }
+    events
+}
+
+struct Inotify {
+    fd: i32,
+    /// Directory and its depth below the watched root, per watch descriptor.
+    watches: HashMap<i32, (PathBuf, usize)>,
+}
+
+impl Inotify {
+    fn new() -> Result<Self, String> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(format!("inotify: {}", io::Error::last_os_error()));
+        }
+        Ok(Inotify {
+            fd,
+            watches: HashMap::new(),
+        })
+    }
+
+    fn add(&mut self, dir: &Path, depth: usize) -> Result<(), String> {
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| format!("Invalid path {}", dir.display()))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(format!(
+                "Cannot watch {}: {}",
+                dir.display(),
+                io::Error::last_os_error()
+            ));
+        }
+        self.watches.insert(wd, (dir.to_path_buf(), depth));
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for events and returns `(path, mask, depth of its directory)`.
+    fn read(&mut self, timeout: Duration) -> Result<Vec<(PathBuf, u32, usize)>, String> {
+        let mut poll = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let ready = unsafe { libc::poll(&mut poll, 1, millis) };
+        if ready < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(format!("inotify: {}", error));
+        }
+        if ready == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buffer = vec![0u8; 64 * 1024];
+        let read = unsafe { libc::read(self.fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+        if read < 0 {
+            return Err(format!("inotify: {}", io::Error::last_os_error()));
+        }
+
+        let mut events = Vec::new();
+        for event in parse_events(&buffer[..read as usize]) {
+            if event.mask & libc::IN_IGNORED != 0 {
+                self.watches.remove(&event.wd);
+                continue;
+            }
+            if let Some((dir, depth)) = self.watches.get(&event.wd) {
+                let path = dir.join(OsStr::from_bytes(&event.name));
+                events.push((path, event.mask, *depth));
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Alerts on executable files appearing anywhere below the watched directories.
+pub struct ExecWatcher {
+    inotify: Inotify,
+    proc_root: PathBuf,
+    /// Writers spotted when a file was created, before it was closed.
+    writers: HashMap<PathBuf, Writer>,
+    /// Last hash reported per path, so chmod after close does not alert twice.
+    reported: HashMap<PathBuf, String>,
+}
+
+impl ExecWatcher {
+    pub fn new() -> Result<Self, String> {
+        Ok(ExecWatcher {
+            inotify: Inotify::new()?,
+            proc_root: PathBuf::from("/proc"),
+            writers: HashMap::new(),
+            reported: HashMap::new(),
+        })
+    }
+
+    /// Watches `root` and its subdirectories, returning how many directories that took.
+    /// Files already there are not reported.
+    pub fn watch(&mut self, root: &Path) -> Result<usize, String> {
+        if !root.is_dir() {
+            return Err(format!("{} is not a directory", root.display()));
+        }
+        let mut existing = Vec::new();
+        self.add_tree(root, 0, &mut existing)
+    }
+
+    fn add_tree(
+        &mut self,
+        dir: &Path,
+        depth: usize,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<usize, String> {
+        self.inotify.add(dir, depth)?;
+        let mut watched = 1;
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(watched);
+        };
+        for entry in entries.flatten() {
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            if kind.is_dir() && depth < MAX_DEPTH {
+                // Unreadable subdirectories of /tmp are normal; skip them
+                watched += self.add_tree(&entry.path(), depth + 1, files).unwrap_or(0);
+            } else if kind.is_file() {
+                files.push(entry.path());
+            }
+        }
+        Ok(watched)
+    }
+
+    pub fn poll(&mut self, timeout: Duration) -> Result<Vec<ExecAlert>, String> {
+        let mut alerts = Vec::new();
+        for (path, mask, depth) in self.inotify.read(timeout)? {
+            if mask & libc::IN_ISDIR != 0 {
+                if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 && depth < MAX_DEPTH {
+                    // Anything written before the watch was in place would be missed otherwise
+                    let mut files = Vec::new();
+                    let _ = self.add_tree(&path, depth + 1, &mut files);
+                    alerts.extend(files.iter().filter_map(|file| self.check(file)));
+                }
+                continue;
+            }
+            if mask & libc::IN_DELETE != 0 {
+                self.writers.remove(&path);
+                self.reported.remove(&path);
+                continue;
+            }
+            if mask & libc::IN_CREATE != 0
+                && let Some(writer) = writer_of(&self.proc_root, &path)
+            {
+                self.writers.insert(path.clone(), writer);
+            }
+            if mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_ATTRIB) != 0 {
+                alerts.extend(self.check(&path));
+            }
+        }
+        Ok(alerts)
+    }
+
+    fn check(&mut self, path: &Path) -> Option<ExecAlert> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+            return None;
+        }
+        let sha256 = sha256_file(path).ok()?;
+        if self.reported.get(path) == Some(&sha256) {
+            return None;
+        }
+        let writer = self
+            .writers
+            .get(path)
+            .cloned()
+            .or_else(|| writer_of(&self.proc_root, path));
+        let self_copy = writer.as_ref().is_some_and(|writer| {
+            let exe = self.proc_root.join(writer.pid.to_string()).join("exe");
+            fs::metadata(&exe).is_ok_and(|exe| exe.len() == metadata.len())
+                && sha256_file(&exe).is_ok_and(|hash| hash == sha256)
+        });
+        self.reported.insert(path.to_path_buf(), sha256.clone());
+        Some(ExecAlert {
+            timestamp: Local::now(),
+            path: path.to_path_buf(),
+            sha256,
+            size: metadata.len(),
+            writer,
+            self_copy,
+        })
+    }
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// First process holding `path` open, found through the `/proc/<pid>/fd` links.
+fn writer_of(proc_root: &Path, path: &Path) -> Option<Writer> {
+    fs::read_dir(proc_root).ok()?.flatten().find_map(|entry| {
+        let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+        let open = fs::read_dir(entry.path().join("fd"))
+            .ok()?
+            .flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == path));
+        open.then(|| Writer {
+            pid,
+            name: fs::read_to_string(entry.path().join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_default(),
+            exe: fs::read_link(entry.path().join("exe"))
+                .ok()
+                .map(|exe| exe.display().to_string()),
+        })
+    })
+}
+
+/// Expands a leading `~` to `$HOME`.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn print_alert(alert: &ExecAlert) {
+    println!(
+        "{} [!] Executable created: {} ({} bytes)",
+        alert.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        alert.path.display(),
+        alert.size
+    );
+    println!("    sha256 {}", alert.sha256);
+    match &alert.writer {
+        Some(writer) => println!(
+            "    written by {} (pid {}, {}){}",
+            writer.name,
+            writer.pid,
+            writer.exe.as_deref().unwrap_or("exe unknown"),
+            if alert.self_copy {
+                ", a copy of its own binary"
+            } else {
+                ""
+            }
+        ),
+        None => println!("    writer unknown (file was closed before we looked)"),
+    }
+}
+
+/// Watches `paths` until killed, printing alerts and optionally posting them as JSON.
+pub fn run_watch(paths: &[String], webhook_url: Option<&str>) {
+    let mut watcher = match ExecWatcher::new() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            println!("[-] {}", e);
+            return;
+        }
+    };
+    let mut watched = 0;
+    for path in paths {
+        match watcher.watch(&expand_home(path)) {
+            Ok(count) => watched += count,
+            Err(e) => println!("[-] {}", e),
+        }
+    }
+    if watched == 0 {
+        println!("[-] Nothing to watch");
+        return;
+    }
+    println!(
+        "[+] Watching {} directories under {} for new executables",
+        watched,
+        paths.join(", ")
+    );
+
+    loop {
+        let alerts = match watcher.poll(Duration::from_secs(1)) {
+            Ok(alerts) => alerts,
+            Err(e) => {
+                println!("[-] {}", e);
+                return;
+            }
+        };
+        for alert in &alerts {
+            print_alert(alert);
+            if let Some(url) = webhook_url {
+                let body = serde_json::to_string(alert).unwrap_or_default();
+                if let Err(e) = webhook::post_json(url, &body) {
+                    eprintln!("[-] {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    /// Collects alerts until `attempts` polls in a row came back empty.
+    fn drain(watcher: &mut ExecWatcher, attempts: usize) -> Vec<ExecAlert> {
+        let mut alerts = Vec::new();
+        let mut quiet = 0;
+        while quiet < attempts {
+            let batch = watcher.poll(Duration::from_millis(100)).unwrap();
+            quiet = if batch.is_empty() { quiet + 1 } else { 0 };
+            alerts.extend(batch);
+        }
+        alerts
+    }
+
+    #[test]
+    fn reports_executables_with_hash_and_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = ExecWatcher::new().unwrap();
+        assert_eq!(watcher.watch(dir.path()).unwrap(), 1);
+
+        // Copy our own binary in while holding it open, like a dropper would
+        let own_binary = fs::read(env::current_exe().unwrap()).unwrap();
+        let path = dir.path().join("payload");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o755)
+            .open(&path)
+            .unwrap();
+        assert!(drain(&mut watcher, 2).is_empty());
+        file.write_all(&own_binary).unwrap();
+        drop(file);
+        fs::write(dir.path().join("notes.txt"), "not executable").unwrap();
+
+        let alerts = drain(&mut watcher, 3);
+        assert_eq!(alerts.len(), 1);
+        let alert = &alerts[0];
+        assert_eq!(alert.path, path);
+        assert_eq!(alert.size, own_binary.len() as u64);
+        assert_eq!(alert.sha256, sha256_file(&path).unwrap());
+        assert_eq!(
+            alert.writer.as_ref().map(|w| w.pid),
+            Some(std::process::id())
+        );
+        assert!(alert.self_copy);
+
+        // Making it executable again with the same contents is not news
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(drain(&mut watcher, 2).is_empty());
+    }
+
+    #[test]
+    fn follows_new_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = tempfile::tempdir().unwrap();
+        let script = source.path().join("tool");
+        fs::write(&script, "#!/bin/sh\necho hello\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut watcher = ExecWatcher::new().unwrap();
+        watcher.watch(dir.path()).unwrap();
+        let hidden = dir.path().join("wraithmarked_data");
+        fs::create_dir(&hidden).unwrap();
+        fs::copy(&script, hidden.join("tool")).unwrap();
+
+        let alerts = drain(&mut watcher, 3);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].path, hidden.join("tool"));
+        assert_eq!(
+            alerts[0].sha256,
+            "bfdeaeb08cffb6a36438bcd12dda25417e3cdd36f1e7e482a2849d539225288b"
+        );
+    }
+
+    #[test]
+    fn parses_event_buffers_and_home_paths() {
+        let mut buffer = Vec::new();
+        for (wd, mask, name) in [
+            (1, libc::IN_CREATE, &b"a.out\0\0\0"[..]),
+            (2, libc::IN_IGNORED, b""),
+        ] {
+            buffer.extend_from_slice(&i32::to_ne_bytes(wd));
+            buffer.extend_from_slice(&mask.to_ne_bytes());
+            buffer.extend_from_slice(&0u32.to_ne_bytes());
+            buffer.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+            buffer.extend_from_slice(name);
+        }
+        let events = parse_events(&buffer);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, b"a.out");
+        assert_eq!(events[1].mask, libc::IN_IGNORED);
+
+        assert_eq!(expand_home("/dev/shm"), PathBuf::from("/dev/shm"));
+        if let Some(home) = env::var_os("HOME") {
+            assert_eq!(
+                expand_home("~/.local/share"),
+                PathBuf::from(home).join(".local/share")
+            );
+        }
+    }
+}
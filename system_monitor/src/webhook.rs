@@ -0,0 +1,104 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// POSTs a JSON body to a plain `http://host[:port]/path` URL and checks for a 2xx reply.
+pub fn post_json(url: &str, body: &str) -> Result<(), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Only http:// webhooks are supported: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let mut stream = connect(&address).map_err(|e| format!("Webhook {}: {}", address, e))?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok();
+    stream.set_write_timeout(Some(TIMEOUT)).ok();
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority,
+        body.len(),
+        body
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("Webhook {}: {}", address, e))?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok();
+    let status = response.split_whitespace().nth(1).unwrap_or("");
+    if status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(format!("Webhook {} answered {:?}", url, status))
+    }
+}
+
+/// Tries each resolved address in turn, so a host that drops packets costs
+/// `TIMEOUT` per address instead of the OS connect timeout.
+fn connect(address: &str) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses to connect to")
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn posts_and_checks_the_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut statuses = ["500 Internal Server Error", "204 No Content"].into_iter();
+            let mut requests = Vec::new();
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                requests.push(line);
+                let status = statuses.next().unwrap();
+                write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+            }
+            requests
+        });
+        assert_eq!(
+            post_json(&url, "{}"),
+            Err(format!("Webhook {} answered \"500\"", url))
+        );
+        assert_eq!(post_json(&url, "{}"), Ok(()));
+        assert_eq!(server.join().unwrap(), ["POST /hook HTTP/1.1\r\n"; 2]);
+        assert!(post_json("https://example.com", "{}").is_err());
+    }
+
+    #[test]
+    fn gives_up_on_unreachable_hosts() {
+        // Nothing answers at this non-routable address, so the connect would
+        // otherwise hang for the OS timeout
+        let start = Instant::now();
+        assert!(post_json("http://10.255.255.1:9/hook", "{}").is_err());
+        assert!(start.elapsed() < TIMEOUT + Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,223 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::storage;
+use crate::task::TaskError;
+
+const HEADER: &str = "# todo_cli config";
+
+/// Settings kept apart from the task file, so they follow the user rather
+/// than one list: the saved `list` queries and the sync server and token.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// Names and query text, in the order they were saved.
+    pub queries: Vec<(String, String)>,
+    /// URL of the server `sync` uses when given none.
+    pub server: Option<String>,
+    /// Secret a sync server and its clients share.
+    pub token: Option<String>,
+}
+
+/// `$TODO_CLI_CONFIG`, or `todo_cli/config` under `$XDG_CONFIG_HOME` or
+/// `~/.config`.
+pub fn default_path() -> String {
+    if let Ok(path) = env::var("TODO_CLI_CONFIG") {
+        return path;
+    }
+    let base = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|base| !base.is_empty())
+        .or_else(|| {
+            env::var("HOME")
+                .ok()
+                .map(|home| format!("{}/.config", home))
+        });
+    match base {
+        Some(base) => format!("{}/todo_cli/config", base),
+        None => "todo_cli.conf".to_string(),
+    }
+}
+
+/// Query names are single words so they are easy to type after `--query`.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+impl Config {
+    /// A missing file is an empty config.
+    pub fn load(path: &str) -> Result<Config, TaskError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Config::parse(&contents)
+                .map_err(|reason| TaskError::Config(format!("{}: {}", path, reason))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// `name = query` lines under `[queries]`, and `server = <url>` and
+    /// `token = <secret>` under `[sync]`; `#` starts a comment line.
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut section = None;
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                if name != "queries" && name != "sync" {
+                    return Err(format!("line {}: unknown section [{}]", index + 1, name));
+                }
+                section = Some(name);
+                continue;
+            }
+            if section == Some("sync") {
+                match line
+                    .split_once('=')
+                    .map(|(key, value)| (key.trim(), value.trim()))
+                {
+                    Some(("server", url)) => config.server = Some(url.to_string()),
+                    Some(("token", token)) if !token.is_empty() => {
+                        config.token = Some(token.to_string())
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {}: expected server = <url> or token = <secret>",
+                            index + 1
+                        ));
+                    }
+                }
+                continue;
+            }
+            let Some((name, query)) = line.split_once('=').filter(|_| section.is_some()) else {
+                return Err(format!("line {}: expected name = query", index + 1));
+            };
+            let name = name.trim();
+            if !valid_name(name) {
+                return Err(format!("line {}: bad query name '{}'", index + 1, name));
+            }
+            config.set_query(name, query.trim());
+        }
+        Ok(config)
+    }
+
+    pub fn render(&self) -> String {
+        let mut contents = format!("{}\n[queries]\n", HEADER);
+        for (name, query) in &self.queries {
+            contents.push_str(&format!("{} = {}\n", name, query));
+        }
+        if self.server.is_some() || self.token.is_some() {
+            contents.push_str("\n[sync]\n");
+        }
+        if let Some(server) = &self.server {
+            contents.push_str(&format!("server = {}\n", server));
+        }
+        if let Some(token) = &self.token {
+            contents.push_str(&format!("token = {}\n", token));
+        }
+        contents
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), TaskError> {
+        if let Some(dir) = Path::new(path)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir)?;
+        }
+        if self.token.is_none() {
+            return storage::write_file(path, &self.render());
+        }
+        storage::write_private(path, &self.render())?;
+        // A backup from before the token was set or changed
+        match fs::remove_file(format!("{}.bak", path)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.queries
+            .iter()
+            .find(|(saved, _)| saved == name)
+            .map(|(_, query)| query.as_str())
+    }
+
+    /// Replaces a query of the same name in place.
+    pub fn set_query(&mut self, name: &str, query: &str) {
+        match self.queries.iter_mut().find(|(saved, _)| saved == name) {
+            Some(saved) => saved.1 = query.to_string(),
+            None => self.queries.push((name.to_string(), query.to_string())),
+        }
+    }
+
+    pub fn remove_query(&mut self, name: &str) -> bool {
+        let before = self.queries.len();
+        self.queries.retain(|(saved, _)| saved != name);
+        self.queries.len() < before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+
+    #[test]
+    fn parses_and_renders_saved_queries() {
+        let contents = "# mine\n\n[sync]\nserver = http://nas:7878\ntoken = 5e3f\n[queries]\nwork = project:work and not done\nsoon= due<=\"in 3d\"\n";
+        let mut config = Config::parse(contents).unwrap();
+        assert_eq!(config.query("soon"), Some("due<=\"in 3d\""));
+        assert_eq!(config.server.as_deref(), Some("http://nas:7878"));
+        assert_eq!(config.token.as_deref(), Some("5e3f"));
+        assert_eq!(Config::parse(&config.render()).unwrap(), config);
+        config.server = None;
+        config.token = None;
+        config.set_query("work", "project:work");
+        assert!(config.remove_query("soon"));
+        assert!(!config.remove_query("soon"));
+        assert_eq!(
+            config.render(),
+            "# todo_cli config\n[queries]\nwork = project:work\n"
+        );
+        assert_eq!(Config::parse(&config.render()).unwrap(), config);
+
+        assert!(Config::parse("work = done\n").is_err());
+        assert!(Config::parse("[colors]\n").is_err());
+        assert!(Config::parse("[queries]\nmy query = done\n").is_err());
+        assert!(Config::parse("[sync]\nport = 80\n").is_err());
+        assert!(Config::parse("[sync]\ntoken =\n").is_err());
+    }
+
+    #[test]
+    fn keeps_the_sync_token_private() {
+        let file = Scratch::new();
+        let path = file.config();
+        let mut config = Config::default();
+        config.set_query("work", "project:work");
+        config.save(&path).unwrap();
+        config.save(&path).unwrap();
+        assert!(Path::new(&format!("{}.bak", path)).exists());
+
+        config.token = Some("5e3f".to_string());
+        config.save(&path).unwrap();
+        config.token = Some("77aa".to_string());
+        config.save(&path).unwrap();
+        assert!(!Path::new(&format!("{}.bak", path)).exists());
+        assert_eq!(Config::load(&path).unwrap(), config);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use chrono::{
+    Datelike, Days, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike, Weekday,
+};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A due date, optionally at a particular time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Due {
+    pub date: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+impl Due {
+    /// A due date without a time sorts after every timed one on that day.
+    fn sort_key(&self) -> (NaiveDate, NaiveTime) {
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default();
+        (self.date, self.time.unwrap_or(end_of_day))
+    }
+
+    /// Past due once its day is over, or once its time has passed.
+    pub fn is_overdue(&self, now: NaiveDateTime) -> bool {
+        match self.time {
+            Some(time) => self.date.and_time(time) < now,
+            None => self.date < now.date(),
+        }
+    }
+
+    /// `2026-10-20` or `2026-10-20T17:00`, as written to the task file.
+    pub fn to_stored(self) -> String {
+        match self.time {
+            Some(time) => format!("{}T{}", self.date, time.format("%H:%M")),
+            None => self.date.to_string(),
+        }
+    }
+
+    pub fn from_stored(text: &str) -> Option<Due> {
+        let (date, time) = match text.split_once('T') {
+            Some((date, time)) => (date, Some(NaiveTime::parse_from_str(time, "%H:%M").ok()?)),
+            None => (text, None),
+        };
+        Some(Due {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?,
+            time,
+        })
+    }
+}
+
+impl Ord for Due {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Due {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Due {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.time {
+            Some(time) => write!(f, "{} {}", self.date, time.format("%H:%M")),
+            None => write!(f, "{}", self.date),
+        }
+    }
+}
+
+/// Understands `2026-10-20`, `today`, `tomorrow`, weekday names (`fri`,
+/// `next fri`: the coming one, never today), `next week`, `next month`,
+/// `in 3d`, `2w`, `in 1m`, `in 4h`, each optionally followed by `17:00`,
+/// `9am` or `5pm`.
+pub fn parse_due(text: &str, now: NaiveDateTime) -> Result<Due, String> {
+    let invalid = || format!("cannot understand the date '{}'", text);
+    let lowered = text.trim().to_lowercase();
+    let mut words: Vec<&str> = lowered.split_whitespace().collect();
+    if words.is_empty() {
+        return Err(invalid());
+    }
+
+    if let Some(due) = Due::from_stored(&words[0].to_uppercase()).filter(|_| words.len() == 1) {
+        return Ok(due);
+    }
+    let mut time = match words.last().and_then(|word| parse_time(word)) {
+        Some(time) => {
+            words.pop();
+            Some(time)
+        }
+        None => None,
+    };
+
+    let today = now.date();
+    let date = match words[..] {
+        [] | ["today"] | ["tod"] => Some(today),
+        ["tomorrow"] | ["tom"] => today.checked_add_days(Days::new(1)),
+        ["yesterday"] => today.checked_sub_days(Days::new(1)),
+        ["next", "week"] => today.checked_add_days(Days::new(7)),
+        ["next", "month"] => today.checked_add_months(Months::new(1)),
+        ["next", day] | [day] if parse_weekday(day).is_some() => {
+            let target = parse_weekday(day).ok_or_else(invalid)?;
+            let ahead =
+                (7 + target.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            today.checked_add_days(Days::new(if ahead == 0 { 7 } else { ahead as u64 }))
+        }
+        [date] if date.contains('-') => NaiveDate::parse_from_str(date, "%Y-%m-%d").ok(),
+        ["in", amount] | [amount] => {
+            let (count, unit) = split_amount(amount).ok_or_else(invalid)?;
+            offset(now, count, unit, &mut time)
+        }
+        ["in", count, unit] => {
+            let count = count.parse().map_err(|_| invalid())?;
+            offset(now, count, unit, &mut time)
+        }
+        _ => None,
+    };
+    Ok(Due {
+        date: date.ok_or_else(invalid)?,
+        time,
+    })
+}
+
+/// `17:00`, `9am`, `5:30pm`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    if let Ok(time) = NaiveTime::parse_from_str(word, "%H:%M") {
+        return Some(time);
+    }
+    let (clock, afternoon) = match (word.strip_suffix("am"), word.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, false),
+        (_, Some(clock)) => (clock, true),
+        _ => return None,
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    if !(1..=12).contains(&hour) {
+        return None;
+    }
+    NaiveTime::from_hms_opt(hour % 12 + if afternoon { 12 } else { 0 }, minute, 0)
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    const NAMES: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon),
+        ("tuesday", Weekday::Tue),
+        ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu),
+        ("friday", Weekday::Fri),
+        ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    if word.len() < 3 {
+        return None;
+    }
+    NAMES
+        .iter()
+        .find(|(name, _)| name.starts_with(word))
+        .map(|(_, day)| *day)
+}
+
+/// `3d` into `(3, "d")`.
+fn split_amount(word: &str) -> Option<(u32, &str)> {
+    let digits = word.find(|c: char| !c.is_ascii_digit())?;
+    Some((word[..digits].parse().ok()?, &word[digits..]))
+}
+
+/// Moves `now` forward by `count` units. Hours also set the time of day.
+fn offset(
+    now: NaiveDateTime,
+    count: u32,
+    unit: &str,
+    time: &mut Option<NaiveTime>,
+) -> Option<NaiveDate> {
+    let today = now.date();
+    match unit {
+        "d" | "day" | "days" => today.checked_add_days(Days::new(count.into())),
+        "w" | "wk" | "week" | "weeks" => today.checked_add_days(Days::new(u64::from(count) * 7)),
+        "m" | "mo" | "month" | "months" => today.checked_add_months(Months::new(count)),
+        "h" | "hour" | "hours" => {
+            let later = now.checked_add_signed(TimeDelta::hours(count.into()))?;
+            *time = Some(NaiveTime::from_hms_opt(
+                later.time().hour(),
+                later.time().minute(),
+                0,
+            )?);
+            Some(later.date())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        // A Wednesday afternoon
+        NaiveDate::from_ymd_opt(2026, 10, 14)
+            .unwrap()
+            .and_hms_opt(15, 20, 0)
+            .unwrap()
+    }
+
+    fn parse(text: &str) -> String {
+        parse_due(text, now()).unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_natural_dates() {
+        assert_eq!(parse("today"), "2026-10-14");
+        assert_eq!(parse("tomorrow 17:00"), "2026-10-15 17:00");
+        assert_eq!(parse("fri"), "2026-10-16");
+        assert_eq!(parse("next fri 9am"), "2026-10-16 09:00");
+        assert_eq!(parse("Wednesday"), "2026-10-21");
+        assert_eq!(parse("in 3d"), "2026-10-17");
+        assert_eq!(parse("2w"), "2026-10-28");
+        assert_eq!(parse("in 1 month"), "2026-11-14");
+        assert_eq!(parse("in 10h"), "2026-10-15 01:20");
+        assert_eq!(parse("next week"), "2026-10-21");
+        assert_eq!(parse("2026-12-24 12:30pm"), "2026-12-24 12:30");
+        assert_eq!(parse("5pm"), "2026-10-14 17:00");
+        assert_eq!(parse("2026-12-24T08:00"), "2026-12-24 08:00");
+        assert!(parse_due("someday", now()).is_err());
+        assert!(parse_due("in 3 fortnights", now()).is_err());
+    }
+
+    #[test]
+    fn orders_and_stores_due_dates() {
+        let timed = Due::from_stored("2026-10-14T09:00").unwrap();
+        let all_day = Due::from_stored("2026-10-14").unwrap();
+        assert!(timed < all_day);
+        assert_eq!(timed.to_stored(), "2026-10-14T09:00");
+        assert!(timed.is_overdue(now()));
+        assert!(!all_day.is_overdue(now()));
+        assert_eq!(Due::from_stored("14/10/2026"), None);
+    }
+}
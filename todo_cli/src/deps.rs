@@ -0,0 +1,154 @@
+use crate::task::{Task, TaskError};
+
+/// Done and total counts over every task nested under `id`, or `None` when
+/// it has no subtasks.
+pub fn progress(tasks: &[Task], id: &str) -> Option<(usize, usize)> {
+    let (mut done, mut total) = (0, 0);
+    let mut pending = vec![id];
+    // Tracked in case a hand-edited file nests a task under itself
+    let mut seen = vec![id];
+    while let Some(parent) = pending.pop() {
+        for child in tasks
+            .iter()
+            .filter(|task| task.parent.as_deref() == Some(parent))
+        {
+            if seen.contains(&child.id.as_str()) {
+                continue;
+            }
+            seen.push(&child.id);
+            total += 1;
+            done += usize::from(child.completed);
+            pending.push(&child.id);
+        }
+    }
+    (total > 0).then_some((done, total))
+}
+
+/// The tasks `task` is blocked by that are still open. Links to tasks that no
+/// longer exist are ignored.
+pub fn open_blockers<'a>(tasks: &'a [Task], task: &Task) -> Vec<&'a Task> {
+    task.blocked_by
+        .iter()
+        .filter_map(|id| tasks.iter().find(|other| &other.id == id))
+        .filter(|blocker| !blocker.completed)
+        .collect()
+}
+
+/// Rejects a parent or blocker link from `tasks[index]` that leads back to it.
+pub fn check_cycles(tasks: &[Task], index: usize) -> Result<(), TaskError> {
+    let start = &tasks[index].id;
+    let parents = |task: &Task| task.parent.iter().cloned().collect();
+    let blockers = |task: &Task| task.blocked_by.clone();
+    for next in [&parents as &dyn Fn(&Task) -> Vec<String>, &blockers] {
+        if let Some(path) = path_to(tasks, start, start, next, &mut Vec::new()) {
+            let mut cycle = vec![start.clone()];
+            cycle.extend(path);
+            return Err(TaskError::Cycle(cycle));
+        }
+    }
+    Ok(())
+}
+
+/// IDs leading from `from` to `target` by following `next`, ending with
+/// `target`.
+fn path_to(
+    tasks: &[Task],
+    from: &str,
+    target: &str,
+    next: &dyn Fn(&Task) -> Vec<String>,
+    seen: &mut Vec<String>,
+) -> Option<Vec<String>> {
+    let task = tasks.iter().find(|task| task.id == from)?;
+    for id in next(task) {
+        if id == target {
+            return Some(vec![id]);
+        }
+        if seen.contains(&id) {
+            continue;
+        }
+        seen.push(id.clone());
+        if let Some(mut path) = path_to(tasks, &id, target, next, seen) {
+            path.insert(0, id);
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Drops links to a removed task. Its subtasks move up to its own parent.
+pub fn forget(tasks: &mut [Task], removed: &Task) {
+    for task in tasks {
+        if task.parent.as_ref() == Some(&removed.id) {
+            task.parent = removed.parent.clone();
+        }
+        task.blocked_by.retain(|id| id != &removed.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, parent: Option<&str>, blocked_by: &[&str], completed: bool) -> Task {
+        Task {
+            id: id.to_string(),
+            parent: parent.map(str::to_string),
+            blocked_by: blocked_by.iter().map(|id| id.to_string()).collect(),
+            completed,
+            ..Task::default()
+        }
+    }
+
+    #[test]
+    fn rolls_up_progress_and_blockers() {
+        let mut tasks = vec![
+            task("a0", None, &[], false),
+            task("b0", Some("a0"), &[], true),
+            task("c0", Some("a0"), &["b0", "d0", "gone"], false),
+            task("d0", Some("c0"), &[], false),
+        ];
+        assert_eq!(progress(&tasks, "a0"), Some((1, 3)));
+        assert_eq!(progress(&tasks, "c0"), Some((0, 1)));
+        assert_eq!(progress(&tasks, "d0"), None);
+        // Tasks on a parent cycle are not their own subtasks
+        tasks[0].parent = Some("c0".to_string());
+        assert_eq!(progress(&tasks, "a0"), Some((1, 3)));
+        assert_eq!(progress(&tasks, "d0"), None);
+        tasks[0].parent = None;
+        let open: Vec<&str> = open_blockers(&tasks, &tasks[2])
+            .iter()
+            .map(|task| task.id.as_str())
+            .collect();
+        assert_eq!(open, ["d0"]);
+
+        let removed = tasks.remove(2);
+        forget(&mut tasks, &removed);
+        assert_eq!(tasks[2].parent.as_deref(), Some("a0"));
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let mut tasks = vec![
+            task("a0", None, &["c0"], false),
+            task("b0", Some("a0"), &["a0"], false),
+            task("c0", Some("b0"), &[], false),
+        ];
+        assert!(check_cycles(&tasks, 2).is_ok());
+
+        tasks[0].parent = Some("c0".to_string());
+        let Err(TaskError::Cycle(cycle)) = check_cycles(&tasks, 0) else {
+            panic!("expected a cycle");
+        };
+        assert_eq!(cycle, ["a0", "c0", "b0", "a0"]);
+
+        tasks[0].parent = None;
+        tasks[2].blocked_by = vec!["b0".to_string()];
+        assert!(
+            matches!(check_cycles(&tasks, 2), Err(TaskError::Cycle(cycle)) if cycle.len() == 4)
+        );
+        tasks[2].blocked_by = vec!["c0".to_string()];
+        assert!(
+            matches!(check_cycles(&tasks, 2), Err(TaskError::Cycle(cycle)) if cycle == ["c0", "c0"])
+        );
+    }
+}
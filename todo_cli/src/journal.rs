@@ -0,0 +1,602 @@
+use chrono::{DateTime, Local, SecondsFormat};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::storage::{self, COLUMNS};
+use crate::task::{Task, TaskError};
+use crate::todo_txt;
+
+const MAGIC: &str = "# todo_cli journal";
+const VERSION: u32 = 1;
+
+/// One task as it was before and after an operation, each with its position
+/// in the list. `None` on either side means the task did not exist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub before: Option<(usize, Task)>,
+    pub after: Option<(usize, Task)>,
+}
+
+impl Change {
+    fn task(&self) -> &Task {
+        match (&self.before, &self.after) {
+            (_, Some((_, task))) | (Some((_, task)), None) => task,
+            (None, None) => unreachable!("a change has at least one side"),
+        }
+    }
+
+    /// `added Buy milk`, `removed Buy milk` or `changed Pay rent: due, priority`.
+    pub fn describe(&self) -> String {
+        match (&self.before, &self.after) {
+            (Some((_, old)), Some((_, new))) => {
+                let fields = changed_fields(old, new);
+                if fields.is_empty() {
+                    format!("changed {}", new.title)
+                } else {
+                    format!("changed {}: {}", new.title, fields.join(", "))
+                }
+            }
+            (None, _) => format!("added {}", self.task().title),
+            (_, None) => format!("removed {}", self.task().title),
+        }
+    }
+}
+
+/// An operation as recorded, such as one `rm` or `import`.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub seq: usize,
+    pub time: DateTime<Local>,
+    /// The command line that made the change, e.g. `rm 3f2`.
+    pub action: String,
+    pub changes: Vec<Change>,
+    pub undone: bool,
+}
+
+/// The operations made to one task file, kept in `<file>.journal`. The journal
+/// is only ever appended to: `undo` and `redo` add a line naming the operation
+/// they reverted or replayed.
+pub struct Journal {
+    path: String,
+    task_path: String,
+    pub entries: Vec<Entry>,
+    /// Entries `undo` would revert, the newest last.
+    done: Vec<usize>,
+    /// Entries `redo` would replay, the most recently undone last.
+    undone: Vec<usize>,
+    /// The columns the last task rows were written with.
+    columns: Option<String>,
+}
+
+fn check_header(header: &str) -> Result<(), String> {
+    let version = header
+        .strip_prefix(MAGIC)
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .ok_or("missing its header")?;
+    if version > VERSION {
+        return Err(format!(
+            "format version {}, which this todo_cli cannot read",
+            version
+        ));
+    }
+    Ok(())
+}
+
+pub fn path_for(task_path: &str) -> String {
+    format!("{}.journal", task_path)
+}
+
+impl Journal {
+    /// A missing journal has no entries yet.
+    pub fn load(task_path: &str) -> Result<Journal, TaskError> {
+        let path = path_for(task_path);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut journal = Journal {
+            path,
+            task_path: task_path.to_string(),
+            entries: Vec::new(),
+            done: Vec::new(),
+            undone: Vec::new(),
+            columns: None,
+        };
+        let torn = journal
+            .parse(&contents)
+            .map_err(|reason| TaskError::Journal(format!("{}: {}", journal.path, reason)))?;
+        // Cut the broken line off so the next record starts on a line of its own
+        if let Some(length) = torn {
+            eprintln!(
+                "Dropped an incomplete last line from {}, likely left by a crash",
+                journal.path
+            );
+            OpenOptions::new()
+                .write(true)
+                .open(&journal.path)?
+                .set_len(length as u64)?;
+        }
+        Ok(journal)
+    }
+
+    /// Replays the journal to find which operations are currently undone.
+    /// A crash while appending can leave the last line cut short; it is
+    /// skipped and its offset returned rather than failing every later command.
+    fn parse(&mut self, contents: &str) -> Result<Option<usize>, String> {
+        let mut offset = 0;
+        for (index, line) in contents.split_inclusive('\n').enumerate() {
+            let start = offset;
+            offset += line.len();
+            let complete = line.ends_with('\n');
+            let result = match line.trim_end_matches('\n') {
+                _ if !complete => Err("cut short".to_string()),
+                header if index == 0 => check_header(header),
+                "" => Ok(()),
+                line => self.parse_line(line),
+            };
+            match result {
+                Ok(()) => {}
+                // A bad header means this is no journal, even on its own
+                Err(_) if offset == contents.len() && (index > 0 || !complete) => {
+                    return Ok(Some(start));
+                }
+                Err(reason) => return Err(format!("line {}: {}", index + 1, reason)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let (kind, rest) = line.split_once('\t').unwrap_or((line, ""));
+        match kind {
+            "columns" => self.columns = Some(rest.to_string()),
+            "op" => {
+                let [seq, time, action] = fields(rest)?;
+                let entry = Entry {
+                    seq: self.next_seq(seq)?,
+                    time: parse_time(time)?,
+                    action: storage::unescape(action)?,
+                    changes: Vec::new(),
+                    undone: false,
+                };
+                self.undone.clear();
+                self.done.push(self.entries.len());
+                self.entries.push(entry);
+            }
+            "-" | "+" => {
+                let (position, row) = rest.split_once('\t').ok_or("expected a position")?;
+                let position = position
+                    .parse()
+                    .map_err(|_| format!("bad position '{}'", position))?;
+                let columns = self
+                    .columns
+                    .as_deref()
+                    .ok_or("task row before its columns")?;
+                let columns: Vec<&str> = columns.split('\t').collect();
+                let task = storage::parse_row(&columns, row)?;
+                let entry = self.entries.last_mut().ok_or("task row before any op")?;
+                let last = entry
+                    .changes
+                    .last_mut()
+                    .filter(|change| change.after.is_none() && change.task().id == task.id);
+                match (kind, last) {
+                    // `+` right after `-` for the same task is its other side
+                    ("+", Some(change)) => change.after = Some((position, task)),
+                    ("+", None) => entry.changes.push(Change {
+                        before: None,
+                        after: Some((position, task)),
+                    }),
+                    _ => entry.changes.push(Change {
+                        before: Some((position, task)),
+                        after: None,
+                    }),
+                }
+            }
+            "undo" | "redo" => {
+                let [seq, time] = fields(rest)?;
+                parse_time(time)?;
+                let (from, to) = if kind == "undo" {
+                    (&mut self.done, &mut self.undone)
+                } else {
+                    (&mut self.undone, &mut self.done)
+                };
+                let index = from
+                    .last()
+                    .copied()
+                    .filter(|index| self.entries[*index].seq.to_string() == seq)
+                    .ok_or_else(|| format!("cannot {} op {} here", kind, seq))?;
+                from.pop();
+                to.push(index);
+                self.entries[index].undone = kind == "undo";
+            }
+            other => return Err(format!("unknown record '{}'", other)),
+        }
+        Ok(())
+    }
+
+    fn next_seq(&self, seq: &str) -> Result<usize, String> {
+        let expected = self.entries.len() + 1;
+        match seq.parse() {
+            Ok(seq) if seq == expected => Ok(seq),
+            _ => Err(format!("expected op {}, found '{}'", expected, seq)),
+        }
+    }
+
+    /// Appends the difference between `before` and `after` as an operation,
+    /// if there is one. Anything that could have been redone no longer can.
+    pub fn record(
+        &mut self,
+        before: &[Task],
+        after: &[Task],
+        action: &str,
+    ) -> Result<(), TaskError> {
+        let changes = diff(before, after);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut lines = String::new();
+        let columns = COLUMNS.join("\t");
+        if self.columns.as_ref() != Some(&columns) {
+            lines.push_str(&format!("columns\t{}\n", columns));
+            self.columns = Some(columns);
+        }
+        let entry = Entry {
+            seq: self.entries.len() + 1,
+            time: Local::now(),
+            action: action.to_string(),
+            changes,
+            undone: false,
+        };
+        lines.push_str(&format!(
+            "op\t{}\t{}\t{}\n",
+            entry.seq,
+            stamp(entry.time),
+            storage::escape(&entry.action)
+        ));
+        for change in &entry.changes {
+            for (sign, side) in [("-", &change.before), ("+", &change.after)] {
+                if let Some((position, task)) = side {
+                    lines.push_str(&format!(
+                        "{}\t{}\t{}\n",
+                        sign,
+                        position,
+                        storage::format_row(task)
+                    ));
+                }
+            }
+        }
+        self.append(&lines)?;
+        self.undone.clear();
+        self.done.push(self.entries.len());
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Reverts up to `count` operations, newest first, and saves the result.
+    /// Returns the actions that were undone.
+    pub fn undo(&mut self, tasks: &mut Vec<Task>, count: usize) -> Result<Vec<String>, TaskError> {
+        self.step(tasks, count, true)
+    }
+
+    /// Replays up to `count` undone operations, the most recently undone first.
+    pub fn redo(&mut self, tasks: &mut Vec<Task>, count: usize) -> Result<Vec<String>, TaskError> {
+        self.step(tasks, count, false)
+    }
+
+    /// Nothing is saved unless every step applies.
+    fn step(
+        &mut self,
+        tasks: &mut Vec<Task>,
+        count: usize,
+        undo: bool,
+    ) -> Result<Vec<String>, TaskError> {
+        let mut stepped = Vec::new();
+        for _ in 0..count {
+            let stack = if undo { &self.done } else { &self.undone };
+            let Some(&index) = stack.iter().rev().nth(stepped.len()) else {
+                break;
+            };
+            let entry = &self.entries[index];
+            apply(&self.task_path, tasks, &entry.changes, undo).map_err(|title| {
+                TaskError::Journal(format!(
+                    "task {} was changed outside todo_cli after `{}`",
+                    title, entry.action
+                ))
+            })?;
+            stepped.push(index);
+        }
+        if stepped.is_empty() {
+            return Ok(Vec::new());
+        }
+        storage::save_tasks(&self.task_path, tasks)?;
+
+        let kind = if undo { "undo" } else { "redo" };
+        let time = stamp(Local::now());
+        let lines: String = stepped
+            .iter()
+            .map(|index| format!("{}\t{}\t{}\n", kind, self.entries[*index].seq, time))
+            .collect();
+        self.append(&lines)?;
+        let (from, to) = if undo {
+            (&mut self.done, &mut self.undone)
+        } else {
+            (&mut self.undone, &mut self.done)
+        };
+        from.truncate(from.len() - stepped.len());
+        to.extend(&stepped);
+        for index in &stepped {
+            self.entries[*index].undone = undo;
+        }
+        Ok(stepped
+            .iter()
+            .map(|index| self.entries[*index].action.clone())
+            .collect())
+    }
+
+    fn append(&self, lines: &str) -> Result<(), TaskError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(format!("{} {}\n", MAGIC, VERSION).as_bytes())?;
+        }
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Saves `tasks` and journals how they differ from `loaded`, so `undo` can
+/// take the change back.
+pub fn save(path: &str, loaded: &[Task], tasks: &[Task], action: &str) -> Result<(), TaskError> {
+    let mut journal = Journal::load(path)?;
+    storage::save_tasks(path, tasks)?;
+    journal.record(loaded, tasks, action)
+}
+
+/// The tasks that differ between `before` and `after`, matched by ID.
+pub fn diff(before: &[Task], after: &[Task]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (position, old) in before.iter().enumerate() {
+        match after.iter().position(|task| task.id == old.id) {
+            Some(new) if after[new] == *old => {}
+            new => changes.push(Change {
+                before: Some((position, old.clone())),
+                after: new.map(|new| (new, after[new].clone())),
+            }),
+        }
+    }
+    for (position, new) in after.iter().enumerate() {
+        if !before.iter().any(|task| task.id == new.id) {
+            changes.push(Change {
+                before: None,
+                after: Some((position, new.clone())),
+            });
+        }
+    }
+    changes
+}
+
+/// Moves `tasks` from the after side of `changes` to the before side, or the
+/// other way round when redoing. Returns the title of a task that is not
+/// where the operation left it.
+fn apply(
+    task_path: &str,
+    tasks: &mut Vec<Task>,
+    changes: &[Change],
+    undo: bool,
+) -> Result<(), String> {
+    let sides = |change: &Change| {
+        if undo {
+            (change.after.clone(), change.before.clone())
+        } else {
+            (change.before.clone(), change.after.clone())
+        }
+    };
+    for change in changes {
+        let id = &change.task().id;
+        let current = tasks.iter().find(|task| &task.id == id);
+        let unchanged = match (sides(change).0, current) {
+            (Some((_, expected)), Some(current)) => {
+                stored(task_path, &expected) == stored(task_path, current)
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if !unchanged {
+            return Err(current.unwrap_or(change.task()).title.clone());
+        }
+    }
+    tasks.retain(|task| !changes.iter().any(|change| change.task().id == task.id));
+    let mut targets: Vec<(usize, Task)> = changes
+        .iter()
+        .filter_map(|change| sides(change).1)
+        .collect();
+    targets.sort_by_key(|(position, _)| *position);
+    for (position, task) in targets {
+        tasks.insert(position.min(tasks.len()), task);
+    }
+    Ok(())
+}
+
+/// How `task` is written to `task_path`; tasks that look the same there are
+/// the same as far as the journal is concerned.
+fn stored(task_path: &str, task: &Task) -> String {
+    if todo_txt::is_todo_txt(task_path) {
+        todo_txt::format_line(task)
+    } else {
+        storage::format_row(task)
+    }
+}
+
+fn changed_fields(old: &Task, new: &Task) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if old.title != new.title {
+        fields.push("title");
+    }
+    if old.completed != new.completed {
+        fields.push(if new.completed {
+            "completed"
+        } else {
+            "reopened"
+        });
+    }
+    if old.priority != new.priority {
+        fields.push("priority");
+    }
+    if old.due != new.due {
+        fields.push("due");
+    }
+    if (old.recur, old.recur_day) != (new.recur, new.recur_day) {
+        fields.push("recurrence");
+    }
+    if old.parent != new.parent {
+        fields.push("parent");
+    }
+    if old.blocked_by != new.blocked_by {
+        fields.push("blockers");
+    }
+    if (&old.projects, &old.contexts, &old.extensions)
+        != (&new.projects, &new.contexts, &new.extensions)
+    {
+        fields.push("tags");
+    }
+    fields
+}
+
+fn fields<const N: usize>(rest: &str) -> Result<[&str; N], String> {
+    let fields: Vec<&str> = rest.splitn(N, '\t').collect();
+    fields
+        .try_into()
+        .map_err(|_| format!("expected {} fields", N + 1))
+}
+
+fn stamp(time: DateTime<Local>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+fn parse_time(text: &str) -> Result<DateTime<Local>, String> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Local))
+        .map_err(|_| format!("bad timestamp '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+
+    fn task(id: &str, title: &str) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            ..Task::default()
+        }
+    }
+
+    #[test]
+    fn reverts_and_replays_changes() {
+        let before = vec![task("a0", "one"), task("b0", "two"), task("c0", "three")];
+        let mut after = before.clone();
+        after.remove(0);
+        after[0].set_completed(true);
+        after[0].due = crate::dates::Due::from_stored("2026-10-20");
+        after.push(task("d0", "four"));
+
+        let changes = diff(&before, &after);
+        let described: Vec<String> = changes.iter().map(Change::describe).collect();
+        assert_eq!(
+            described,
+            ["removed one", "changed two: completed, due", "added four"]
+        );
+
+        let mut tasks = after.clone();
+        apply("tasks.txt", &mut tasks, &changes, true).unwrap();
+        assert_eq!(tasks, before);
+        apply("tasks.txt", &mut tasks, &changes, false).unwrap();
+        assert_eq!(tasks, after);
+        // Undoing again finds the tasks already moved on
+        tasks[0].title = "edited".to_string();
+        assert_eq!(
+            apply("tasks.txt", &mut tasks, &changes, true),
+            Err("edited".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_undo_state_across_loads() {
+        let file = Scratch::new();
+        let one = vec![task("a0", "one")];
+        let two = vec![task("a0", "one"), task("b0", "two\tlines")];
+        let mut journal = Journal::load(file.path()).unwrap();
+        journal.record(&[], &one, "add one").unwrap();
+        journal.record(&one, &one, "list").unwrap();
+        journal.record(&one, &two, "add two").unwrap();
+        storage::save_tasks(file.path(), &two).unwrap();
+
+        let mut tasks = two.clone();
+        let mut journal = Journal::load(file.path()).unwrap();
+        assert_eq!(journal.entries.len(), 2);
+        assert_eq!(journal.undo(&mut tasks, 5).unwrap(), ["add two", "add one"]);
+        assert!(tasks.is_empty());
+        assert_eq!(journal.redo(&mut tasks, 1).unwrap(), ["add one"]);
+        assert_eq!(storage::load_tasks(file.path()).unwrap(), one);
+
+        let mut journal = Journal::load(file.path()).unwrap();
+        assert!(journal.entries[1].undone && !journal.entries[0].undone);
+        assert_eq!(
+            journal.entries[1].changes[0].after.as_ref().unwrap().1,
+            two[1]
+        );
+        // A new operation drops what could have been redone
+        journal.record(&one, &[], "rm one").unwrap();
+        let mut journal = Journal::load(file.path()).unwrap();
+        assert!(journal.redo(&mut tasks, 1).unwrap().is_empty());
+
+        fs::write(
+            path_for(file.path()),
+            "# todo_cli journal 1\nundo\t1\tx\ncolumns\tid\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Journal::load(file.path()),
+            Err(TaskError::Journal(_))
+        ));
+    }
+
+    #[test]
+    fn drops_a_cut_short_last_line() {
+        let file = Scratch::new();
+        let one = vec![task("a0", "one")];
+        let two = vec![task("a0", "one"), task("b0", "two")];
+        let mut journal = Journal::load(file.path()).unwrap();
+        journal.record(&[], &one, "add one").unwrap();
+        journal.record(&one, &two, "add two").unwrap();
+        let path = path_for(file.path());
+        let contents = fs::read_to_string(&path).unwrap();
+
+        // Cut inside the last task row, then a garbled but complete line
+        for torn in [
+            contents[..contents.len() - 5].to_string(),
+            format!("{}+\tx\n", contents),
+        ] {
+            fs::write(&path, &torn).unwrap();
+            let mut journal = Journal::load(file.path()).unwrap();
+            assert_eq!(journal.entries.len(), 2);
+            assert!(contents.starts_with(&fs::read_to_string(&path).unwrap()));
+            // Later records start on a line of their own
+            journal.record(&two, &one, "rm two").unwrap();
+            assert_eq!(Journal::load(file.path()).unwrap().entries.len(), 3);
+            fs::write(&path, &contents).unwrap();
+        }
+
+        // Even a header cut short is only a fresh journal
+        fs::write(&path, "# todo_cli jour").unwrap();
+        let mut journal = Journal::load(file.path()).unwrap();
+        assert!(journal.entries.is_empty());
+        journal.record(&[], &one, "add one").unwrap();
+        assert_eq!(Journal::load(file.path()).unwrap().entries.len(), 1);
+    }
+}
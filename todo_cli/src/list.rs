@@ -0,0 +1,413 @@
+use chrono::NaiveDateTime;
+use std::cmp::{Ordering, Reverse};
+
+use crate::config::Config;
+use crate::dates::{self, Due};
+use crate::deps;
+use crate::query::Query;
+use crate::task::{self, Task, TaskError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Due,
+    Priority,
+    Created,
+    Completed,
+    Title,
+    Project,
+    Context,
+}
+
+impl SortKey {
+    fn parse(name: &str) -> Result<Self, TaskError> {
+        match name {
+            "due" => Ok(SortKey::Due),
+            "priority" | "pri" => Ok(SortKey::Priority),
+            "created" => Ok(SortKey::Created),
+            "completed" | "done" => Ok(SortKey::Completed),
+            "title" => Ok(SortKey::Title),
+            "project" => Ok(SortKey::Project),
+            "context" => Ok(SortKey::Context),
+            _ => Err(TaskError::Usage(format!(
+                "cannot sort by {}; use due, priority, created, completed, title, project or context",
+                name
+            ))),
+        }
+    }
+
+    /// Tasks without the field sort after those with it, whichever direction.
+    fn compare(self, a: &Task, b: &Task) -> Ordering {
+        fn missing_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        match self {
+            SortKey::Due => missing_last(a.due, b.due),
+            SortKey::Priority => missing_last(a.priority, b.priority),
+            // Newest first reads most naturally for timestamps
+            SortKey::Created => missing_last(a.created.map(Reverse), b.created.map(Reverse)),
+            SortKey::Completed => {
+                missing_last(a.completed_at.map(Reverse), b.completed_at.map(Reverse))
+            }
+            SortKey::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortKey::Project => missing_last(a.projects.first(), b.projects.first()),
+            SortKey::Context => missing_last(a.contexts.first(), b.contexts.first()),
+        }
+    }
+}
+
+/// What `list` shows and in which order.
+#[derive(Debug, Default)]
+pub struct ListOptions {
+    /// `None` shows pending and completed tasks.
+    pub completed: Option<bool>,
+    pub project: Option<String>,
+    pub context: Option<String>,
+    /// Inclusive range of priorities, `('A', 'C')` for `--priority A-C`.
+    pub priority: Option<(char, char)>,
+    /// Due on or before this.
+    pub due: Option<Due>,
+    pub overdue: bool,
+    pub sort: Vec<SortKey>,
+    pub reverse: bool,
+    /// Indent subtasks under their parents.
+    pub tree: bool,
+    pub query: Option<Query>,
+}
+
+impl ListOptions {
+    /// Words that are not options form a query, and `--query <name>` adds one
+    /// saved in `config`. With a query, completed tasks are included unless
+    /// `--pending` or `--done` says otherwise.
+    pub fn parse(params: &[&str], now: NaiveDateTime, config: &Config) -> Result<Self, TaskError> {
+        let mut options = ListOptions {
+            completed: Some(false),
+            ..ListOptions::default()
+        };
+        let mut status_given = false;
+        let mut queries = Vec::new();
+        let mut words = Vec::new();
+        let mut params = params.iter();
+        while let Some(param) = params.next() {
+            let mut value = || {
+                params
+                    .next()
+                    .copied()
+                    .ok_or_else(|| TaskError::Usage(format!("{} needs a value", param)))
+            };
+            match *param {
+                "--all" | "--pending" | "--done" => {
+                    status_given = true;
+                    options.completed = match *param {
+                        "--all" => None,
+                        other => Some(other == "--done"),
+                    };
+                }
+                "--project" => options.project = Some(value()?.trim_start_matches('+').to_string()),
+                "--context" => options.context = Some(value()?.trim_start_matches('@').to_string()),
+                "--priority" => {
+                    let range = value()?;
+                    let (low, high) = range.split_once('-').unwrap_or((range, range));
+                    let low = task::parse_priority(low).map_err(TaskError::Usage)?;
+                    let high = task::parse_priority(high).map_err(TaskError::Usage)?;
+                    options.priority = Some((low.min(high), low.max(high)));
+                }
+                "--due" => {
+                    options.due = Some(dates::parse_due(value()?, now).map_err(TaskError::Usage)?)
+                }
+                "--overdue" => options.overdue = true,
+                "--sort" => {
+                    for key in value()?.split(',') {
+                        options.sort.push(SortKey::parse(key.trim())?);
+                    }
+                }
+                "--reverse" => options.reverse = true,
+                "--tree" => options.tree = true,
+                "--query" => {
+                    let name = value()?;
+                    let query = config.query(name).ok_or_else(|| {
+                        TaskError::Usage(format!("no saved query named {}", name))
+                    })?;
+                    queries.push(format!("({})", query));
+                }
+                other if other.starts_with("--") => {
+                    return Err(TaskError::Usage(format!("unknown list option {}", other)));
+                }
+                word => words.push(word),
+            }
+        }
+        if !words.is_empty() {
+            queries.push(format!("({})", words.join(" ")));
+        }
+        if !queries.is_empty() {
+            let query = Query::compile(&queries.join(" and "), now).map_err(TaskError::Usage)?;
+            options.query = Some(query);
+            if !status_given {
+                options.completed = None;
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn matches(&self, task: &Task, tasks: &[Task], now: NaiveDateTime) -> bool {
+        self.query
+            .as_ref()
+            .is_none_or(|query| query.matches(task, tasks))
+            && self
+                .completed
+                .is_none_or(|completed| task.completed == completed)
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|project| task.projects.contains(project))
+            && self
+                .context
+                .as_ref()
+                .is_none_or(|context| task.contexts.contains(context))
+            && self.priority.is_none_or(|(low, high)| {
+                task.priority
+                    .is_some_and(|priority| (low..=high).contains(&priority))
+            })
+            && self
+                .due
+                .is_none_or(|limit| task.due.is_some_and(|due| due <= limit))
+            && (!self.overdue
+                || (!task.completed && task.due.is_some_and(|due| due.is_overdue(now))))
+    }
+
+    /// The matching tasks in the requested order; file order when unsorted.
+    pub fn apply<'a>(&self, tasks: &'a [Task], now: NaiveDateTime) -> Vec<&'a Task> {
+        let mut shown: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| self.matches(task, tasks, now))
+            .collect();
+        shown.sort_by(|a, b| {
+            self.sort
+                .iter()
+                .map(|key| key.compare(a, b))
+                .find(|order| order.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        if self.reverse {
+            shown.reverse();
+        }
+        shown
+    }
+}
+
+/// `shown` with every task after its nearest shown ancestor, paired with how
+/// deep it sits. Siblings keep their order.
+pub fn tree<'a>(tasks: &[Task], shown: &[&'a Task]) -> Vec<(usize, &'a Task)> {
+    let shown_parent = |task: &Task| {
+        let mut parent = task.parent.as_deref();
+        // Bounded in case a hand-edited file nests a task under itself
+        for _ in 0..tasks.len() {
+            let id = parent?;
+            if let Some(index) = shown.iter().position(|other| other.id == id) {
+                return Some(index);
+            }
+            parent = tasks
+                .iter()
+                .find(|other| other.id == id)
+                .and_then(|other| other.parent.as_deref());
+        }
+        None
+    };
+    let parents: Vec<Option<usize>> = shown.iter().map(|task| shown_parent(task)).collect();
+
+    let mut arranged = Vec::new();
+    let mut placed = vec![false; shown.len()];
+    let roots = (0..shown.len()).filter(|index| parents[*index].is_none());
+    // Tasks on a parent cycle have no root above them, so whatever is left
+    // once the roots are placed starts a tree of its own
+    for start in roots.chain(0..shown.len()) {
+        let mut pending = vec![(0, start)];
+        while let Some((depth, index)) = pending.pop() {
+            if placed[index] {
+                continue;
+            }
+            placed[index] = true;
+            arranged.push((depth, shown[index]));
+            pending.extend(
+                (0..shown.len())
+                    .rev()
+                    .filter(|child| parents[*child] == Some(index) && !placed[*child])
+                    .map(|child| (depth + 1, child)),
+            );
+        }
+    }
+    arranged
+}
+
+/// One `list` line: `[ ] 3f2  (A) Call plumber +home @phone  due 2026-10-20`,
+/// followed by subtask progress and open blockers.
+pub fn format_task(task: &Task, tasks: &[Task], id_len: usize, now: NaiveDateTime) -> String {
+    let mut line = format!(
+        "[{}] {}  ",
+        if task.completed { "x" } else { " " },
+        task::short_id(&task.id, id_len)
+    );
+    if let Some(priority) = task.priority {
+        line.push_str(&format!("({}) ", priority));
+    }
+    line.push_str(&task.title);
+    for project in &task.projects {
+        line.push_str(&format!(" +{}", project));
+    }
+    for context in &task.contexts {
+        line.push_str(&format!(" @{}", context));
+    }
+    for (key, value) in &task.extensions {
+        line.push_str(&format!(" {}:{}", key, value));
+    }
+    if let Some(due) = task.due {
+        line.push_str(&format!("  due {}", due));
+        if !task.completed && due.is_overdue(now) {
+            line.push_str(" (overdue)");
+        }
+    }
+    if let Some(recur) = task.recur {
+        line.push_str(&format!("  repeats {}", recur));
+    }
+    if let Some((done, total)) = deps::progress(tasks, &task.id) {
+        line.push_str(&format!(
+            "  {}/{} subtasks done ({}%)",
+            done,
+            total,
+            done * 100 / total
+        ));
+    }
+    let blockers: Vec<&str> = deps::open_blockers(tasks, task)
+        .iter()
+        .map(|blocker| task::short_id(&blocker.id, id_len))
+        .collect();
+    if !task.completed && !blockers.is_empty() {
+        line.push_str(&format!("  blocked by {}", blockers.join(", ")));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn task(id: &str, text: &str, priority: Option<char>, due: Option<&str>) -> Task {
+        let mut task = Task::new(id.to_string(), text);
+        task.priority = priority;
+        task.due = due.and_then(Due::from_stored);
+        task
+    }
+
+    fn ids(options: &[&str], tasks: &[Task]) -> Vec<String> {
+        ListOptions::parse(options, now(), &Config::default())
+            .unwrap()
+            .apply(tasks, now())
+            .iter()
+            .map(|task| task.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn filters_and_sorts() {
+        let mut done = task("d00", "Filed taxes +admin", Some('A'), Some("2026-10-01"));
+        done.set_completed(true);
+        let tasks = vec![
+            task(
+                "a00",
+                "Call plumber +home @phone",
+                Some('C'),
+                Some("2026-10-20"),
+            ),
+            task("b00", "Pay rent +home", Some('A'), Some("2026-10-13")),
+            task("c00", "Read book @couch", None, None),
+            done,
+        ];
+
+        assert_eq!(ids(&[], &tasks), ["a00", "b00", "c00"]);
+        assert_eq!(ids(&["--project", "+home"], &tasks), ["a00", "b00"]);
+        assert_eq!(ids(&["--context", "phone"], &tasks), ["a00"]);
+        assert_eq!(ids(&["--all", "--priority", "A"], &tasks), ["b00", "d00"]);
+        assert_eq!(ids(&["--priority", "a-c"], &tasks), ["a00", "b00"]);
+        assert_eq!(ids(&["--overdue", "--all"], &tasks), ["b00"]);
+        assert_eq!(ids(&["--due", "2026-10-15"], &tasks), ["b00"]);
+        assert_eq!(ids(&["--sort", "due"], &tasks), ["b00", "a00", "c00"]);
+        assert_eq!(
+            ids(&["--sort", "priority", "--reverse"], &tasks),
+            ["c00", "a00", "b00"]
+        );
+        assert_eq!(
+            ids(&["--sort", "context,title"], &tasks),
+            ["c00", "a00", "b00"]
+        );
+        assert!(ListOptions::parse(&["--sort", "size"], now(), &Config::default()).is_err());
+        assert!(ListOptions::parse(&["--priority"], now(), &Config::default()).is_err());
+    }
+
+    #[test]
+    fn formats_tags_and_overdue_dates() {
+        let task = task("b00", "Pay rent +home @bank", Some('A'), Some("2026-10-13"));
+        assert_eq!(
+            format_task(&task, &[], 3, now()),
+            "[ ] b00  (A) Pay rent +home @bank  due 2026-10-13 (overdue)"
+        );
+    }
+
+    #[test]
+    fn arranges_subtasks_as_a_tree() {
+        let mut tasks = vec![
+            task("a00", "Launch site", None, None),
+            task("b00", "Write copy", None, None),
+            task("c00", "Pick fonts", None, None),
+            task("d00", "Buy domain", None, None),
+            task("e00", "Draft intro", None, None),
+        ];
+        tasks[1].parent = Some("a00".to_string());
+        tasks[2].parent = Some("a00".to_string());
+        tasks[3].parent = Some("a00".to_string());
+        tasks[4].parent = Some("b00".to_string());
+        tasks[3].set_completed(true);
+        tasks[1].blocked_by = vec!["c00".to_string(), "d00".to_string()];
+
+        let shown: Vec<&Task> = vec![&tasks[4], &tasks[2], &tasks[1], &tasks[0]];
+        let arranged: Vec<(usize, &str)> = tree(&tasks, &shown)
+            .iter()
+            .map(|(depth, task)| (*depth, task.id.as_str()))
+            .collect();
+        assert_eq!(arranged, [(0, "a00"), (1, "c00"), (1, "b00"), (2, "e00")]);
+        // Subtasks whose parent is filtered out move up to the nearest shown ancestor
+        let shown: Vec<&Task> = vec![&tasks[0], &tasks[4]];
+        assert_eq!(tree(&tasks, &shown)[1].0, 1);
+
+        // A hand-edited parent cycle still shows every task
+        tasks[0].parent = Some("b00".to_string());
+        let shown: Vec<&Task> = vec![&tasks[0], &tasks[1], &tasks[2]];
+        let arranged: Vec<(usize, &str)> = tree(&tasks, &shown)
+            .iter()
+            .map(|(depth, task)| (*depth, task.id.as_str()))
+            .collect();
+        assert_eq!(arranged, [(0, "a00"), (1, "b00"), (1, "c00")]);
+        assert_eq!(deps::progress(&tasks, "a00"), Some((1, 4)));
+        tasks[0].parent = None;
+
+        assert_eq!(
+            format_task(&tasks[0], &tasks, 3, now()),
+            "[ ] a00  Launch site  1/4 subtasks done (25%)"
+        );
+        assert_eq!(
+            format_task(&tasks[1], &tasks, 3, now()),
+            "[ ] b00  Write copy  0/1 subtasks done (0%)  blocked by c00"
+        );
+    }
+}
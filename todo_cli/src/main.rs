@@ -1,108 +1,971 @@
+mod config;
+mod dates;
+mod deps;
+mod journal;
+mod list;
+mod merge;
+mod query;
+mod recur;
+#[cfg(test)]
+mod scratch;
+mod storage;
+mod sync;
+mod task;
+mod todo_txt;
+
+use chrono::{Local, NaiveDateTime};
 use std::env;
 use std::fs;
 use std::io::{self, Write};
+use std::net::TcpListener;
+use std::process;
+
+use config::Config;
+use journal::Journal;
+use list::ListOptions;
+use query::Query;
+use recur::Recurrence;
+use storage::{load_tasks, write_file};
+use task::{EXIT_OK, EXIT_USAGE, Task, TaskError};
+
+const DEFAULT_FILE: &str = "tasks.txt";
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+/// Options `add` and `edit` take besides the title.
+const FIELD_OPTIONS: [&str; 5] = ["--due", "--priority", "--recur", "--parent", "--blocked-by"];
 
-#[derive(Debug)]
-struct Task {
-    id: u32,
-    title: String,
-    completed: bool,
+/// Field options such as `--due` and the value that followed each.
+type FieldOptions<'a> = Vec<(&'a str, &'a str)>;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match run(&args[1..]) {
+        Ok(()) => process::exit(EXIT_OK),
+        Err(TaskError::Usage(message)) => {
+            eprintln!("Error: {}", message);
+            print_usage(&args[0]);
+            process::exit(EXIT_USAGE);
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(err.exit_code());
+        }
+    }
 }
 
-#[derive(Debug)]
-enum TaskError {
-    Io(std::io::Error),
-    ParseInt(std::num::ParseIntError),
-    ParseBool(std::str::ParseBoolError),
-    InvalidFormat,
+fn print_usage(program: &str) {
+    eprintln!(
+        "Usage: {} [--file <path>] [--config <path>] <command>",
+        program
+    );
+    eprintln!("Commands:");
+    eprintln!("  add <title> [<field options>]");
+    eprintln!("                         Add a pending task; +word and @word become tags");
+    eprintln!("  list [<query>] [--query <name>] [--all|--pending|--done]");
+    eprintln!("       [--project <name>] [--context <name>]");
+    eprintln!("       [--priority <A|A-C>] [--due <when>] [--overdue] [--tree]");
+    eprintln!("       [--sort <due,priority,created,completed,title,project,context>] [--reverse]");
+    eprintln!("                         List tasks (pending by default)");
+    eprintln!("  done <id> [--force]    Mark a task as completed and queue its next repeat;");
+    eprintln!("                         --force allows it while blockers are still open");
+    eprintln!("  reopen <id>            Mark a task as pending again");
+    eprintln!("  edit <id> [<title>] [<field options>]");
+    eprintln!("                         Change a task's title or fields");
+    eprintln!("  rm <id>                Delete a task");
+    eprintln!("  import <todo.txt>      Add tasks from a todo.txt file, updating known ones");
+    eprintln!("  export <todo.txt|->    Write every task in todo.txt format");
+    eprintln!("  undo [--last <count>]  Revert the last change, or the last <count>");
+    eprintln!("  redo [--last <count>]  Make undone changes again");
+    eprintln!("  history [<count>]      Show the last 10 changes, or the last <count>");
+    eprintln!("  sync [<url>]           Merge with a server such as http://nas:7878, which");
+    eprintln!("                         is remembered for the next sync");
+    eprintln!("  serve [<address>]      Act as the sync server for this task file");
+    eprintln!(
+        "                         (default {}); clients need the token it prints",
+        DEFAULT_ADDRESS
+    );
+    eprintln!("  query [list]           Show the saved queries");
+    eprintln!("  query save <name> <query>");
+    eprintln!("                         Save a query for `list --query <name>`");
+    eprintln!("  query rm <name>        Forget a saved query");
+    eprintln!("Queries: project:work and due<2026-11-01 and not done and title~\"invoice\"");
+    eprintln!("  Fields title, project, context, priority, due, created, completed, id and any");
+    eprintln!("  key:value extension, with : = != < <= > >= or ~ for a regex. Also done, pending,");
+    eprintln!("  overdue, blocked, recurring, words to search for, and, or, not and parentheses.");
+    eprintln!("Field options, where none clears a field:");
+    eprintln!("  --due <when>  --priority <A-E>  --recur <rule>");
+    eprintln!("  --parent <id>  --blocked-by <id,id>");
+    eprintln!("Dates: 2026-10-20, today, tomorrow, fri, next fri, next week, in 3d, 2w, in 4h,");
+    eprintln!("       optionally followed by a time such as 17:00 or 5pm.");
+    eprintln!("Recurrence: daily, weekdays, weekly, every 2 weeks, the 1st of each month,");
+    eprintln!("            monthly on the 15th, 3 days after completion.");
+    eprintln!("A --file named todo.txt, *.todo.txt or done.txt is read and written as todo.txt.");
+    eprintln!("IDs can be shortened to any unique prefix, e.g. `done 3f2`.");
+    eprintln!(
+        "Exit codes: 0 ok, 1 no such task, 2 bad usage, 3 task file error, 4 blocked, 5 sync failed"
+    );
 }
 
-impl From<std::io::Error> for TaskError {
-    fn from(err: std::io::Error) -> Self {
-        TaskError::Io(err)
+/// Splits off `--file <path>` and `--config <path>` wherever they appear and
+/// runs the command.
+fn run(args: &[String]) -> Result<(), TaskError> {
+    let mut path = DEFAULT_FILE.to_string();
+    let mut config = config::default_path();
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--file" {
+            path = iter
+                .next()
+                .ok_or_else(|| TaskError::Usage("--file needs a path".to_string()))?
+                .clone();
+        } else if arg == "--config" {
+            config = iter
+                .next()
+                .ok_or_else(|| TaskError::Usage("--config needs a path".to_string()))?
+                .clone();
+        } else {
+            rest.push(arg.as_str());
+        }
     }
+
+    let Some((command, params)) = rest.split_first() else {
+        return Err(TaskError::Usage("missing command".to_string()));
+    };
+    let mut out = io::stdout();
+    execute(&path, &config, command, params, &mut out)
 }
 
-impl From<std::num::ParseIntError> for TaskError {
-    fn from(err: std::num::ParseIntError) -> Self {
-        TaskError::ParseInt(err)
+fn execute(
+    path: &str,
+    config_path: &str,
+    command: &str,
+    params: &[&str],
+    out: &mut dyn Write,
+) -> Result<(), TaskError> {
+    let mut tasks = load_tasks(path)?;
+    let loaded = tasks.clone();
+    let action = [command]
+        .iter()
+        .chain(params)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let now = Local::now().naive_local();
+    match command {
+        "add" => {
+            let (words, options) = split_options(params)?;
+            let task = Task::new(task::new_id(&tasks), &title_from(&words)?);
+            let id = task.id.clone();
+            tasks.push(task);
+            set_fields(&mut tasks, id.as_str(), &options, now)?;
+            save(path, &loaded, &mut tasks, &action)?;
+            writeln!(
+                out,
+                "Added task {}",
+                task::short_id(&id, task::prefix_len(&tasks))
+            )?;
+        }
+        "list" => {
+            let config = Config::load(config_path)?;
+            let options = ListOptions::parse(params, now, &config)?;
+            let shown = options.apply(&tasks, now);
+            if shown.is_empty() {
+                writeln!(out, "No tasks")?;
+            }
+            // Prefixes must be unique across every task, not just the shown ones
+            let len = task::prefix_len(&tasks);
+            let arranged = if options.tree {
+                list::tree(&tasks, &shown)
+            } else {
+                shown.into_iter().map(|task| (0, task)).collect()
+            };
+            for (depth, task) in arranged {
+                writeln!(
+                    out,
+                    "{}{}",
+                    "    ".repeat(depth),
+                    list::format_task(task, &tasks, len, now)
+                )?;
+            }
+        }
+        "done" | "reopen" => {
+            let rest: Vec<&str> = params
+                .iter()
+                .copied()
+                .filter(|param| *param != "--force")
+                .collect();
+            let force = rest.len() < params.len();
+            let [id] = rest[..] else {
+                return Err(TaskError::Usage(format!(
+                    "{} takes exactly one id",
+                    command
+                )));
+            };
+            let index = task::find(&tasks, id)?;
+            let blockers = deps::open_blockers(&tasks, &tasks[index]);
+            if command == "done" && !force && !blockers.is_empty() {
+                let len = task::prefix_len(&tasks);
+                return Err(TaskError::Blocked(
+                    tasks[index].title.clone(),
+                    blockers
+                        .iter()
+                        .map(|blocker| task::short_id(&blocker.id, len).to_string())
+                        .collect(),
+                ));
+            }
+            let was_completed = tasks[index].completed;
+            tasks[index].set_completed(command == "done");
+            let mut message = format!(
+                "Marked task {} as {}",
+                tasks[index].title,
+                if command == "done" { "done" } else { "pending" }
+            );
+            if command == "done"
+                && !was_completed
+                && let Some(next) =
+                    recur::next_instance(&tasks[index], task::new_id(&tasks), now.date())
+            {
+                tasks.push(next);
+                let next = &tasks[tasks.len() - 1];
+                message.push_str(&format!(
+                    "\nNext one is task {}, due {}",
+                    task::short_id(&next.id, task::prefix_len(&tasks)),
+                    next.due.map(|due| due.to_string()).unwrap_or_default()
+                ));
+            }
+            save(path, &loaded, &mut tasks, &action)?;
+            writeln!(out, "{}", message)?;
+        }
+        "edit" => {
+            let Some((id, rest)) = params.split_first() else {
+                return Err(TaskError::Usage("edit takes an id and a title".to_string()));
+            };
+            let (words, options) = split_options(rest)?;
+            if words.is_empty() && options.is_empty() {
+                return Err(TaskError::Usage(
+                    "edit needs a new title or a field option".to_string(),
+                ));
+            }
+            let index = task::find(&tasks, id)?;
+            if !words.is_empty() {
+                let title = title_from(&words)?;
+                tasks[index].set_title(&title);
+            }
+            let full_id = tasks[index].id.clone();
+            set_fields(&mut tasks, &full_id, &options, now)?;
+            save(path, &loaded, &mut tasks, &action)?;
+            writeln!(out, "Updated task {}", id)?;
+        }
+        "rm" => {
+            let [id] = params else {
+                return Err(TaskError::Usage("rm takes exactly one id".to_string()));
+            };
+            let removed = tasks.remove(task::find(&tasks, id)?);
+            deps::forget(&mut tasks, &removed);
+            save(path, &loaded, &mut tasks, &action)?;
+            writeln!(out, "Removed task {}", removed.title)?;
+        }
+        "import" => {
+            let [source] = params else {
+                return Err(TaskError::Usage(
+                    "import takes one todo.txt file".to_string(),
+                ));
+            };
+            let (added, updated) = todo_txt::import(&mut tasks, &fs::read_to_string(source)?);
+            save(path, &loaded, &mut tasks, &action)?;
+            writeln!(
+                out,
+                "Imported {} tasks from {} ({} new, {} updated)",
+                added + updated,
+                source,
+                added,
+                updated
+            )?;
+        }
+        "export" => match params {
+            ["-"] => write!(out, "{}", todo_txt::render(&tasks))?,
+            [target] => {
+                write_file(target, &todo_txt::render(&tasks))?;
+                writeln!(out, "Exported {} tasks to {}", tasks.len(), target)?;
+            }
+            _ => {
+                return Err(TaskError::Usage(
+                    "export takes a todo.txt file or - for stdout".to_string(),
+                ));
+            }
+        },
+        "undo" | "redo" => {
+            let count = last_count(command, params, &tasks)?;
+            let mut journal = Journal::load(path)?;
+            let actions = if command == "undo" {
+                journal.undo(&mut tasks, count)?
+            } else {
+                journal.redo(&mut tasks, count)?
+            };
+            if actions.is_empty() {
+                return Err(TaskError::Usage(format!("nothing to {}", command)));
+            }
+            for action in actions {
+                let verb = if command == "undo" { "Undid" } else { "Redid" };
+                writeln!(out, "{} {}", verb, action)?;
+            }
+        }
+        "history" => {
+            let count = count_from(command, params, 10)?;
+            let journal = Journal::load(path)?;
+            if journal.entries.is_empty() {
+                writeln!(out, "No history")?;
+            }
+            for entry in journal.entries.iter().rev().take(count) {
+                writeln!(
+                    out,
+                    "{}  {}  {}{}",
+                    entry.seq,
+                    entry.time.format("%Y-%m-%d %H:%M"),
+                    entry.action,
+                    if entry.undone { "  (undone)" } else { "" }
+                )?;
+                for change in &entry.changes {
+                    writeln!(out, "    {}", change.describe())?;
+                }
+            }
+        }
+        "sync" => {
+            let mut config = Config::load(config_path)?;
+            let url = match params {
+                [] => config.server.clone().ok_or_else(|| {
+                    TaskError::Usage("sync needs a server URL the first time".to_string())
+                })?,
+                [url] => url.to_string(),
+                _ => return Err(TaskError::Usage("sync takes one server URL".to_string())),
+            };
+            let report = sync::sync(path, &url, config.token.as_deref())?;
+            if config.server.as_ref() != Some(&url) {
+                config.server = Some(url.clone());
+                config.save(config_path)?;
+            }
+            writeln!(
+                out,
+                "Synced with {}: {} changes received, {} sent",
+                url, report.received, report.sent
+            )?;
+            if !report.conflicts.is_empty() {
+                writeln!(out, "Conflicts:")?;
+            }
+            for conflict in &report.conflicts {
+                writeln!(out, "  {}", conflict)?;
+            }
+        }
+        "serve" => {
+            let address = match params {
+                [] => DEFAULT_ADDRESS,
+                [address] => address,
+                _ => return Err(TaskError::Usage("serve takes one address".to_string())),
+            };
+            let mut config = Config::load(config_path)?;
+            let token = match config.token.clone() {
+                Some(token) => token,
+                None => {
+                    let token = sync::new_token().map_err(|err| {
+                        TaskError::Sync(format!("cannot make a sync token: {}", err))
+                    })?;
+                    config.token = Some(token.clone());
+                    config.save(config_path)?;
+                    token
+                }
+            };
+            let listener = TcpListener::bind(address)
+                .map_err(|err| TaskError::Sync(format!("cannot listen on {}: {}", address, err)))?;
+            writeln!(out, "Serving {} on http://{}", path, listener.local_addr()?)?;
+            writeln!(
+                out,
+                "Clients need \"token = {}\" under [sync] in their config",
+                token
+            )?;
+            out.flush()?;
+            sync::serve(listener, path, &token);
+        }
+        "query" => {
+            let mut config = Config::load(config_path)?;
+            match params {
+                [] | ["list"] => {
+                    if config.queries.is_empty() {
+                        writeln!(out, "No saved queries")?;
+                    }
+                    for (name, query) in &config.queries {
+                        writeln!(out, "{} = {}", name, query)?;
+                    }
+                }
+                ["save", name, words @ ..] if !words.is_empty() => {
+                    if !config::valid_name(name) {
+                        return Err(TaskError::Usage(format!(
+                            "query names are letters, digits, - and _, not {}",
+                            name
+                        )));
+                    }
+                    let text = words.join(" ");
+                    Query::compile(&text, now).map_err(TaskError::Usage)?;
+                    config.set_query(name, &text);
+                    config.save(config_path)?;
+                    writeln!(out, "Saved query {}", name)?;
+                }
+                ["rm", name] => {
+                    if !config.remove_query(name) {
+                        return Err(TaskError::Usage(format!("no saved query named {}", name)));
+                    }
+                    config.save(config_path)?;
+                    writeln!(out, "Removed query {}", name)?;
+                }
+                _ => {
+                    return Err(TaskError::Usage(
+                        "query takes list, save <name> <query> or rm <name>".to_string(),
+                    ));
+                }
+            }
+        }
+        other => return Err(TaskError::Usage(format!("unknown command {}", other))),
     }
+    Ok(())
 }
 
-impl From<std::str::ParseBoolError> for TaskError {
-    fn from(err: std::str::ParseBoolError) -> Self {
-        TaskError::ParseBool(err)
+/// Marks the tasks that differ from `loaded` as modified now, then saves and
+/// journals them.
+fn save(path: &str, loaded: &[Task], tasks: &mut [Task], action: &str) -> Result<(), TaskError> {
+    let now = Local::now();
+    for task in tasks.iter_mut() {
+        if !loaded.contains(task) {
+            task.modified = Some(now);
+        }
     }
+    journal::save(path, loaded, tasks, action)
 }
 
-fn main() -> Result<(), TaskError> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <file_path>", args[0]);
-        return Ok(());
+/// The `--last <count>` `undo` and `redo` take. `undo <id>` used to reopen a
+/// task, so a bare id is refused rather than read as a count.
+fn last_count(command: &str, params: &[&str], tasks: &[Task]) -> Result<usize, TaskError> {
+    match params {
+        [] => Ok(1),
+        ["--last", count] => count_from(command, &[count], 1),
+        [id] if task::find(tasks, id).is_ok() => Err(TaskError::Usage(format!(
+            "{} takes --last <count>; use `reopen {}` to mark a task pending again",
+            command, id
+        ))),
+        _ => Err(TaskError::Usage(format!(
+            "{} takes --last <count>",
+            command
+        ))),
     }
+}
 
-    let file_path = &args[1];
+/// The optional count `undo --last`, `redo --last` and `history` take.
+fn count_from(command: &str, params: &[&str], default: usize) -> Result<usize, TaskError> {
+    match params {
+        [] => Ok(default),
+        [count] => count
+            .parse()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(|| {
+                TaskError::Usage(format!(
+                    "{} takes a number of changes, not {}",
+                    command, count
+                ))
+            }),
+        _ => Err(TaskError::Usage(format!(
+            "{} takes at most one number",
+            command
+        ))),
+    }
+}
 
-    // Load existing tasks
-    let mut tasks = load_tasks(file_path)?;
+/// Separates field options and their values from the title words.
+fn split_options<'a>(params: &[&'a str]) -> Result<(Vec<&'a str>, FieldOptions<'a>), TaskError> {
+    let mut words = Vec::new();
+    let mut options = Vec::new();
+    let mut params = params.iter();
+    while let Some(param) = params.next() {
+        if FIELD_OPTIONS.contains(param) {
+            let value = params
+                .next()
+                .ok_or_else(|| TaskError::Usage(format!("{} needs a value", param)))?;
+            options.push((*param, *value));
+        } else {
+            words.push(*param);
+        }
+    }
+    Ok((words, options))
+}
 
-    println!("Before adding new task:");
-    for task in &tasks {
-        println!("{:?}", task);
+/// Applies field options to the task with ID `id`; `none` clears a field.
+/// Parent and blocker links that would form a loop are rejected.
+fn set_fields(
+    tasks: &mut [Task],
+    id: &str,
+    options: &[(&str, &str)],
+    now: NaiveDateTime,
+) -> Result<(), TaskError> {
+    let index = task::find(tasks, id)?;
+    for (option, value) in options {
+        let clear = value.eq_ignore_ascii_case("none");
+        if *option == "--parent" {
+            tasks[index].parent = if clear {
+                None
+            } else {
+                Some(full_id(tasks, value)?)
+            };
+            continue;
+        }
+        if *option == "--blocked-by" {
+            let mut ids = Vec::new();
+            for prefix in value.split(',').filter(|_| !clear) {
+                let id = full_id(tasks, prefix)?;
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+            tasks[index].blocked_by = ids;
+            continue;
+        }
+        let task = &mut tasks[index];
+        // A due date or rule set by hand starts the day of month afresh
+        if matches!(*option, "--due" | "--recur") {
+            task.recur_day = None;
+        }
+        match *option {
+            "--due" if clear => task.due = None,
+            "--due" => task.due = Some(dates::parse_due(value, now).map_err(TaskError::Usage)?),
+            "--priority" if clear => task.priority = None,
+            "--recur" if clear => task.recur = None,
+            "--recur" => task.recur = Some(Recurrence::parse(value).map_err(TaskError::Usage)?),
+            _ => task.priority = Some(task::parse_priority(value).map_err(TaskError::Usage)?),
+        }
     }
+    let len = task::prefix_len(tasks);
+    deps::check_cycles(tasks, index).map_err(|err| match err {
+        TaskError::Cycle(ids) => TaskError::Cycle(
+            ids.iter()
+                .map(|id| task::short_id(id, len).to_string())
+                .collect(),
+        ),
+        other => other,
+    })
+}
 
-    // Add a mock task (normally, you'd get this from user input)
-    let new_task = Task {
-        id: (tasks.len() + 1) as u32,
-        title: "Learn Rust Error Handling".to_string(),
-        completed: false,
-    };
-    tasks.push(new_task);
+fn full_id(tasks: &[Task], prefix: &str) -> Result<String, TaskError> {
+    Ok(tasks[task::find(tasks, prefix.trim())?].id.clone())
+}
 
-    // Save back to file
-    save_tasks(file_path, &tasks)?;
+fn title_from(words: &[&str]) -> Result<String, TaskError> {
+    let title = words.join(" ");
+    if title.trim().is_empty() {
+        return Err(TaskError::Usage("a task needs a title".to_string()));
+    }
+    Ok(title)
+}
 
-    println!("\nAfter saving new task:");
-    for task in &tasks {
-        println!("{:?}", task);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+    use crate::task::{EXIT_BLOCKED, EXIT_NOT_FOUND, EXIT_STORAGE, EXIT_SYNC};
+    use std::fs;
+
+    fn run_in(file: &Scratch, command: &str, params: &[&str]) -> Result<String, TaskError> {
+        let mut out = Vec::new();
+        execute(file.path(), &file.config(), command, params, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
     }
 
-    Ok(())
-}
+    /// Adds a task and returns the ID prefix it was reported under.
+    fn add(file: &Scratch, title: &str) -> String {
+        let output = run_in(file, "add", &[title]).unwrap();
+        output.trim().trim_start_matches("Added task ").to_string()
+    }
 
-fn load_tasks(path: &str) -> Result<Vec<Task>, TaskError> {
-    let contents = fs::read_to_string(path)?;
-    let mut tasks = Vec::new();
-    for line in contents.lines() {
-        let parts: Vec<&str> = line.split('|').collect();
-        if parts.len() != 3 {
-            continue; // skip invalid lines
-        }
-        let id = parts[0].parse::<u32>()?;
-        // let id = match parts[0].parse::<u32>() {
-        //     Ok(val) => val,
-        //     Err(e) => return Err(e.into()), // convert to our custom error if needed
-        // };
-        let title = parts[1].to_string();
-        let completed = parts[2].parse::<bool>()?;
-
-        tasks.push(Task {
-            id,
-            title,
-            completed,
-        });
-    }
-    Ok(tasks)
-}
+    #[test]
+    fn adds_completes_and_lists_tasks() {
+        let file = Scratch::new();
+        let milk = add(&file, "Buy milk");
+        let book = add(&file, "Read the book");
+        assert_eq!(
+            run_in(&file, "done", &[&milk]).unwrap(),
+            "Marked task Buy milk as done\n"
+        );
 
-fn save_tasks(path: &str, tasks: &Vec<Task>) -> Result<(), TaskError> {
-    let mut contents = String::new();
-    for task in tasks {
-        contents.push_str(&format!("{}|{}|{}\n", task.id, task.title, task.completed));
+        let len = task::prefix_len(&load_tasks(file.path()).unwrap());
+        let short = |id: &str| task::short_id(id, len).to_string();
+        assert_eq!(
+            run_in(&file, "list", &[]).unwrap(),
+            format!("[ ] {}  Read the book\n", short(&book))
+        );
+        assert_eq!(
+            run_in(&file, "list", &["--done"]).unwrap(),
+            format!("[x] {}  Buy milk\n", short(&milk))
+        );
+        assert_eq!(
+            run_in(&file, "list", &["--all"]).unwrap().lines().count(),
+            2
+        );
+
+        run_in(&file, "reopen", &[&milk]).unwrap();
+        run_in(&file, "edit", &[&milk, "Buy", "oat", "milk"]).unwrap();
+        let tasks = load_tasks(file.path()).unwrap();
+        assert_eq!(tasks[0].title, "Buy oat milk");
+        assert!(!tasks[0].completed);
     }
 
-    let mut file = fs::File::create(path)?;
-    file.write_all(contents.as_bytes())?;
-    Ok(())
+    #[test]
+    fn sets_due_dates_priorities_and_tags() {
+        let file = Scratch::new();
+        let rent = run_in(
+            &file,
+            "add",
+            &[
+                "Pay",
+                "rent",
+                "+home",
+                "--due",
+                "2026-10-01",
+                "--priority",
+                "b",
+            ],
+        )
+        .unwrap();
+        let rent = rent.trim().trim_start_matches("Added task ").to_string();
+        add(&file, "Call plumber @phone");
+
+        let tasks = load_tasks(file.path()).unwrap();
+        assert_eq!(tasks[0].title, "Pay rent");
+        assert_eq!(tasks[0].projects, ["home"]);
+        assert_eq!(tasks[0].priority, Some('B'));
+        assert_eq!(tasks[1].contexts, ["phone"]);
+        let output = run_in(&file, "list", &["--overdue"]).unwrap();
+        assert!(output.contains("(B) Pay rent +home  due 2026-10-01 (overdue)"));
+
+        run_in(
+            &file,
+            "edit",
+            &[&rent, "--due", "none", "--priority", "none"],
+        )
+        .unwrap();
+        run_in(&file, "done", &[&rent]).unwrap();
+        let tasks = load_tasks(file.path()).unwrap();
+        assert_eq!((tasks[0].due, tasks[0].priority), (None, None));
+        assert!(tasks[0].completed_at.is_some());
+        assert_eq!(
+            run_in(&file, "list", &["--project", "home"]).unwrap(),
+            "No tasks\n"
+        );
+        assert!(run_in(&file, "add", &["x", "--due", "someday"]).is_err());
+        assert!(run_in(&file, "edit", &[&rent]).is_err());
+    }
+
+    #[test]
+    fn completing_recurring_tasks_queues_the_next() {
+        let file = Scratch::new();
+        let output = run_in(
+            &file,
+            "add",
+            &["Water", "plants", "--due", "yesterday", "--recur", "daily"],
+        )
+        .unwrap();
+        let plants = output.trim().trim_start_matches("Added task ").to_string();
+        let output = run_in(&file, "done", &[&plants]).unwrap();
+        assert!(output.contains("\nNext one is task "));
+
+        let tasks = load_tasks(file.path()).unwrap();
+        let tomorrow = Local::now().date_naive().succ_opt().unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks[0].completed && !tasks[1].completed);
+        assert_eq!(tasks[1].due.map(|due| due.date), Some(tomorrow));
+        assert_eq!(tasks[1].recur, tasks[0].recur);
+        assert!(
+            run_in(&file, "list", &[])
+                .unwrap()
+                .contains("Water plants  due ")
+        );
+        assert!(
+            run_in(&file, "list", &[])
+                .unwrap()
+                .ends_with("  repeats daily\n")
+        );
+
+        // Completing it again, or an already completed one, queues nothing
+        run_in(&file, "done", &[&plants]).unwrap();
+        run_in(&file, "edit", &[&tasks[1].id, "--recur", "none"]).unwrap();
+        run_in(&file, "done", &[&tasks[1].id]).unwrap();
+        assert_eq!(load_tasks(file.path()).unwrap().len(), 2);
+        assert!(run_in(&file, "add", &["x", "--recur", "sometimes"]).is_err());
+    }
+
+    #[test]
+    fn nests_and_blocks_tasks() {
+        let file = Scratch::new();
+        let site = add(&file, "Launch site");
+        let copy = add(&file, "Write copy");
+        let fonts = add(&file, "Pick fonts");
+        run_in(&file, "edit", &[&copy, "--parent", &site]).unwrap();
+        run_in(&file, "edit", &[&fonts, "--parent", &site]).unwrap();
+        run_in(
+            &file,
+            "edit",
+            &[&site, "--blocked-by", &format!("{},{}", copy, fonts)],
+        )
+        .unwrap();
+
+        let code = |result: Result<String, TaskError>| result.unwrap_err().exit_code();
+        assert_eq!(code(run_in(&file, "done", &[&site])), EXIT_BLOCKED);
+        assert_eq!(
+            code(run_in(&file, "edit", &[&copy, "--blocked-by", &site])),
+            EXIT_USAGE
+        );
+        assert_eq!(
+            code(run_in(&file, "edit", &[&site, "--parent", &fonts])),
+            EXIT_USAGE
+        );
+        run_in(&file, "done", &[&copy]).unwrap();
+
+        let tree = run_in(&file, "list", &["--all", "--tree"]).unwrap();
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(&format!(
+            "Launch site  1/2 subtasks done (50%)  blocked by {}",
+            fonts
+        )));
+        assert!(lines[1].starts_with("    [x] "));
+        assert!(lines[2].starts_with("    [ ] "));
+
+        run_in(&file, "done", &[&site, "--force"]).unwrap();
+        run_in(&file, "rm", &[&fonts]).unwrap();
+        let tasks = load_tasks(file.path()).unwrap();
+        assert!(tasks.iter().all(|task| task.completed));
+        assert_eq!(tasks[0].blocked_by.len(), 1);
+    }
+
+    #[test]
+    fn lists_with_queries_and_saved_queries() {
+        let file = Scratch::new();
+        let invoice = add(&file, "Send invoice +work");
+        add(&file, "Pay rent +home");
+        run_in(
+            &file,
+            "add",
+            &["Old", "invoice", "+work", "--due", "2026-01-01"],
+        )
+        .unwrap();
+        run_in(&file, "done", &[&invoice]).unwrap();
+
+        let titles = |params: &[&str]| -> Vec<String> {
+            let output = run_in(&file, "list", params).unwrap();
+            output
+                .lines()
+                .map(|line| {
+                    let title = line[4..].split("  ").nth(1).unwrap_or_default();
+                    title.split(" +").next().unwrap_or_default().to_string()
+                })
+                .collect()
+        };
+        // A query shows completed tasks unless told otherwise
+        assert_eq!(titles(&["project:work"]), ["Send invoice", "Old invoice"]);
+        assert_eq!(titles(&["--pending", "project:work"]), ["Old invoice"]);
+        assert_eq!(
+            titles(&["title~^Pay", "or", "overdue"]),
+            ["Pay rent", "Old invoice"]
+        );
+
+        assert_eq!(
+            run_in(
+                &file,
+                "query",
+                &["save", "work", "project:work", "and", "not", "done"]
+            )
+            .unwrap(),
+            "Saved query work\n"
+        );
+        assert_eq!(titles(&["--query", "work"]), ["Old invoice"]);
+        assert_eq!(
+            run_in(&file, "list", &["--query", "work", "rent"]).unwrap(),
+            "No tasks\n"
+        );
+        assert_eq!(
+            run_in(&file, "query", &[]).unwrap(),
+            "work = project:work and not done\n"
+        );
+
+        let code = |result: Result<String, TaskError>| result.unwrap_err().exit_code();
+        assert_eq!(code(run_in(&file, "list", &["(done"])), EXIT_USAGE);
+        assert_eq!(
+            code(run_in(&file, "list", &["--query", "home"])),
+            EXIT_USAGE
+        );
+        assert_eq!(
+            code(run_in(&file, "query", &["save", "bad", "due<x"])),
+            EXIT_USAGE
+        );
+        run_in(&file, "query", &["rm", "work"]).unwrap();
+        assert_eq!(
+            run_in(&file, "query", &["list"]).unwrap(),
+            "No saved queries\n"
+        );
+    }
+
+    #[test]
+    fn undoes_and_redoes_changes() {
+        let file = Scratch::new();
+        let code = |result: Result<String, TaskError>| result.unwrap_err().exit_code();
+        assert_eq!(code(run_in(&file, "undo", &[])), EXIT_USAGE);
+        let milk = add(&file, "Buy milk");
+        let rent = add(&file, "Pay rent");
+        run_in(&file, "rm", &[&milk]).unwrap();
+        run_in(&file, "edit", &[&rent, "--due", "2026-11-01"]).unwrap();
+        run_in(&file, "list", &[]).unwrap();
+
+        let titles = || -> Vec<String> {
+            let tasks = load_tasks(file.path()).unwrap();
+            tasks.iter().map(|task| task.title.clone()).collect()
+        };
+        assert_eq!(
+            run_in(&file, "undo", &["--last", "2"]).unwrap(),
+            format!("Undid edit {} --due 2026-11-01\nUndid rm {}\n", rent, milk)
+        );
+        assert_eq!(titles(), ["Buy milk", "Pay rent"]);
+        assert_eq!(load_tasks(file.path()).unwrap()[1].due, None);
+        assert_eq!(
+            run_in(&file, "redo", &[]).unwrap(),
+            format!("Redid rm {}\n", milk)
+        );
+        assert_eq!(titles(), ["Pay rent"]);
+
+        let history = run_in(&file, "history", &["3"]).unwrap();
+        let lines: Vec<&str> = history.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("4  ") && lines[0].ends_with("  (undone)"));
+        assert_eq!(lines[1], "    changed Pay rent: due");
+        assert!(lines[2].ends_with(&format!("  rm {}", milk)));
+        assert_eq!(lines[3], "    removed Buy milk");
+        assert_eq!(lines[5], "    added Pay rent");
+
+        // A new change replaces what could have been redone
+        run_in(&file, "done", &[&rent]).unwrap();
+        assert_eq!(code(run_in(&file, "redo", &[])), EXIT_USAGE);
+        assert_eq!(code(run_in(&file, "undo", &["--last", "zero"])), EXIT_USAGE);
+        assert_eq!(code(run_in(&file, "undo", &["2"])), EXIT_USAGE);
+        // The old `undo <id>` points at reopen instead of reverting changes
+        let TaskError::Usage(message) = run_in(&file, "undo", &[&rent]).unwrap_err() else {
+            panic!("expected a usage error");
+        };
+        assert!(message.contains(&format!("reopen {}", rent)), "{}", message);
+        assert!(load_tasks(file.path()).unwrap()[0].completed);
+
+        // Changes made behind the journal's back are not overwritten
+        let mut tasks = load_tasks(file.path()).unwrap();
+        tasks[0].title = "Pay the rent".to_string();
+        storage::save_tasks(file.path(), &tasks).unwrap();
+        assert_eq!(code(run_in(&file, "undo", &[])), EXIT_STORAGE);
+        assert_eq!(titles(), ["Pay the rent"]);
+    }
+
+    #[test]
+    fn syncs_two_clients_through_a_server() {
+        let server = Scratch::new();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let served = server.path().to_string();
+        std::thread::spawn(move || sync::serve(listener, &served, "5e3f"));
+        let (laptop, desktop) = (Scratch::new(), Scratch::new());
+        for client in [&laptop, &desktop] {
+            let config = Config {
+                token: Some("5e3f".to_string()),
+                ..Config::default()
+            };
+            config.save(&client.config()).unwrap();
+        }
+        let synced = |received: usize, sent: usize| {
+            format!(
+                "Synced with {}: {} changes received, {} sent\n",
+                url, received, sent
+            )
+        };
+
+        let rent = add(&laptop, "Pay rent");
+        assert_eq!(run_in(&laptop, "sync", &[&url]).unwrap(), synced(0, 1));
+        assert_eq!(run_in(&desktop, "sync", &[&url]).unwrap(), synced(1, 0));
+
+        // Changes to different fields both survive
+        run_in(&laptop, "edit", &[&rent, "--priority", "a"]).unwrap();
+        run_in(&desktop, "done", &[&rent]).unwrap();
+        add(&desktop, "Buy milk");
+        assert_eq!(run_in(&desktop, "sync", &[]).unwrap(), synced(0, 2));
+        assert_eq!(run_in(&laptop, "sync", &[]).unwrap(), synced(2, 1));
+        let tasks = load_tasks(laptop.path()).unwrap();
+        assert_eq!((tasks[0].priority, tasks[0].completed), (Some('A'), true));
+
+        // When both change the same field the later change wins
+        run_in(&desktop, "edit", &[&rent, "Pay", "the", "rent"]).unwrap();
+        run_in(&laptop, "edit", &[&rent, "Pay", "October", "rent"]).unwrap();
+        let mut tasks = load_tasks(laptop.path()).unwrap();
+        tasks[0].modified = tasks[0]
+            .modified
+            .map(|time| time + chrono::Duration::minutes(1));
+        storage::save_tasks(laptop.path(), &tasks).unwrap();
+        run_in(&desktop, "sync", &[]).unwrap();
+        assert_eq!(
+            run_in(&laptop, "sync", &[]).unwrap(),
+            format!(
+                "{}Conflicts:\n  Pay October rent: title changed on both sides; kept the newer change, made here\n",
+                synced(0, 1)
+            )
+        );
+        assert_eq!(run_in(&desktop, "sync", &[]).unwrap(), synced(1, 0));
+        assert_eq!(
+            load_tasks(desktop.path()).unwrap(),
+            load_tasks(laptop.path()).unwrap()
+        );
+        assert_eq!(
+            load_tasks(server.path()).unwrap(),
+            load_tasks(laptop.path()).unwrap()
+        );
+
+        // Syncs are journaled like any other change
+        run_in(&desktop, "undo", &[]).unwrap();
+        assert_eq!(load_tasks(desktop.path()).unwrap()[0].title, "Pay the rent");
+
+        let code = |result: Result<String, TaskError>| result.unwrap_err().exit_code();
+        let nobody = Scratch::new();
+        assert_eq!(code(run_in(&nobody, "sync", &[])), EXIT_USAGE);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        assert_eq!(code(run_in(&nobody, "sync", &[&closed])), EXIT_SYNC);
+    }
+
+    #[test]
+    fn ids_survive_removal() {
+        let file = Scratch::new();
+        let one = add(&file, "one");
+        add(&file, "two");
+        run_in(&file, "rm", &[&one]).unwrap();
+        add(&file, "three");
+        let tasks = load_tasks(file.path()).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_ne!(tasks[0].id, tasks[1].id);
+        assert!(matches!(
+            run_in(&file, "done", &[&one]),
+            Err(TaskError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn errors_map_to_exit_codes() {
+        let file = Scratch::new();
+        let code = |result: Result<String, TaskError>| result.unwrap_err().exit_code();
+        assert_eq!(code(run_in(&file, "done", &["777"])), EXIT_NOT_FOUND);
+        assert_eq!(code(run_in(&file, "rm", &["seven"])), EXIT_USAGE);
+        assert_eq!(code(run_in(&file, "add", &[])), EXIT_USAGE);
+        assert_eq!(code(run_in(&file, "list", &["--later"])), EXIT_USAGE);
+        assert_eq!(code(run_in(&file, "frobnicate", &[])), EXIT_USAGE);
+
+        fs::write(file.path(), "# todo_cli 3\nid\ttitle\nxyz\tbroken\n").unwrap();
+        assert_eq!(code(run_in(&file, "list", &[])), EXIT_STORAGE);
+    }
 }
@@ -0,0 +1,368 @@
+use std::fmt;
+
+use crate::deps;
+use crate::task::Task;
+
+/// Which copy of the list a change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Server,
+}
+
+impl Side {
+    fn here(self) -> &'static str {
+        match self {
+            Side::Local => "here",
+            Side::Server => "on the server",
+        }
+    }
+}
+
+/// A change one side lost, or almost lost, in a merge.
+#[derive(Debug, PartialEq)]
+pub enum Conflict {
+    /// Both sides changed `field`; the side that changed the task last won.
+    Field {
+        title: String,
+        field: &'static str,
+        kept: Side,
+    },
+    /// One side removed a task the other edited, so the edit was kept.
+    Removed { title: String, removed: Side },
+    /// The merged `field` made a loop with the other side's links, so the
+    /// value from the last sync was kept.
+    Cycle { title: String, field: &'static str },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conflict::Field { title, field, kept } => write!(
+                f,
+                "{}: {} changed on both sides; kept the newer change, made {}",
+                title,
+                field,
+                kept.here()
+            ),
+            Conflict::Removed { title, removed } => {
+                let edited = match removed {
+                    Side::Local => Side::Server,
+                    Side::Server => Side::Local,
+                };
+                write!(
+                    f,
+                    "{}: removed {} but edited {}; kept the edited task",
+                    title,
+                    removed.here(),
+                    edited.here()
+                )
+            }
+            Conflict::Cycle { title, field } => write!(
+                f,
+                "{}: the merged {} made a loop; kept the value from the last sync",
+                title, field
+            ),
+        }
+    }
+}
+
+/// A part of a task the merge treats as one value, with how to compare and
+/// copy it.
+struct Field {
+    name: &'static str,
+    same: fn(&Task, &Task) -> bool,
+    copy: fn(&mut Task, &Task),
+}
+
+const FIELDS: [Field; 9] = [
+    Field {
+        name: "title",
+        same: |a, b| a.title == b.title,
+        copy: |to, from| to.title = from.title.clone(),
+    },
+    Field {
+        name: "tags",
+        same: |a, b| {
+            (&a.projects, &a.contexts, &a.extensions) == (&b.projects, &b.contexts, &b.extensions)
+        },
+        copy: |to, from| {
+            to.projects = from.projects.clone();
+            to.contexts = from.contexts.clone();
+            to.extensions = from.extensions.clone();
+        },
+    },
+    Field {
+        name: "status",
+        same: |a, b| a.completed == b.completed,
+        copy: |to, from| {
+            to.completed = from.completed;
+            to.completed_at = from.completed_at;
+        },
+    },
+    Field {
+        name: "priority",
+        same: |a, b| a.priority == b.priority,
+        copy: |to, from| to.priority = from.priority,
+    },
+    Field {
+        name: "due date",
+        same: |a, b| a.due == b.due,
+        copy: |to, from| to.due = from.due,
+    },
+    Field {
+        name: "recurrence",
+        same: |a, b| (a.recur, a.recur_day) == (b.recur, b.recur_day),
+        copy: |to, from| {
+            to.recur = from.recur;
+            to.recur_day = from.recur_day;
+        },
+    },
+    Field {
+        name: "parent",
+        same: |a, b| a.parent == b.parent,
+        copy: |to, from| to.parent = from.parent.clone(),
+    },
+    Field {
+        name: "blockers",
+        same: |a, b| a.blocked_by == b.blocked_by,
+        copy: |to, from| to.blocked_by = from.blocked_by.clone(),
+    },
+    Field {
+        name: "created",
+        same: |a, b| a.created == b.created,
+        copy: |to, from| to.created = from.created,
+    },
+];
+
+/// Three-way merges the local and server lists against `base`, the list both
+/// agreed on at the last sync. A field changed on one side takes that side's
+/// value; a field changed on both takes the value from the task modified last,
+/// preferring the local one on a tie. Local order is kept and tasks new on
+/// the server go at the end. Parent and blocker links that loop once merged
+/// go back to their base values.
+pub fn merge(base: &[Task], local: &[Task], server: &[Task]) -> (Vec<Task>, Vec<Conflict>) {
+    let find = |tasks: &'_ [Task], id: &str| tasks.iter().find(|task| task.id == id).cloned();
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+    for task in local {
+        let base = find(base, &task.id);
+        match (base, find(server, &task.id)) {
+            (base, Some(theirs)) => merged.push(merge_task(base, task, &theirs, &mut conflicts)),
+            (None, None) => merged.push(task.clone()),
+            (Some(base), None) if same(&base, task) => {}
+            (Some(_), None) => {
+                conflicts.push(Conflict::Removed {
+                    title: task.title.clone(),
+                    removed: Side::Server,
+                });
+                merged.push(task.clone());
+            }
+        }
+    }
+    for theirs in server {
+        if local.iter().any(|task| task.id == theirs.id) {
+            continue;
+        }
+        match find(base, &theirs.id) {
+            None => merged.push(theirs.clone()),
+            Some(base) if same(&base, theirs) => {}
+            Some(_) => {
+                conflicts.push(Conflict::Removed {
+                    title: theirs.title.clone(),
+                    removed: Side::Local,
+                });
+                merged.push(theirs.clone());
+            }
+        }
+    }
+    break_cycles(base, &mut merged, &mut conflicts);
+    (merged, conflicts)
+}
+
+/// Each side only saved links without loops, but the two together can still
+/// make one. Restores the looping kind of link from `base` until none is left;
+/// a task new since the last sync loses the link instead.
+fn break_cycles(base: &[Task], merged: &mut [Task], conflicts: &mut Vec<Conflict>) {
+    let mut restored = true;
+    while restored {
+        restored = false;
+        for index in 0..merged.len() {
+            if deps::check_cycles(merged, index).is_ok() {
+                continue;
+            }
+            let original = base
+                .iter()
+                .find(|task| task.id == merged[index].id)
+                .cloned()
+                .unwrap_or_default();
+            for field in FIELDS
+                .iter()
+                .filter(|field| matches!(field.name, "parent" | "blockers"))
+            {
+                if (field.same)(&merged[index], &original) {
+                    continue;
+                }
+                // check_cycles follows one kind of link at a time, so with
+                // the other kind cleared it only fails on a loop of this one
+                let mut alone = merged.to_vec();
+                if field.name == "parent" {
+                    alone[index].blocked_by.clear();
+                } else {
+                    alone[index].parent = None;
+                }
+                if deps::check_cycles(&alone, index).is_ok() {
+                    continue;
+                }
+                (field.copy)(&mut merged[index], &original);
+                conflicts.push(Conflict::Cycle {
+                    title: merged[index].title.clone(),
+                    field: field.name,
+                });
+                restored = true;
+            }
+        }
+    }
+}
+
+fn merge_task(
+    base: Option<Task>,
+    ours: &Task,
+    theirs: &Task,
+    conflicts: &mut Vec<Conflict>,
+) -> Task {
+    let mut merged = ours.clone();
+    let theirs_newer = theirs.modified > ours.modified;
+    for field in &FIELDS {
+        if (field.same)(ours, theirs) {
+            continue;
+        }
+        // Without a base both sides count as changed
+        let changed = |task: &Task| base.as_ref().is_none_or(|base| !(field.same)(base, task));
+        let take_theirs = match (changed(ours), changed(theirs)) {
+            (false, _) => true,
+            (true, false) => false,
+            (true, true) => {
+                conflicts.push(Conflict::Field {
+                    title: ours.title.clone(),
+                    field: field.name,
+                    kept: if theirs_newer {
+                        Side::Server
+                    } else {
+                        Side::Local
+                    },
+                });
+                theirs_newer
+            }
+        };
+        if take_theirs {
+            (field.copy)(&mut merged, theirs);
+        }
+    }
+    merged.modified = ours.modified.max(theirs.modified);
+    merged
+}
+
+/// Whether two copies of a task have the same value in every merged field.
+fn same(a: &Task, b: &Task) -> bool {
+    FIELDS.iter().all(|field| (field.same)(a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Local};
+
+    fn task(id: &str, title: &str) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            ..Task::default()
+        }
+    }
+
+    #[test]
+    fn takes_changes_from_either_side() {
+        let base = vec![task("a0", "Pay rent"), task("b0", "Buy milk")];
+        let mut local = base.clone();
+        local[0].priority = Some('A');
+        local.remove(1);
+        local.push(task("c0", "Call Mom"));
+        let mut server = base.clone();
+        server[0].set_completed(true);
+        server.push(task("d0", "Walk dog"));
+
+        let (merged, conflicts) = merge(&base, &local, &server);
+        assert_eq!(conflicts, []);
+        let ids: Vec<&str> = merged.iter().map(|task| task.id.as_str()).collect();
+        assert_eq!(ids, ["a0", "c0", "d0"]);
+        assert_eq!(merged[0].priority, Some('A'));
+        assert!(merged[0].completed && merged[0].completed_at.is_some());
+        // Merging again changes nothing
+        assert_eq!(merge(&merged, &merged, &merged), (merged.clone(), vec![]));
+    }
+
+    #[test]
+    fn resolves_conflicts_by_last_change() {
+        let now = Local::now();
+        let base = vec![task("a0", "Pay rent"), task("b0", "Buy milk")];
+        let mut local = base.clone();
+        local[0].title = "Pay the rent".to_string();
+        local[0].due = crate::dates::Due::from_stored("2026-11-01");
+        local[0].modified = Some(now - Duration::minutes(5));
+        local[1].title = "Buy oat milk".to_string();
+        let mut server = base.clone();
+        server[0].title = "Pay October rent".to_string();
+        server[0].modified = Some(now);
+        server.remove(1);
+
+        let (merged, conflicts) = merge(&base, &local, &server);
+        assert_eq!(merged[0].title, "Pay October rent");
+        assert!(merged[0].due.is_some());
+        assert_eq!(merged[0].modified, Some(now));
+        assert_eq!(merged[1].title, "Buy oat milk");
+        let report: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            report,
+            [
+                "Pay the rent: title changed on both sides; kept the newer change, made on the server",
+                "Buy oat milk: removed on the server but edited here; kept the edited task",
+            ]
+        );
+
+        // Without a base, differences are conflicts the local side wins on a tie
+        let (merged, conflicts) = merge(&[], &[task("a0", "mine")], &[task("a0", "theirs")]);
+        assert_eq!(merged[0].title, "mine");
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn breaks_loops_made_by_both_sides() {
+        let base = vec![task("a0", "Launch"), task("b0", "Write copy")];
+        let mut local = base.clone();
+        local[0].parent = Some("b0".to_string());
+        local[1].blocked_by = vec!["a0".to_string()];
+        let mut server = base.clone();
+        server[1].parent = Some("a0".to_string());
+        server.push(task("c0", "Pick fonts"));
+        server[2].blocked_by = vec!["b0".to_string()];
+        server[0].blocked_by = vec!["c0".to_string()];
+
+        let (merged, conflicts) = merge(&base, &local, &server);
+        assert_eq!(merged[0].parent, None);
+        assert_eq!(merged[1].parent.as_deref(), Some("a0"));
+        // The blocker loop a0 -> c0 -> b0 -> a0 is broken at a0 too
+        assert!(merged[0].blocked_by.is_empty());
+        assert_eq!(merged[1].blocked_by, ["a0"]);
+        assert_eq!(merged[2].blocked_by, ["b0"]);
+        let report: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            report,
+            [
+                "Launch: the merged parent made a loop; kept the value from the last sync",
+                "Launch: the merged blockers made a loop; kept the value from the last sync",
+            ]
+        );
+        assert!((0..merged.len()).all(|index| deps::check_cycles(&merged, index).is_ok()));
+    }
+}
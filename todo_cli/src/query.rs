@@ -0,0 +1,612 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::dates::{self, Due};
+use crate::deps;
+use crate::task::{self, Task};
+
+type Predicate = Box<dyn Fn(&Task, &[Task]) -> bool>;
+
+/// A compiled `list` query such as
+/// `project:work and due<2026-11-01 and not done and title~"invoice"`.
+///
+/// Terms are `field op value` conditions, the states `done`, `pending`,
+/// `overdue`, `blocked` and `recurring`, or bare words and `"quoted phrases"`
+/// searched for in titles, tags and extension values. They combine with
+/// `and` (also implied between terms), `or`, `not` and parentheses.
+pub struct Query {
+    text: String,
+    predicate: Predicate,
+}
+
+impl Query {
+    /// Parses `text`, compiling its regexes and resolving its dates against
+    /// `now`, so a bad query fails before any task is looked at.
+    pub fn compile(text: &str, now: NaiveDateTime) -> Result<Query, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            now,
+        };
+        let predicate = if parser.tokens.is_empty() {
+            Box::new(|_: &Task, _: &[Task]| true)
+        } else {
+            parser.or()?
+        };
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("unexpected {} in query", token));
+        }
+        Ok(Query {
+            text: text.to_string(),
+            predicate,
+        })
+    }
+
+    /// `tasks` is the whole list, which `blocked` needs to look blockers up.
+    pub fn matches(&self, task: &Task, tasks: &[Task]) -> bool {
+        (self.predicate)(task, tasks)
+    }
+}
+
+impl fmt::Debug for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Query({:?})", self.text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    /// `:`, contains for text and equals for everything else.
+    Has,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `~`, a regex match.
+    Match,
+}
+
+impl Op {
+    /// Longest first, so `<=` is not read as `<`.
+    const ALL: [(&'static str, Op); 8] = [
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("!=", Op::Ne),
+        (":", Op::Has),
+        ("=", Op::Eq),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+        ("~", Op::Match),
+    ];
+
+    /// Whether `order`, of the task's value against the query's, satisfies
+    /// this comparison. `None` means the task has no value.
+    fn test(self, order: Option<Ordering>) -> bool {
+        match self {
+            Op::Has | Op::Eq => order == Some(Ordering::Equal),
+            Op::Ne => order != Some(Ordering::Equal),
+            Op::Lt => order == Some(Ordering::Less),
+            Op::Le => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+            Op::Gt => order == Some(Ordering::Greater),
+            Op::Ge => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+            Op::Match => false,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = Op::ALL
+            .iter()
+            .find(|(_, op)| op == self)
+            .map(|(symbol, _)| *symbol)
+            .unwrap_or_default();
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Condition(String, Op, String),
+    /// A bare word, which may be a keyword.
+    Word(String),
+    Phrase(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Condition(field, op, value) => write!(f, "'{}{}{}'", field, op, value),
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Phrase(phrase) => write!(f, "\"{}\"", phrase),
+        }
+    }
+}
+
+fn op_at(chars: &[char], index: usize) -> Option<(Op, usize)> {
+    Op::ALL.iter().find_map(|(symbol, op)| {
+        symbol
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| chars.get(index + offset) == Some(&c))
+            .then_some((*op, symbol.len()))
+    })
+}
+
+/// Reads the `"..."` starting at `start`, where `\"` and `\\` are escapes.
+/// Returns the text and the index after the closing quote.
+fn quoted(chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let mut text = String::new();
+    let mut index = start + 1;
+    while let Some(&c) = chars.get(index) {
+        match c {
+            '"' => return Ok((text, index + 1)),
+            '\\' if index + 1 < chars.len() => {
+                text.push(chars[index + 1]);
+                index += 2;
+            }
+            _ => {
+                text.push(c);
+                index += 1;
+            }
+        }
+    }
+    Err("unterminated quote in query".to_string())
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let ends_word = |c: char| c.is_whitespace() || c == '(' || c == ')' || c == '"';
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while let Some(&c) = chars.get(index) {
+        match c {
+            _ if c.is_whitespace() => index += 1,
+            '(' | ')' => {
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+                index += 1;
+            }
+            '"' => {
+                let (phrase, next) = quoted(&chars, index)?;
+                tokens.push(Token::Phrase(phrase));
+                index = next;
+            }
+            _ => {
+                let start = index;
+                while index < chars.len()
+                    && !ends_word(chars[index])
+                    && op_at(&chars, index).is_none()
+                {
+                    index += 1;
+                }
+                let field: String = chars[start..index].iter().collect();
+                let is_field = field.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && field
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                match op_at(&chars, index) {
+                    Some((op, len)) if is_field => {
+                        index += len;
+                        let value = if chars.get(index) == Some(&'"') {
+                            let (value, next) = quoted(&chars, index)?;
+                            index = next;
+                            value
+                        } else {
+                            let start = index;
+                            while index < chars.len() && !ends_word(chars[index]) {
+                                index += 1;
+                            }
+                            chars[start..index].iter().collect()
+                        };
+                        if value.is_empty() {
+                            return Err(format!("missing value after {}{}", field, op));
+                        }
+                        tokens.push(Token::Condition(field.to_lowercase(), op, value));
+                    }
+                    _ => {
+                        // Not a condition after all, such as `10:30`: one word
+                        while index < chars.len() && !ends_word(chars[index]) {
+                            index += 1;
+                        }
+                        tokens.push(Token::Word(chars[start..index].iter().collect()));
+                    }
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    now: NaiveDateTime,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Predicate, String> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            let right = self.and()?;
+            left = Box::new(move |task, tasks| left(task, tasks) || right(task, tasks));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Predicate, String> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) => break,
+                Some(Token::Word(word)) if word.eq_ignore_ascii_case("or") => break,
+                _ => {}
+            }
+            // Terms side by side are and-ed too
+            self.keyword("and");
+            let right = self.unary()?;
+            left = Box::new(move |task, tasks| left(task, tasks) && right(task, tasks));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Predicate, String> {
+        if self.keyword("not") {
+            let inner = self.unary()?;
+            return Ok(Box::new(move |task, tasks| !inner(task, tasks)));
+        }
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| "query ends too early".to_string())?;
+        self.position += 1;
+        match token {
+            Token::Open => {
+                let inner = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing ')' in query".to_string());
+                }
+                self.position += 1;
+                Ok(inner)
+            }
+            Token::Close => Err("unexpected ')' in query".to_string()),
+            Token::Word(word) => match state(&word.to_lowercase(), self.now) {
+                Some(predicate) => Ok(predicate),
+                None if ["and", "or"].contains(&word.to_lowercase().as_str()) => {
+                    Err(format!("'{}' needs something on both sides", word))
+                }
+                None => Ok(search(&word)),
+            },
+            Token::Phrase(phrase) => Ok(search(&phrase)),
+            Token::Condition(field, op, value) => condition(&field, op, &value, self.now),
+        }
+    }
+}
+
+/// `done`, `pending`, `overdue`, `blocked` and `recurring`.
+fn state(name: &str, now: NaiveDateTime) -> Option<Predicate> {
+    Some(match name {
+        "done" => Box::new(|task, _| task.completed),
+        "pending" => Box::new(|task, _| !task.completed),
+        "overdue" => Box::new(move |task, _| {
+            !task.completed && task.due.is_some_and(|due| due.is_overdue(now))
+        }),
+        "blocked" => {
+            Box::new(|task, tasks| !task.completed && !deps::open_blockers(tasks, task).is_empty())
+        }
+        "recurring" => Box::new(|task, _| task.recur.is_some()),
+        _ => return None,
+    })
+}
+
+/// Case-insensitive search through the title, tags and extension values.
+fn search(text: &str) -> Predicate {
+    let needle = text.to_lowercase();
+    Box::new(move |task, _| {
+        let found = |haystack: &str| haystack.to_lowercase().contains(&needle);
+        found(&task.title)
+            || task
+                .projects
+                .iter()
+                .chain(&task.contexts)
+                .any(|tag| found(tag))
+            || task.extensions.iter().any(|(_, value)| found(value))
+    })
+}
+
+fn regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|err| format!("bad regex '{}': {}", value, err))
+}
+
+fn condition(field: &str, op: Op, value: &str, now: NaiveDateTime) -> Result<Predicate, String> {
+    let unsupported = || Err(format!("{} cannot be used with {}", field, op));
+    let none = value.eq_ignore_ascii_case("none");
+    let value = value.to_string();
+    Ok(match field {
+        "title" => match op {
+            Op::Match => {
+                let pattern = regex(&value)?;
+                Box::new(move |task, _| pattern.is_match(&task.title))
+            }
+            Op::Has => {
+                let needle = value.to_lowercase();
+                Box::new(move |task, _| task.title.to_lowercase().contains(&needle))
+            }
+            Op::Eq | Op::Ne => {
+                Box::new(move |task, _| (op == Op::Eq) == task.title.eq_ignore_ascii_case(&value))
+            }
+            _ => return unsupported(),
+        },
+        "project" | "context" => {
+            let projects = field == "project";
+            let tags = move |task: &Task| {
+                if projects {
+                    task.projects.clone()
+                } else {
+                    task.contexts.clone()
+                }
+            };
+            match op {
+                Op::Has | Op::Eq | Op::Ne if none => {
+                    Box::new(move |task, _| (op == Op::Ne) != tags(task).is_empty())
+                }
+                Op::Has | Op::Eq | Op::Ne => {
+                    let name = value.trim_start_matches(['+', '@']).to_lowercase();
+                    Box::new(move |task, _| {
+                        (op == Op::Ne) != tags(task).iter().any(|tag| tag.to_lowercase() == name)
+                    })
+                }
+                Op::Match => {
+                    let pattern = regex(&value)?;
+                    Box::new(move |task, _| tags(task).iter().any(|tag| pattern.is_match(tag)))
+                }
+                _ => return unsupported(),
+            }
+        }
+        "priority" | "pri" => {
+            let wanted = if none {
+                None
+            } else {
+                Some(task::parse_priority(&value)?)
+            };
+            match (op, wanted) {
+                (Op::Match, _) => return unsupported(),
+                (Op::Has | Op::Eq | Op::Ne, None) => {
+                    Box::new(move |task, _| (op == Op::Ne) != task.priority.is_none())
+                }
+                (_, None) => return unsupported(),
+                (_, Some(wanted)) => Box::new(move |task, _| {
+                    op.test(task.priority.map(|priority| priority.cmp(&wanted)))
+                }),
+            }
+        }
+        "due" => {
+            if none {
+                return match op {
+                    Op::Has | Op::Eq | Op::Ne => Ok(Box::new(move |task, _| {
+                        (op == Op::Ne) != task.due.is_none()
+                    })),
+                    _ => unsupported(),
+                };
+            }
+            if op == Op::Match {
+                return unsupported();
+            }
+            let limit = dates::parse_due(&value, now)?;
+            Box::new(move |task, _| op.test(task.due.map(|due| compare_due(due, limit))))
+        }
+        "created" | "completed" => {
+            if op == Op::Match || none {
+                return unsupported();
+            }
+            let limit = dates::parse_due(&value, now)?.date;
+            let created = field == "created";
+            let stamp = move |task: &Task| -> Option<NaiveDate> {
+                let stamp: Option<DateTime<Local>> = if created {
+                    task.created
+                } else {
+                    task.completed_at
+                };
+                stamp.map(|stamp| stamp.date_naive())
+            };
+            Box::new(move |task, _| op.test(stamp(task).map(|date| date.cmp(&limit))))
+        }
+        "id" => match op {
+            Op::Has | Op::Eq => {
+                let prefix = value.to_ascii_lowercase();
+                Box::new(move |task, _| task.id.starts_with(&prefix))
+            }
+            _ => return unsupported(),
+        },
+        "is" if op == Op::Has => {
+            state(&value.to_lowercase(), now).ok_or_else(|| format!("unknown state '{}'", value))?
+        }
+        // Anything else is a todo.txt extension such as `owner:kim`
+        _ => {
+            let key = field.to_string();
+            let values = move |task: &Task| -> Vec<String> {
+                task.extensions
+                    .iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case(&key))
+                    .map(|(_, value)| value.clone())
+                    .collect()
+            };
+            match op {
+                Op::Match => {
+                    let pattern = regex(&value)?;
+                    Box::new(move |task, _| {
+                        values(task).iter().any(|found| pattern.is_match(found))
+                    })
+                }
+                Op::Ne => Box::new(move |task, _| {
+                    !values(task)
+                        .iter()
+                        .any(|found| found.eq_ignore_ascii_case(&value))
+                }),
+                _ => Box::new(move |task, _| {
+                    values(task)
+                        .iter()
+                        .any(|found| op.test(Some(compare_values(found, &value))))
+                }),
+            }
+        }
+    })
+}
+
+/// Against a limit without a time, only the day counts: `due<2026-11-01`
+/// leaves out a task due at 09:00 that day.
+fn compare_due(due: Due, limit: Due) -> Ordering {
+    if limit.time.is_none() {
+        due.date.cmp(&limit.date)
+    } else {
+        due.cmp(&limit)
+    }
+}
+
+/// Numerically when both are numbers, otherwise ignoring case.
+fn compare_values(found: &str, wanted: &str) -> Ordering {
+    match (found.parse::<f64>(), wanted.parse::<f64>()) {
+        (Ok(found), Ok(wanted)) => found.partial_cmp(&wanted).unwrap_or(Ordering::Equal),
+        _ => found.to_lowercase().cmp(&wanted.to_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn at(date: &str) -> Option<DateTime<Local>> {
+        let time = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        time.and_local_timezone(Local).single()
+    }
+
+    fn extension(key: &str, value: &str) -> Vec<(String, String)> {
+        vec![(key.to_string(), value.to_string())]
+    }
+
+    fn tasks() -> Vec<Task> {
+        let mut invoice = Task::new("a00".to_string(), "Send invoice to ACME +work @mail");
+        invoice.extensions = extension("owner", "kim");
+        invoice.due = Due::from_stored("2026-10-20");
+        invoice.priority = Some('A');
+        invoice.created = at("2026-10-05");
+        let mut rent = Task::new("b00".to_string(), "Pay rent +home");
+        rent.extensions = extension("sprint", "12");
+        rent.due = Due::from_stored("2026-11-01T09:00");
+        rent.priority = Some('C');
+        rent.created = at("2026-09-20");
+        let mut report = Task::new("c00".to_string(), "Invoice report +work");
+        report.extensions = extension("sprint", "9");
+        report.due = Due::from_stored("2026-10-01");
+        report.blocked_by = vec!["a00".to_string()];
+        report.created = at("2026-10-02");
+        let mut filed = Task::new("d00".to_string(), "Filed invoice +work");
+        filed.set_completed(true);
+        filed.created = at("2026-09-28");
+        filed.completed_at = at("2026-10-03");
+        vec![invoice, rent, report, filed]
+    }
+
+    fn ids(query: &str) -> Vec<String> {
+        let tasks = tasks();
+        let query = Query::compile(query, now()).unwrap();
+        tasks
+            .iter()
+            .filter(|task| query.matches(task, &tasks))
+            .map(|task| task.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn filters_on_fields() {
+        assert_eq!(
+            ids(r#"project:work and due<2026-11-01 and not done and title~"(?i)invoice""#),
+            ["a00", "c00"]
+        );
+        assert_eq!(ids("project:+work"), ["a00", "c00", "d00"]);
+        assert_eq!(ids("context:none"), ["b00", "c00", "d00"]);
+        assert_eq!(ids("priority<=B"), ["a00"]);
+        assert_eq!(ids("priority:none"), ["c00", "d00"]);
+        // Only the day counts against a date without a time
+        assert_eq!(ids("due<2026-11-01"), ["a00", "c00"]);
+        assert_eq!(ids("due>=2026-11-01T08:00"), ["b00"]);
+        assert_eq!(ids("due:2026-10-20"), ["a00"]);
+        assert_eq!(ids("due:none"), ["d00"]);
+        assert_eq!(ids("created>2026-10-01"), ["a00", "c00"]);
+        assert_eq!(ids("created<=2026-09-28"), ["b00", "d00"]);
+        assert_eq!(ids("completed>2026-10-01"), ["d00"]);
+        assert_eq!(ids("completed>2026-10-03"), Vec::<String>::new());
+        assert_eq!(ids("id:b0"), ["b00"]);
+        assert_eq!(ids("title=\"pay RENT\""), ["b00"]);
+        assert_eq!(ids("sprint>10"), ["b00"]);
+        assert_eq!(ids("owner:KIM"), ["a00"]);
+        assert_eq!(ids("owner!=kim"), ["b00", "c00", "d00"]);
+    }
+
+    #[test]
+    fn combines_states_and_searches() {
+        assert_eq!(ids("done or blocked"), ["c00", "d00"]);
+        assert_eq!(ids("overdue"), ["c00"]);
+        assert_eq!(ids("is:pending not project:work"), ["b00"]);
+        assert_eq!(ids("invoice not (done or overdue)"), ["a00"]);
+        assert_eq!(ids("\"invoice to\" or rent"), ["a00", "b00"]);
+        assert_eq!(ids("mail"), ["a00"]);
+        assert_eq!(
+            ids("NOT done AND (project:home OR priority:a)"),
+            ["a00", "b00"]
+        );
+        assert_eq!(ids(""), ["a00", "b00", "c00", "d00"]);
+        // Times are words to search for, not conditions
+        assert_eq!(ids("10:30"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for bad in [
+            "(done",
+            "done)",
+            "title~\"[\"",
+            "due<someday",
+            "priority>Z",
+            "project<work",
+            "title:",
+            "\"open",
+            "done and",
+            "or done",
+            "is:sleeping",
+        ] {
+            assert!(Query::compile(bad, now()).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Weekday};
+use std::fmt;
+
+use crate::dates::Due;
+use crate::task::Task;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+    /// Monday to Friday.
+    Weekdays,
+    /// This day of every `months` months, or the month's last day when it is
+    /// shorter.
+    MonthDay {
+        months: u32,
+        day: u32,
+    },
+}
+
+/// How a task repeats once completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recurrence {
+    pub interval: Interval,
+    /// Counts from the day the task was completed instead of its due date.
+    pub after_completion: bool,
+}
+
+impl Recurrence {
+    /// Understands `daily`, `weekly`, `monthly`, `yearly`, `weekdays`,
+    /// `every 2 weeks`, `the 1st of each month`, `monthly on the 15th`,
+    /// `every 3 months on the 31st`, any of
+    /// those followed by `after completion` (`3 days after completion`), and
+    /// the stored form.
+    pub fn parse(text: &str) -> Result<Recurrence, String> {
+        let invalid = || format!("cannot understand the recurrence '{}'", text);
+        let lowered = text.trim().to_lowercase();
+        if let Some(recurrence) = Recurrence::from_stored(&lowered) {
+            return Ok(recurrence);
+        }
+        let mut words: Vec<&str> = lowered.split_whitespace().collect();
+        let after_completion = words.ends_with(&["after", "completion"]);
+        if after_completion {
+            words.truncate(words.len() - 2);
+        }
+
+        let interval = match words[..] {
+            ["daily"] | ["every", "day"] => Interval::Days(1),
+            ["weekly"] | ["every", "week"] => Interval::Weeks(1),
+            ["monthly"] | ["every", "month"] => Interval::Months(1),
+            ["yearly"] | ["annually"] | ["every", "year"] => Interval::Years(1),
+            ["weekdays"] | ["every", "weekday"] => Interval::Weekdays,
+            ["monthly", "on", "the", day]
+            | ["every", "month", "on", "the", day]
+            | ["the", day, "of", "each" | "every", "month"]
+            | [day, "of", "each" | "every", "month"] => Interval::MonthDay {
+                months: 1,
+                day: parse_ordinal(day).ok_or_else(invalid)?,
+            },
+            ["every", count, "months", "on", "the", day] => Interval::MonthDay {
+                months: count
+                    .parse()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(invalid)?,
+                day: parse_ordinal(day).ok_or_else(invalid)?,
+            },
+            ["every", count, unit] | [count, unit] => {
+                let count = count.parse().map_err(|_| invalid())?;
+                interval(count, unit).ok_or_else(invalid)?
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Recurrence {
+            interval,
+            after_completion,
+        })
+    }
+
+    /// `+2w`, `+1b` for weekdays and `+1m@15` for a day of the month, the form
+    /// todo.txt clients use for `rec:`. Without the `+` the rule counts from
+    /// completion.
+    pub fn to_stored(self) -> String {
+        let rule = match self.interval {
+            Interval::Days(count) => format!("{}d", count),
+            Interval::Weeks(count) => format!("{}w", count),
+            Interval::Months(count) => format!("{}m", count),
+            Interval::Years(count) => format!("{}y", count),
+            Interval::Weekdays => "1b".to_string(),
+            Interval::MonthDay { months, day } => format!("{}m@{}", months, day),
+        };
+        if self.after_completion {
+            rule
+        } else {
+            format!("+{}", rule)
+        }
+    }
+
+    pub fn from_stored(text: &str) -> Option<Recurrence> {
+        let (after_completion, rule) = match text.strip_prefix('+') {
+            Some(rule) => (false, rule),
+            None => (true, text),
+        };
+        let interval = match rule.split_once('@') {
+            Some((months, day)) => Interval::MonthDay {
+                months: months
+                    .strip_suffix('m')?
+                    .parse()
+                    .ok()
+                    .filter(|months| *months > 0)?,
+                day: day.parse().ok().filter(|day| (1..=31).contains(day))?,
+            },
+            None => {
+                let digits = rule.find(|c: char| !c.is_ascii_digit())?;
+                interval(rule[..digits].parse().ok()?, &rule[digits..])?
+            }
+        };
+        Some(Recurrence {
+            interval,
+            after_completion,
+        })
+    }
+
+    /// The due date of the next instance once the task is completed `today`.
+    /// Occurrences missed while the task sat overdue are skipped. Month and
+    /// year steps land on `day`, the day of month the rule started on, when a
+    /// short month moved `due` off it.
+    pub fn next_due(self, due: Option<Due>, day: Option<u32>, today: NaiveDate) -> Option<Due> {
+        let from = match due {
+            Some(due) if !self.after_completion => due.date,
+            _ => today,
+        };
+        let day = day
+            .filter(|_| due.is_some() && !self.after_completion)
+            .unwrap_or(from.day());
+        let mut date = self.step(from, day)?;
+        while date <= today {
+            date = self.step(date, day)?;
+        }
+        Some(Due {
+            date,
+            time: due.and_then(|due| due.time),
+        })
+    }
+
+    fn step(self, date: NaiveDate, day: u32) -> Option<NaiveDate> {
+        let months = |count: u32| {
+            day_of_month(
+                date.with_day(1)?.checked_add_months(Months::new(count))?,
+                day,
+            )
+        };
+        match self.interval {
+            Interval::Days(count) => date.checked_add_days(Days::new(count.into())),
+            Interval::Weeks(count) => date.checked_add_days(Days::new(u64::from(count) * 7)),
+            Interval::Months(count) => months(count),
+            Interval::Years(count) => months(count.checked_mul(12)?),
+            Interval::Weekdays => {
+                let mut next = date.succ_opt()?;
+                while matches!(next.weekday(), Weekday::Sat | Weekday::Sun) {
+                    next = next.succ_opt()?;
+                }
+                Some(next)
+            }
+            Interval::MonthDay { months, day } => {
+                let this_month = day_of_month(date.with_day(1)?, day)?;
+                if this_month > date {
+                    return Some(this_month);
+                }
+                day_of_month(
+                    date.with_day(1)?.checked_add_months(Months::new(months))?,
+                    day,
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let every = |f: &mut fmt::Formatter, count: u32, single: &str, unit: &str| {
+            if count == 1 {
+                write!(f, "{}", single)
+            } else {
+                write!(f, "every {} {}", count, unit)
+            }
+        };
+        match self.interval {
+            Interval::Days(count) => every(f, count, "daily", "days")?,
+            Interval::Weeks(count) => every(f, count, "weekly", "weeks")?,
+            Interval::Months(count) => every(f, count, "monthly", "months")?,
+            Interval::Years(count) => every(f, count, "yearly", "years")?,
+            Interval::Weekdays => write!(f, "weekdays")?,
+            Interval::MonthDay { months: 1, day } => write!(f, "monthly on the {}", ordinal(day))?,
+            Interval::MonthDay { months, day } => {
+                write!(f, "every {} months on the {}", months, ordinal(day))?
+            }
+        }
+        if self.after_completion {
+            write!(f, " after completion")?;
+        }
+        Ok(())
+    }
+}
+
+/// The pending copy that replaces a recurring `task` when it is completed.
+pub fn next_instance(task: &Task, id: String, today: NaiveDate) -> Option<Task> {
+    let recurrence = task.recur?;
+    let due = recurrence.next_due(task.due, task.recur_day, today)?;
+    let mut next = task.clone();
+    next.id = id;
+    next.set_completed(false);
+    next.created = Some(Local::now());
+    next.due = Some(due);
+    // A monthly task due on the 31st falls on the 28th in February; remember
+    // the 31st until a longer month brings the due date back to it
+    let anchored = matches!(
+        recurrence.interval,
+        Interval::Months(_) | Interval::Years(_)
+    ) && !recurrence.after_completion;
+    next.recur_day = task
+        .recur_day
+        .or(task.due.map(|due| due.date.day()))
+        .filter(|day| anchored && *day != due.date.day());
+    Some(next)
+}
+
+fn interval(count: u32, unit: &str) -> Option<Interval> {
+    if count == 0 {
+        return None;
+    }
+    match unit {
+        "d" | "day" | "days" => Some(Interval::Days(count)),
+        "w" | "week" | "weeks" => Some(Interval::Weeks(count)),
+        "m" | "month" | "months" => Some(Interval::Months(count)),
+        "y" | "year" | "years" => Some(Interval::Years(count)),
+        "b" | "weekday" | "weekdays" if count == 1 => Some(Interval::Weekdays),
+        _ => None,
+    }
+}
+
+/// `day` in the month starting at `first`, or its last day if shorter.
+fn day_of_month(first: NaiveDate, day: u32) -> Option<NaiveDate> {
+    let last = first.checked_add_months(Months::new(1))?.pred_opt()?.day();
+    first.with_day(day.min(last))
+}
+
+/// `1st`, `22nd`, `15`.
+fn parse_ordinal(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    digits
+        .parse()
+        .ok()
+        .filter(|day| (1..=31).contains(day) && ordinal(*day).ends_with(&word[digits.len()..]))
+}
+
+fn ordinal(day: u32) -> String {
+    let suffix = match (day % 10, day % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", day, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn next(rule: &str, due: Option<&str>, today: &str) -> String {
+        Recurrence::parse(rule)
+            .unwrap()
+            .next_due(due.and_then(Due::from_stored), None, date(today))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn parses_and_stores_rules() {
+        for (rule, shown, stored) in [
+            ("daily", "daily", "+1d"),
+            ("Weekdays", "weekdays", "+1b"),
+            ("every 2 weeks", "every 2 weeks", "+2w"),
+            ("the 1st of each month", "monthly on the 1st", "+1m@1"),
+            ("monthly on the 22nd", "monthly on the 22nd", "+1m@22"),
+            (
+                "3 days after completion",
+                "every 3 days after completion",
+                "3d",
+            ),
+            ("weekly after completion", "weekly after completion", "1w"),
+            ("+1y", "yearly", "+1y"),
+            (
+                "every 2 months on the 30th",
+                "every 2 months on the 30th",
+                "+2m@30",
+            ),
+        ] {
+            let recurrence = Recurrence::parse(rule).unwrap();
+            assert_eq!(recurrence.to_string(), shown);
+            assert_eq!(recurrence.to_stored(), stored);
+            assert_eq!(Recurrence::from_stored(stored), Some(recurrence));
+        }
+        for bad in [
+            "sometimes",
+            "every 0 days",
+            "the 32nd of each month",
+            "1th of each month",
+            "every 0 months on the 5th",
+        ] {
+            assert!(Recurrence::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn rolls_due_dates_forward() {
+        assert_eq!(
+            next("daily", Some("2026-10-19"), "2026-10-19"),
+            "2026-10-20"
+        );
+        // 2026-10-16 is a Friday
+        assert_eq!(
+            next("weekdays", Some("2026-10-16"), "2026-10-16"),
+            "2026-10-19"
+        );
+        assert_eq!(
+            next("every 2 weeks", Some("2026-10-19T09:00"), "2026-10-19"),
+            "2026-11-02 09:00"
+        );
+        assert_eq!(
+            next("monthly on the 31st", Some("2027-01-31"), "2027-01-31"),
+            "2027-02-28"
+        );
+        assert_eq!(
+            next("monthly on the 31st", Some("2027-02-28"), "2027-02-28"),
+            "2027-03-31"
+        );
+        assert_eq!(
+            next("the 1st of each month", None, "2026-10-19"),
+            "2026-11-01"
+        );
+        // Missed occurrences are skipped, and completion-based rules ignore the due date
+        assert_eq!(
+            next("weekly", Some("2026-10-01"), "2026-10-19"),
+            "2026-10-22"
+        );
+        assert_eq!(
+            next("3 days after completion", Some("2026-10-01"), "2026-10-19"),
+            "2026-10-22"
+        );
+    }
+
+    #[test]
+    fn keeps_the_day_of_month_across_instances() {
+        let mut task = Task {
+            title: "Pay rent".to_string(),
+            due: Due::from_stored("2027-01-31"),
+            recur: Recurrence::parse("monthly").ok(),
+            ..Task::default()
+        };
+        let rule = task.recur;
+        let mut dues = Vec::new();
+        for _ in 0..4 {
+            let today = task.due.unwrap().date;
+            task = next_instance(&task, "a0".to_string(), today).unwrap();
+            dues.push((task.due.unwrap().to_string(), task.recur_day));
+            // The rule stays as written
+            assert_eq!(task.recur, rule);
+            assert_eq!(task.recur.unwrap().to_stored(), "+1m");
+        }
+        assert_eq!(
+            dues,
+            [
+                ("2027-02-28".to_string(), Some(31)),
+                ("2027-03-31".to_string(), None),
+                ("2027-04-30".to_string(), Some(31)),
+                ("2027-05-31".to_string(), None),
+            ]
+        );
+
+        // Occurrences missed while overdue are skipped, still landing on the kept day
+        task.due = Due::from_stored("2027-02-28");
+        task.recur_day = Some(30);
+        let next = next_instance(&task, "e0".to_string(), date("2027-04-02")).unwrap();
+        assert_eq!(next.due.unwrap().to_string(), "2027-04-30");
+        task.recur_day = None;
+
+        task.due = Due::from_stored("2026-12-30");
+        task.recur = Recurrence::parse("every 2 months").ok();
+        let next = next_instance(&task, "b0".to_string(), date("2026-12-30")).unwrap();
+        assert_eq!(next.due.unwrap().to_string(), "2027-02-28");
+        let next = next_instance(&next, "c0".to_string(), date("2027-02-28")).unwrap();
+        assert_eq!(next.due.unwrap().to_string(), "2027-04-30");
+
+        task.due = Due::from_stored("2028-02-29");
+        task.recur = Recurrence::parse("yearly").ok();
+        let next = next_instance(&task, "d0".to_string(), date("2028-02-29")).unwrap();
+        assert_eq!(
+            (next.due.unwrap().to_string(), next.recur_day),
+            ("2029-02-28".to_string(), Some(29))
+        );
+    }
+}
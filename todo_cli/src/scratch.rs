@@ -0,0 +1,45 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh task file path per test, removed with its backup on drop.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "todo_cli_test_{}_{}.txt",
+            process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        Scratch(env::temp_dir().join(name))
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    pub fn backup(&self) -> String {
+        format!("{}.bak", self.path())
+    }
+
+    /// A config file to go with the task file.
+    pub fn config(&self) -> String {
+        format!("{}.conf", self.path())
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        let _ = fs::remove_file(self.backup());
+        let _ = fs::remove_file(format!("{}.journal", self.path()));
+        let _ = fs::remove_file(format!("{}.synced", self.path()));
+        let _ = fs::remove_file(format!("{}.synced.bak", self.path()));
+        let _ = fs::remove_file(self.config());
+        let _ = fs::remove_file(format!("{}.bak", self.config()));
+    }
+}
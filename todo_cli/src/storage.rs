@@ -0,0 +1,491 @@
+use chrono::{DateTime, Local, SecondsFormat};
+use std::fs;
+use std::io::{self, Write};
+
+use crate::dates::Due;
+use crate::recur::Recurrence;
+use crate::task::{self, LineError, Task, TaskError};
+use crate::todo_txt;
+
+/// Bumped when a change would make older builds misread the file.
+/// Adding a column does not need a bump; readers look columns up by name.
+pub const VERSION: u32 = 3;
+/// Before v3, IDs were small sequential numbers.
+const RANDOM_IDS_SINCE: u32 = 3;
+const MAGIC: &str = "# todo_cli";
+pub const COLUMNS: [&str; 15] = [
+    "id",
+    "completed",
+    "priority",
+    "due",
+    "recur",
+    "recur_day",
+    "parent",
+    "blocked_by",
+    "title",
+    "projects",
+    "contexts",
+    "extensions",
+    "created",
+    "completed_at",
+    "modified",
+];
+
+/// Backslash-escapes the characters that would break a TSV row.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn unescape(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => return Err(format!("unknown escape \\{}", other)),
+            None => return Err("trailing backslash".to_string()),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Reads the task file. A missing file is an empty list, and files in an older
+/// format, including the original `id|title|completed` lines, are rewritten in
+/// the current one. Files named like `todo.txt` are read as todo.txt.
+pub fn load_tasks(path: &str) -> Result<Vec<Task>, TaskError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    if todo_txt::is_todo_txt(path) {
+        return todo_txt::load(path, &contents);
+    }
+    let (version, mut tasks) = if contents.starts_with(MAGIC) {
+        parse(&contents)?
+    } else {
+        (1, parse_legacy(&contents)?)
+    };
+    if version == VERSION || tasks.is_empty() {
+        return Ok(tasks);
+    }
+
+    // Ordinary saves rotate `.bak`, so the original gets a name of its own
+    let backup = format!("{}.v{}.bak", path, version);
+    fs::copy(path, &backup)?;
+    let renumbered = if version < RANDOM_IDS_SINCE {
+        renumber(&mut tasks)
+    } else {
+        Vec::new()
+    };
+    save_tasks(path, &tasks)?;
+    eprintln!(
+        "Migrated {} from format v{} to v{} (the old file is kept as {})",
+        path, version, VERSION, backup
+    );
+    for (old, new) in renumbered {
+        eprintln!("  task {} is now {}", old, new);
+    }
+    Ok(tasks)
+}
+
+/// Gives every task a random ID and returns the `(old, new)` pairs.
+fn renumber(tasks: &mut [Task]) -> Vec<(String, String)> {
+    let mut renumbered = Vec::new();
+    for index in 0..tasks.len() {
+        let id = task::new_id(tasks);
+        renumbered.push((std::mem::replace(&mut tasks[index].id, id.clone()), id));
+    }
+    renumbered
+}
+
+/// Returns the format version the file was written in along with its tasks.
+pub fn parse(contents: &str) -> Result<(u32, Vec<Task>), TaskError> {
+    let mut lines = contents.lines();
+    let version = lines
+        .next()
+        .and_then(|line| line.strip_prefix(MAGIC))
+        .and_then(|version| version.trim().trim_start_matches('v').parse::<u32>().ok())
+        .ok_or(TaskError::InvalidFormat)?;
+    if version > VERSION {
+        return Err(TaskError::UnsupportedVersion(version));
+    }
+    let columns: Vec<&str> = lines
+        .next()
+        .ok_or(TaskError::InvalidFormat)?
+        .split('\t')
+        .collect();
+    if !columns.contains(&"id") || !columns.contains(&"title") {
+        return Err(TaskError::InvalidFormat);
+    }
+
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut errors = Vec::new();
+    // Line numbers are 1-based and count the two header lines
+    for (index, line) in lines.enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let line_number = index + 3;
+        match parse_row(&columns, line) {
+            Ok(task) if tasks.iter().any(|other| other.id == task.id) => errors.push(LineError {
+                line: line_number,
+                reason: format!("duplicate id {}", task.id),
+            }),
+            Ok(task) => tasks.push(task),
+            Err(reason) => errors.push(LineError {
+                line: line_number,
+                reason,
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok((version, tasks))
+    } else {
+        Err(TaskError::Corrupt(errors))
+    }
+}
+
+pub fn parse_row(columns: &[&str], line: &str) -> Result<Task, String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != columns.len() {
+        return Err(format!(
+            "expected {} fields, found {}",
+            columns.len(),
+            fields.len()
+        ));
+    }
+    let field = |name: &str| {
+        columns
+            .iter()
+            .position(|column| *column == name)
+            .map(|index| fields[index])
+    };
+
+    let id = field("id").unwrap_or_default();
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("bad id '{}'", id));
+    }
+    let id = id.to_ascii_lowercase();
+    let completed = match field("completed") {
+        None | Some("0") => false,
+        Some("1") => true,
+        Some(other) => return Err(format!("bad completed flag '{}'", other)),
+    };
+    let title = unescape(field("title").unwrap_or_default())?;
+    // Columns added after v3 was introduced are optional and empty when unset
+    let optional = |name: &str| field(name).filter(|value| !value.is_empty());
+    let priority = optional("priority").map(task::parse_priority).transpose()?;
+    let due = optional("due")
+        .map(|due| Due::from_stored(due).ok_or_else(|| format!("bad due date '{}'", due)))
+        .transpose()?;
+    let recur = optional("recur")
+        .map(|rule| {
+            Recurrence::from_stored(rule).ok_or_else(|| format!("bad recurrence '{}'", rule))
+        })
+        .transpose()?;
+    let recur_day = optional("recur_day")
+        .map(|day| parse_day(day).ok_or_else(|| format!("bad recurrence day '{}'", day)))
+        .transpose()?;
+    let link = |id: &str| {
+        if id.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(id.to_ascii_lowercase())
+        } else {
+            Err(format!("bad linked id '{}'", id))
+        }
+    };
+    let parent = optional("parent").map(link).transpose()?;
+    let blocked_by = optional("blocked_by")
+        .unwrap_or_default()
+        .split(' ')
+        .filter(|id| !id.is_empty())
+        .map(link)
+        .collect::<Result<_, _>>()?;
+    let names = |name: &str| -> Result<Vec<String>, String> {
+        Ok(unescape(optional(name).unwrap_or_default())?
+            .split(' ')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect())
+    };
+    let extensions = names("extensions")?
+        .iter()
+        .map(|pair| {
+            pair.split_once(':')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or_else(|| format!("bad extension '{}'", pair))
+        })
+        .collect::<Result<_, _>>()?;
+    let timestamp = |name: &str| {
+        optional(name)
+            .map(|stamp| {
+                DateTime::parse_from_rfc3339(stamp)
+                    .map(|stamp| stamp.with_timezone(&Local))
+                    .map_err(|_| format!("bad {} timestamp '{}'", name, stamp))
+            })
+            .transpose()
+    };
+    Ok(Task {
+        id,
+        title,
+        completed,
+        priority,
+        due,
+        recur,
+        recur_day,
+        parent,
+        blocked_by,
+        projects: names("projects")?,
+        contexts: names("contexts")?,
+        extensions,
+        created: timestamp("created")?,
+        completed_at: timestamp("completed_at")?,
+        modified: timestamp("modified")?,
+    })
+}
+
+/// A day of the month, `1` to `31`.
+pub fn parse_day(text: &str) -> Option<u32> {
+    text.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// The pre-versioning format. Titles could not be escaped, so anything between
+/// the first and last `|` is taken as the title.
+pub fn parse_legacy(contents: &str) -> Result<Vec<Task>, TaskError> {
+    let mut tasks = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed = line
+            .split_once('|')
+            .and_then(|(id, rest)| Some((id, rest.rsplit_once('|')?)))
+            .ok_or_else(|| "expected id|title|completed".to_string())
+            .and_then(|(id, (title, completed))| {
+                id.parse::<u32>().map_err(|_| format!("bad id '{}'", id))?;
+                Ok(Task {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    completed: completed
+                        .parse()
+                        .map_err(|_| format!("bad completed flag '{}'", completed))?,
+                    ..Task::default()
+                })
+            });
+        match parsed {
+            Ok(task) => tasks.push(task),
+            Err(reason) => errors.push(LineError {
+                line: index + 1,
+                reason,
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(tasks)
+    } else {
+        Err(TaskError::Corrupt(errors))
+    }
+}
+
+pub fn render(tasks: &[Task]) -> String {
+    let mut contents = format!("{} {}\n{}\n", MAGIC, VERSION, COLUMNS.join("\t"));
+    for task in tasks {
+        contents.push_str(&format_row(task));
+        contents.push('\n');
+    }
+    contents
+}
+
+/// One task as a row of `COLUMNS`.
+pub fn format_row(task: &Task) -> String {
+    let stamp = |time: Option<DateTime<Local>>| {
+        time.map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, false))
+            .unwrap_or_default()
+    };
+    let extensions: Vec<String> = task
+        .extensions
+        .iter()
+        .map(|(key, value)| format!("{}:{}", key, value))
+        .collect();
+    let fields = [
+        task.id.clone(),
+        u8::from(task.completed).to_string(),
+        task.priority.map(String::from).unwrap_or_default(),
+        task.due.map(Due::to_stored).unwrap_or_default(),
+        task.recur.map(Recurrence::to_stored).unwrap_or_default(),
+        task.recur_day
+            .map(|day| day.to_string())
+            .unwrap_or_default(),
+        task.parent.clone().unwrap_or_default(),
+        task.blocked_by.join(" "),
+        escape(&task.title),
+        escape(&task.projects.join(" ")),
+        escape(&task.contexts.join(" ")),
+        escape(&extensions.join(" ")),
+        stamp(task.created),
+        stamp(task.completed_at),
+        stamp(task.modified),
+    ];
+    fields.join("\t")
+}
+
+/// Saves in whichever format `load_tasks` would read `path` in.
+pub fn save_tasks(path: &str, tasks: &[Task]) -> Result<(), TaskError> {
+    if todo_txt::is_todo_txt(path) {
+        write_file(path, &todo_txt::render(tasks))
+    } else {
+        write_file(path, &render(tasks))
+    }
+}
+
+/// Writes to a temporary file and renames it over `path`, so a crash leaves
+/// either the old or the new list. The previous version is kept as `.bak`.
+pub fn write_file(path: &str, contents: &str) -> Result<(), TaskError> {
+    let temp = write_temp(path, contents, fs::OpenOptions::new())?;
+    match fs::copy(path, format!("{}.bak", path)) {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Like `write_file`, for files holding secrets: only the owner can read the
+/// new file, and no `.bak` is kept where an old secret could linger.
+pub fn write_private(path: &str, contents: &str) -> Result<(), TaskError> {
+    let mut options = fs::OpenOptions::new();
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let temp = write_temp(path, contents, options)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Writes `contents` to `<path>.tmp` and returns that path.
+fn write_temp(path: &str, contents: &str, mut options: fs::OpenOptions) -> io::Result<String> {
+    let temp = format!("{}.tmp", path);
+    // A leftover temp file would keep its old mode
+    match fs::remove_file(&temp) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let mut file = options.write(true).create_new(true).open(&temp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    Ok(temp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+
+    fn task(id: &str, title: &str, completed: bool) -> Task {
+        Task {
+            id: id.to_string(),
+            title: title.to_string(),
+            completed,
+            ..Task::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_awkward_titles() {
+        let mut tagged = Task::new("9c0000".to_string(), "tabs\tand\nnewlines\r\n +home @phone");
+        tagged.extensions = vec![("sprint".to_string(), "12".to_string())];
+        tagged.set_completed(true);
+        tagged.priority = Some('B');
+        tagged.due = Due::from_stored("2026-10-20T17:00");
+        tagged.recur = Recurrence::from_stored("+1m");
+        tagged.recur_day = Some(31);
+        tagged.parent = Some("3f2a01".to_string());
+        tagged.blocked_by = vec!["3f2a01".to_string(), "77".to_string()];
+        // Timestamps are stored to the second
+        tagged.created = DateTime::parse_from_rfc3339("2026-10-14T09:00:00+02:00")
+            .ok()
+            .map(|stamp| stamp.with_timezone(&Local));
+        tagged.completed_at = tagged.created;
+        tagged.modified = tagged.created;
+        let tasks = vec![task("3f2a01", "pipes | and \\ backslashes", false), tagged];
+        let contents = render(&tasks);
+        assert_eq!(contents.lines().count(), 4);
+        assert_eq!(parse(&contents).unwrap(), (VERSION, tasks));
+        assert!(unescape("bad \\q").is_err());
+    }
+
+    #[test]
+    fn reports_corrupt_lines_by_number() {
+        let contents = "# todo_cli 3\nid\tcompleted\ttitle\nab1\t0\tfine\nxyz\t0\tbad id\n\nab2\t7\tbad flag\nAB1\t0\tagain\n";
+        let TaskError::Corrupt(errors) = parse(contents).unwrap_err() else {
+            panic!("expected corrupt lines");
+        };
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, [4, 6, 7]);
+        assert_eq!(errors[2].reason, "duplicate id ab1");
+
+        assert!(matches!(
+            parse("# todo_cli 9\nid\ttitle\n"),
+            Err(TaskError::UnsupportedVersion(9))
+        ));
+    }
+
+    #[test]
+    fn migrates_old_formats_with_new_ids() {
+        let file = Scratch::new();
+        let legacy = "1|Buy milk|false\n2|a|b title|true\n";
+        fs::write(file.path(), legacy).unwrap();
+
+        let tasks = load_tasks(file.path()).unwrap();
+        let titles: Vec<(&str, bool)> = tasks
+            .iter()
+            .map(|task| (task.title.as_str(), task.completed))
+            .collect();
+        assert_eq!(titles, [("Buy milk", false), ("a|b title", true)]);
+        assert!(tasks.iter().all(|task| task.id.len() > 2));
+        assert!(
+            fs::read_to_string(file.path())
+                .unwrap()
+                .starts_with("# todo_cli 3\n")
+        );
+        let backup = format!("{}.v1.bak", file.path());
+        assert_eq!(fs::read_to_string(&backup).unwrap(), legacy);
+        fs::remove_file(backup).unwrap();
+        assert_eq!(load_tasks(file.path()).unwrap(), tasks);
+
+        // Sequential v2 IDs are replaced too
+        fs::write(
+            file.path(),
+            "# todo_cli 2\nid\tcompleted\ttitle\n1\t0\tone\n",
+        )
+        .unwrap();
+        let tasks = load_tasks(file.path()).unwrap();
+        assert_ne!(tasks[0].id, "1");
+        fs::remove_file(format!("{}.v2.bak", file.path())).unwrap();
+
+        fs::write(file.path(), "1|ok|false\nnonsense\n").unwrap();
+        let TaskError::Corrupt(errors) = load_tasks(file.path()).unwrap_err() else {
+            panic!("expected corrupt lines");
+        };
+        assert_eq!(errors[0].line, 2);
+    }
+}
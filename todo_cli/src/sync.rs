@@ -0,0 +1,445 @@
+use chrono::{DateTime, Local};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::journal;
+use crate::merge::{self, Conflict};
+use crate::storage;
+use crate::task::{Task, TaskError};
+use crate::todo_txt;
+
+/// The one resource a server has: its whole list, in the native format.
+const TASKS_PATH: &str = "/tasks";
+/// Larger bodies are refused rather than read into memory.
+const MAX_BODY: usize = 16 << 20;
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Times `sync` starts over when another client synced in between.
+const ATTEMPTS: usize = 3;
+
+/// What a sync changed on each side, and the conflicts it resolved.
+pub struct Report {
+    pub received: usize,
+    pub sent: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// The list as it was after the last sync, which tells the merge which side
+/// changed what since.
+pub fn base_path(path: &str) -> String {
+    format!("{}.synced", path)
+}
+
+/// Merges the task file at `path` with the one served at `url`, then saves
+/// the result on both sides. `token` must match the server's.
+pub fn sync(path: &str, url: &str, token: Option<&str>) -> Result<Report, TaskError> {
+    let base = match fs::read_to_string(base_path(path)) {
+        Ok(contents) => storage::parse(&contents)?.1,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut local = storage::load_tasks(path)?;
+    // A missing file has no tasks to restore
+    if todo_txt::is_todo_txt(path)
+        && let Ok(saved) = fs::metadata(path).and_then(|metadata| metadata.modified())
+    {
+        restore_times(&mut local, &base, saved.into());
+    }
+    for _ in 0..ATTEMPTS {
+        let response = request(url, token, "GET", None, "")?;
+        let server = match response.status() {
+            200 => {
+                storage::parse(&response.body)
+                    .map_err(|err| {
+                        TaskError::Sync(format!("{} sent an unreadable list: {}", url, err))
+                    })?
+                    .1
+            }
+            _ => return Err(unexpected(url, &response)),
+        };
+        let (merged, conflicts) = merge::merge(&base, &local, &server);
+
+        let sent = journal::diff(&server, &merged).len();
+        if sent > 0 {
+            // Only accepted if nobody synced since the GET
+            let tag = response.header("etag").unwrap_or_default();
+            let response = request(url, token, "PUT", Some(tag), &storage::render(&merged))?;
+            match response.status() {
+                200 => {}
+                412 => continue,
+                _ => return Err(unexpected(url, &response)),
+            }
+        }
+        let received = journal::diff(&local, &merged).len();
+        if received > 0 {
+            journal::save(path, &local, &merged, &format!("sync {}", url))?;
+        }
+        storage::write_file(&base_path(path), &storage::render(&merged))?;
+        return Ok(Report {
+            received,
+            sent,
+            conflicts,
+        });
+    }
+    Err(TaskError::Sync(format!(
+        "{} kept changing while syncing; try again",
+        url
+    )))
+}
+
+/// todo.txt keeps dates only and no modification time. Tasks read from one
+/// get back the times they had at the last sync where the dates agree, and
+/// ones edited since count as modified when the file was last written.
+fn restore_times(local: &mut [Task], base: &[Task], saved: DateTime<Local>) {
+    let day = |time: Option<DateTime<Local>>| time.map(|time| time.date_naive());
+    for task in local {
+        let Some(synced) = base.iter().find(|synced| synced.id == task.id) else {
+            task.modified = Some(saved);
+            continue;
+        };
+        if day(task.created) == day(synced.created) {
+            task.created = synced.created;
+        }
+        if day(task.completed_at) == day(synced.completed_at) {
+            task.completed_at = synced.completed_at;
+        }
+        task.modified = if todo_txt::format_line(task) == todo_txt::format_line(synced) {
+            synced.modified
+        } else {
+            Some(saved)
+        };
+    }
+}
+
+fn unexpected(url: &str, response: &Message) -> TaskError {
+    if response.status() == 401 {
+        return TaskError::Sync(format!(
+            "{} refused the sync token; copy token = <secret> under [sync] from the server's config",
+            url
+        ));
+    }
+    TaskError::Sync(format!(
+        "{} answered {}: {}",
+        url,
+        response.start.trim(),
+        response.body.trim()
+    ))
+}
+
+/// Serves the task file at `path` until the process is stopped, one request
+/// at a time. `GET /tasks` returns the list with an `ETag`, and `PUT /tasks`
+/// replaces it when `If-Match` names the version the client merged with.
+/// Requests without `Authorization: Bearer <token>` are refused.
+pub fn serve(listener: TcpListener, path: &str, token: &str) {
+    for stream in listener.incoming() {
+        let result = stream
+            .map_err(TaskError::from)
+            .and_then(|stream| handle(stream, path, token));
+        if let Err(err) = result {
+            eprintln!("Error: {}", err);
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream, path: &str, token: &str) -> Result<(), TaskError> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let peer = stream.peer_addr()?;
+    let request = read_message(&stream)?;
+    let action = format!("sync from {}", peer.ip());
+    let (status, body) = respond(&request, path, token, &action).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        ("500 Internal Server Error", format!("{}\n", err))
+    });
+    let tag = if status.starts_with("200") {
+        format!("ETag: {}\r\n", etag(&body))
+    } else {
+        String::new()
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        tag,
+        body
+    )?;
+    Ok(())
+}
+
+fn respond(
+    request: &Message,
+    path: &str,
+    token: &str,
+    action: &str,
+) -> Result<(&'static str, String), TaskError> {
+    let sent = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !sent.is_some_and(|sent| same_secret(sent, token)) {
+        return Ok((
+            "401 Unauthorized",
+            "The sync token is missing or wrong\n".to_string(),
+        ));
+    }
+    let mut parts = request.start.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    if target != TASKS_PATH {
+        let body = format!("Not found. The list is served at {}\n", TASKS_PATH);
+        return Ok(("404 Not Found", body));
+    }
+    let tasks = storage::load_tasks(path)?;
+    let contents = storage::render(&tasks);
+    match method {
+        "GET" => Ok(("200 OK", contents)),
+        "PUT" if request.header("if-match") != Some(&etag(&contents)) => Ok((
+            "412 Precondition Failed",
+            "The list changed since it was fetched\n".to_string(),
+        )),
+        "PUT" => match storage::parse(&request.body) {
+            Ok((_, merged)) => {
+                journal::save(path, &tasks, &merged, action)?;
+                Ok(("200 OK", storage::render(&merged)))
+            }
+            Err(err) => Ok(("400 Bad Request", format!("{}\n", err))),
+        },
+        _ => Ok((
+            "405 Method Not Allowed",
+            "Only GET and PUT are supported\n".to_string(),
+        )),
+    }
+}
+
+/// A random secret for `serve` to share with its clients.
+pub fn new_token() -> io::Result<String> {
+    let mut bytes = [0; 16];
+    fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Compares every byte so the time taken does not tell how much of a guess
+/// was right.
+fn same_secret(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Names one version of the rendered list.
+fn etag(contents: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// An HTTP request or response.
+struct Message {
+    /// The request or status line.
+    start: String,
+    /// Names are lowercased.
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Message {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The code of a response, or 0 if the status line is garbled.
+    fn status(&self) -> u16 {
+        self.start
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// Reads a message whose body length is given by `Content-Length`.
+fn read_message(stream: &TcpStream) -> io::Result<Message> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut start = String::new();
+    reader.read_line(&mut start)?;
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let mut message = Message {
+        start,
+        headers,
+        body: String::new(),
+    };
+    let length = match message.header("content-length") {
+        Some(length) => length.parse().map_err(|_| invalid("bad Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(invalid("message too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    message.body = String::from_utf8(body).map_err(|_| invalid("body is not UTF-8"))?;
+    Ok(message)
+}
+
+/// Sends one request to the server at `url`, e.g. `http://nas:7878`.
+fn request(
+    url: &str,
+    token: Option<&str>,
+    method: &str,
+    if_match: Option<&str>,
+    body: &str,
+) -> Result<Message, TaskError> {
+    let address = url
+        .strip_prefix("http://")
+        .map(|rest| rest.trim_end_matches('/'))
+        .filter(|address| !address.is_empty() && !address.contains('/'))
+        .ok_or_else(|| {
+            TaskError::Usage(format!(
+                "sync needs a server URL like http://host:7878, not {}",
+                url
+            ))
+        })?;
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:80", address)
+    };
+    let failed = |err: io::Error| TaskError::Sync(format!("{}: {}", url, err));
+
+    let mut stream = TcpStream::connect(&address).map_err(failed)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(failed)?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(failed)?;
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        method,
+        TASKS_PATH,
+        address,
+        body.len()
+    );
+    if let Some(token) = token {
+        head.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    if let Some(tag) = if_match {
+        head.push_str(&format!("If-Match: {}\r\n", tag));
+    }
+    head.push_str("\r\n");
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(body.as_bytes()))
+        .map_err(failed)?;
+    read_message(&stream).map_err(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+    use std::thread;
+
+    const TOKEN: &str = "0123abcd";
+
+    fn get(url: &str) -> Result<Message, TaskError> {
+        request(url, Some(TOKEN), "GET", None, "")
+    }
+
+    fn put(url: &str, tag: Option<&str>, tasks: &[Task]) -> Result<Message, TaskError> {
+        request(url, Some(TOKEN), "PUT", tag, &storage::render(tasks))
+    }
+
+    #[test]
+    fn only_accepts_updates_to_the_latest_version() {
+        let server = Scratch::new();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let path = server.path().to_string();
+        thread::spawn(move || serve(listener, &path, TOKEN));
+
+        // Nothing is served or stored without the token
+        for token in [None, Some("0123abce"), Some("0123")] {
+            let response = request(&url, token, "GET", None, "").unwrap();
+            assert_eq!(response.status(), 401);
+            assert_eq!(response.body, "The sync token is missing or wrong\n");
+        }
+        let response = request(&url, None, "PUT", None, "").unwrap();
+        assert_eq!(response.status(), 401);
+        let client = Scratch::new();
+        assert!(matches!(
+            sync(client.path(), &url, Some("wrong")),
+            Err(TaskError::Sync(message)) if message.contains("refused the sync token")
+        ));
+
+        let empty = get(&url).unwrap();
+        assert_eq!(empty.status(), 200);
+        let tag = empty.header("etag").unwrap().to_string();
+        let tasks = vec![Task {
+            id: "ab12".to_string(),
+            title: "Pay rent".to_string(),
+            ..Task::default()
+        }];
+        assert_eq!(put(&url, Some("\"0\""), &tasks).unwrap().status(), 412);
+        assert_eq!(put(&url, None, &tasks).unwrap().status(), 412);
+        let stored = put(&url, Some(&tag), &tasks).unwrap();
+        assert_eq!(stored.status(), 200);
+        assert_ne!(stored.header("etag"), Some(tag.as_str()));
+        // The version the client merged with is gone now
+        assert_eq!(put(&url, Some(&tag), &[]).unwrap().status(), 412);
+
+        let fetched = get(&url).unwrap();
+        assert_eq!(fetched.header("etag"), stored.header("etag"));
+        assert_eq!(storage::parse(&fetched.body).unwrap().1, tasks);
+        assert_eq!(storage::load_tasks(server.path()).unwrap(), tasks);
+
+        let tag = fetched.header("etag").unwrap();
+        assert_eq!(
+            request(&url, Some(TOKEN), "PUT", Some(tag), "junk")
+                .unwrap()
+                .status(),
+            400
+        );
+        assert!(get("ftp://host").is_err());
+        let with_path = format!("{}/other", url);
+        assert!(matches!(get(&with_path), Err(TaskError::Usage(_))));
+    }
+
+    #[test]
+    fn restores_times_todo_txt_drops() {
+        let synced = Task {
+            id: "ab12".to_string(),
+            title: "Pay rent".to_string(),
+            created: Some(Local::now()),
+            modified: Some(Local::now()),
+            ..Task::default()
+        };
+        let read_back = || todo_txt::parse_line(&todo_txt::format_line(&synced));
+        let base = vec![synced.clone()];
+        let saved = Local::now() + chrono::Duration::minutes(1);
+        let mut local = vec![read_back(), read_back()];
+        local[1].id = "cd34".to_string();
+        restore_times(&mut local, &base, saved);
+        assert_eq!(local[0], synced);
+        assert_eq!(local[1].modified, Some(saved));
+
+        let mut local = vec![read_back()];
+        local[0].set_completed(true);
+        restore_times(&mut local, &base, saved);
+        assert_eq!(local[0].created, synced.created);
+        assert_eq!(local[0].modified, Some(saved));
+    }
+}
@@ -0,0 +1,327 @@
+use chrono::{DateTime, Local};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dates::Due;
+use crate::recur::Recurrence;
+
+/// Exit codes, so scripts can tell a missing task from a typo.
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_NOT_FOUND: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_STORAGE: i32 = 3;
+pub const EXIT_BLOCKED: i32 = 4;
+pub const EXIT_SYNC: i32 = 5;
+
+/// Hex digits in a full task ID.
+const ID_LEN: usize = 10;
+/// Shortest prefix shown by `list` and accepted on the command line.
+pub const MIN_PREFIX: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Task {
+    /// Random hex, so tasks added on different machines never collide.
+    pub id: String,
+    /// The title without its `+project` and `@context` words.
+    pub title: String,
+    pub completed: bool,
+    /// `A` (highest) to `E`.
+    pub priority: Option<char>,
+    pub due: Option<Due>,
+    pub recur: Option<Recurrence>,
+    /// The day of month a monthly or yearly rule keeps to while a short month
+    /// has moved the due date off it, e.g. 31 when due on February 28.
+    pub recur_day: Option<u32>,
+    /// ID of the task this one is a subtask of.
+    pub parent: Option<String>,
+    /// IDs of tasks that have to be completed first.
+    pub blocked_by: Vec<String>,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    /// todo.txt style `key:value` words, in the order they were written.
+    pub extensions: Vec<(String, String)>,
+    /// Unknown for tasks written before timestamps were recorded.
+    pub created: Option<DateTime<Local>>,
+    pub completed_at: Option<DateTime<Local>>,
+    /// When the task was last changed, which decides sync conflicts.
+    pub modified: Option<DateTime<Local>>,
+}
+
+impl Task {
+    pub fn new(id: String, text: &str) -> Self {
+        let mut task = Task {
+            id,
+            created: Some(Local::now()),
+            ..Task::default()
+        };
+        task.set_title(text);
+        task
+    }
+
+    /// Sets the title, moving `+project` and `@context` words into their
+    /// lists. `key:value` words stay in the title, since ordinary text such as
+    /// `re:budget` looks the same; only todo.txt lines carry extensions.
+    pub fn set_title(&mut self, text: &str) {
+        let mut words = Vec::new();
+        self.projects.clear();
+        self.contexts.clear();
+        for word in text.split(' ') {
+            let tag = |prefix: char| word.strip_prefix(prefix).filter(|name| !name.is_empty());
+            match (tag('+'), tag('@')) {
+                (Some(project), _) => push_unique(&mut self.projects, project),
+                (_, Some(context)) => push_unique(&mut self.contexts, context),
+                _ => words.push(word),
+            }
+        }
+        self.title = words.join(" ").trim().to_string();
+    }
+
+    pub fn set_completed(&mut self, completed: bool) {
+        self.completed = completed;
+        self.completed_at = completed.then(Local::now);
+    }
+}
+
+fn push_unique(list: &mut Vec<String>, name: &str) {
+    if !list.iter().any(|existing| existing == name) {
+        list.push(name.to_string());
+    }
+}
+
+/// Splits a todo.txt `key:value` word at its first colon. Keys start with a
+/// letter, so times such as `10:30` stay in the title, and `//` keeps URLs
+/// there too. Values may hold colons, as in `due:2026-10-20T17:00`.
+pub fn extension(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    (valid_key && !value.is_empty() && !value.starts_with("//")).then_some((key, value))
+}
+
+/// `A` to `E`, in either case.
+pub fn parse_priority(text: &str) -> Result<char, String> {
+    match text.to_ascii_uppercase().as_str() {
+        letter @ ("A" | "B" | "C" | "D" | "E") => Ok(letter.chars().next().unwrap_or('A')),
+        _ => Err(format!("priority must be A to E, not {}", text)),
+    }
+}
+
+/// A line of the task file that could not be read.
+#[derive(Debug, PartialEq)]
+pub struct LineError {
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub enum TaskError {
+    Io(std::io::Error),
+    /// The file has no usable header.
+    InvalidFormat,
+    /// Written by a newer todo_cli.
+    UnsupportedVersion(u32),
+    Corrupt(Vec<LineError>),
+    NotFound(String),
+    Ambiguous(String, Vec<String>),
+    Usage(String),
+    /// A task and the IDs of the open tasks blocking it.
+    Blocked(String, Vec<String>),
+    /// IDs around a parent or blocker loop, starting and ending at the same one.
+    Cycle(Vec<String>),
+    /// The config file could not be read.
+    Config(String),
+    /// The journal could not be read, or no longer matches the task file.
+    Journal(String),
+    /// The sync server could not be reached or refused the list.
+    Sync(String),
+}
+
+impl From<std::io::Error> for TaskError {
+    fn from(err: std::io::Error) -> Self {
+        TaskError::Io(err)
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskError::Io(err) => write!(f, "could not access the task file: {}", err),
+            TaskError::InvalidFormat => write!(f, "task file is missing its column header"),
+            TaskError::UnsupportedVersion(version) => write!(
+                f,
+                "task file is format version {}, which this todo_cli cannot read",
+                version
+            ),
+            TaskError::Corrupt(errors) => {
+                write!(f, "task file has {} unreadable lines", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  line {}: {}", error.line, error.reason)?;
+                }
+                Ok(())
+            }
+            TaskError::NotFound(id) => write!(f, "no task with id {}", id),
+            TaskError::Ambiguous(prefix, ids) => write!(
+                f,
+                "{} matches {} tasks ({}); type more of the id",
+                prefix,
+                ids.len(),
+                ids.join(", ")
+            ),
+            TaskError::Usage(message) => write!(f, "{}", message),
+            TaskError::Blocked(title, ids) => write!(
+                f,
+                "{} is blocked by {}; finish those first or use --force",
+                title,
+                ids.join(", ")
+            ),
+            TaskError::Config(reason) => write!(f, "bad config file {}", reason),
+            TaskError::Journal(reason) => write!(f, "cannot use the journal: {}", reason),
+            TaskError::Sync(reason) => write!(f, "sync failed: {}", reason),
+            TaskError::Cycle(ids) => write!(f, "that would make a loop: {}", ids.join(" -> ")),
+        }
+    }
+}
+
+impl TaskError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TaskError::NotFound(_) => EXIT_NOT_FOUND,
+            TaskError::Usage(_) | TaskError::Ambiguous(..) | TaskError::Cycle(_) => EXIT_USAGE,
+            TaskError::Blocked(..) => EXIT_BLOCKED,
+            TaskError::Sync(_) => EXIT_SYNC,
+            _ => EXIT_STORAGE,
+        }
+    }
+}
+
+/// A fresh ID that no task in `tasks` uses yet.
+pub fn new_id(tasks: &[Task]) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    loop {
+        // RandomState is seeded randomly per process; the clock and counter
+        // keep IDs from one process apart
+        let mut hasher = RandomState::new().build_hasher();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        hasher.write_u128(nanos);
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        let id = format!("{:016x}", hasher.finish())[..ID_LEN].to_string();
+        if !tasks.iter().any(|task| task.id == id) {
+            return id;
+        }
+    }
+}
+
+/// Shortest prefix length, at least `MIN_PREFIX`, that tells every task apart.
+pub fn prefix_len(tasks: &[Task]) -> usize {
+    let longest = tasks.iter().map(|task| task.id.len()).max().unwrap_or(0);
+    (MIN_PREFIX..longest)
+        .find(|len| {
+            let mut prefixes: Vec<&str> = tasks
+                .iter()
+                .map(|task| &task.id[..(*len).min(task.id.len())])
+                .collect();
+            prefixes.sort_unstable();
+            prefixes.windows(2).all(|pair| pair[0] != pair[1])
+        })
+        .unwrap_or(longest.max(MIN_PREFIX))
+}
+
+/// `id` abbreviated the way `list` shows it.
+pub fn short_id(id: &str, len: usize) -> &str {
+    &id[..len.min(id.len())]
+}
+
+/// Index of the one task whose ID starts with `prefix`.
+pub fn find(tasks: &[Task], prefix: &str) -> Result<usize, TaskError> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(TaskError::Usage(format!("{} is not a task id", prefix)));
+    }
+    if let Some(exact) = tasks.iter().position(|task| task.id == prefix) {
+        return Ok(exact);
+    }
+    // One or two digits would match a task by accident too easily
+    if prefix.len() < MIN_PREFIX {
+        return Err(TaskError::Usage(format!(
+            "type at least {} digits of the id",
+            MIN_PREFIX
+        )));
+    }
+    let matches: Vec<usize> = (0..tasks.len())
+        .filter(|index| tasks[*index].id.starts_with(&prefix))
+        .collect();
+    match matches[..] {
+        [] => Err(TaskError::NotFound(prefix)),
+        [index] => Ok(index),
+        _ => Err(TaskError::Ambiguous(
+            prefix,
+            matches
+                .iter()
+                .map(|index| tasks[*index].id.clone())
+                .collect(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(ids: &[&str]) -> Vec<Task> {
+        ids.iter()
+            .map(|id| Task::new(id.to_string(), "task"))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_tags_from_titles() {
+        let mut task = Task::new("abc".to_string(), "Call +home plumber @phone +home @x");
+        assert_eq!(task.title, "Call plumber");
+        assert_eq!(task.projects, ["home"]);
+        assert_eq!(task.contexts, ["phone", "x"]);
+        assert!(task.created.is_some());
+
+        task.set_title("Email a+b about @ and + signs");
+        assert_eq!(task.title, "Email a+b about @ and + signs");
+        assert!(task.projects.is_empty() && task.contexts.is_empty());
+        let title = "Call re:budget at 10:30, read https://example.com sprint:12";
+        task.set_title(title);
+        assert_eq!(task.title, title);
+        assert!(task.extensions.is_empty());
+        assert_eq!(parse_priority("b"), Ok('B'));
+        assert!(parse_priority("F").is_err());
+    }
+
+    #[test]
+    fn generates_distinct_ids() {
+        let mut existing = Vec::new();
+        for _ in 0..200 {
+            let id = new_id(&existing);
+            assert_eq!(id.len(), ID_LEN);
+            existing.extend(tasks(&[&id]));
+        }
+        assert_eq!(prefix_len(&tasks(&["3f2a01", "3f2b77", "9c0000"])), 4);
+        assert_eq!(prefix_len(&tasks(&["3f2a01"])), MIN_PREFIX);
+    }
+
+    #[test]
+    fn matches_unique_prefixes() {
+        let list = tasks(&["3f2a01bb", "3f2b77cc", "9c0000dd", "1"]);
+        assert_eq!(find(&list, "9c0").unwrap(), 2);
+        assert_eq!(find(&list, "3F2B").unwrap(), 1);
+        // A whole ID wins even when it prefixes others
+        assert_eq!(find(&list, "1").unwrap(), 3);
+        assert!(matches!(find(&list, "3f2"), Err(TaskError::Ambiguous(_, ids)) if ids.len() == 2));
+        assert!(matches!(find(&list, "abc"), Err(TaskError::NotFound(_))));
+        assert!(matches!(find(&list, "9c"), Err(TaskError::Usage(_))));
+        assert!(matches!(find(&list, "zz"), Err(TaskError::Usage(_))));
+    }
+}
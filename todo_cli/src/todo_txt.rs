@@ -0,0 +1,284 @@
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::path::Path;
+
+use crate::dates::Due;
+use crate::recur::Recurrence;
+use crate::storage;
+use crate::task::{self, Task, TaskError};
+
+/// Extension holding the task ID. Other clients keep `key:value` words they
+/// do not know, so the ID survives edits made there.
+pub const ID_KEY: &str = "tid";
+
+/// `todo.txt`, `work.todo.txt` and `done.txt` are todo.txt files.
+pub fn is_todo_txt(path: &str) -> bool {
+    let name = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    name.ends_with("todo.txt") || name == "done.txt"
+}
+
+/// Reads a todo.txt file, giving tasks without a usable ID a new one and
+/// writing those back so the IDs `list` shows keep working.
+pub fn load(path: &str, contents: &str) -> Result<Vec<Task>, TaskError> {
+    let mut tasks = parse(contents);
+    let mut assigned = 0;
+    for index in 0..tasks.len() {
+        let id = &tasks[index].id;
+        if id.is_empty() || tasks[..index].iter().any(|task| &task.id == id) {
+            tasks[index].id = task::new_id(&tasks);
+            assigned += 1;
+        }
+    }
+    if assigned > 0 {
+        storage::save_tasks(path, &tasks)?;
+        eprintln!(
+            "Gave {} tasks in {} an id, stored as {}:<id>",
+            assigned, path, ID_KEY
+        );
+    }
+    Ok(tasks)
+}
+
+/// Every non-blank line is a task; anything not understood stays in the title.
+pub fn parse(contents: &str) -> Vec<Task> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_line)
+        .collect()
+}
+
+pub fn parse_line(line: &str) -> Task {
+    let mut task = Task::default();
+    let mut rest = line;
+    if let Some(after) = rest.strip_prefix("x ") {
+        task.completed = true;
+        rest = after;
+    } else if let Some(priority) = leading_priority(rest) {
+        task.priority = Some(priority);
+        rest = &rest[4..];
+    }
+    // A completed task's first date is when it was completed
+    let first = take_date(&mut rest);
+    if task.completed {
+        task.completed_at = first;
+        task.created = first.and_then(|_| take_date(&mut rest));
+    } else {
+        task.created = first;
+    }
+    let (extensions, words): (Vec<&str>, Vec<&str>) = rest
+        .split(' ')
+        .partition(|word| task::extension(word).is_some());
+    task.set_title(&words.join(" "));
+
+    let mut extensions: Vec<(String, String)> = extensions
+        .iter()
+        .filter_map(|word| task::extension(word))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    extensions.retain(|(key, value)| match key.as_str() {
+        ID_KEY if task.id.is_empty() && is_id(value) => {
+            task.id = value.to_ascii_lowercase();
+            false
+        }
+        "due" if task.due.is_none() => Due::from_stored(value)
+            .map(|due| task.due = Some(due))
+            .is_none(),
+        "parent" if task.parent.is_none() && is_id(value) => {
+            task.parent = Some(value.to_ascii_lowercase());
+            false
+        }
+        "blocked-by" if is_id(value) => {
+            task.blocked_by.push(value.to_ascii_lowercase());
+            false
+        }
+        "rec" if task.recur.is_none() => Recurrence::from_stored(value)
+            .map(|recur| task.recur = Some(recur))
+            .is_none(),
+        "rec-day" if task.recur_day.is_none() => storage::parse_day(value)
+            .map(|day| task.recur_day = Some(day))
+            .is_none(),
+        // Completed lines cannot start with a priority, so clients move it here
+        "pri" if task.completed && task.priority.is_none() => task::parse_priority(value)
+            .map(|priority| task.priority = Some(priority))
+            .is_err(),
+        _ => true,
+    });
+    task.extensions = extensions;
+    task
+}
+
+fn is_id(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// `(A) ` at the start of a line, for the priorities a `Task` can hold.
+fn leading_priority(line: &str) -> Option<char> {
+    let bytes = line.as_bytes();
+    if bytes.len() < 4 || bytes[0] != b'(' || bytes[2] != b')' || bytes[3] != b' ' {
+        return None;
+    }
+    if !bytes[1].is_ascii_uppercase() {
+        return None;
+    }
+    task::parse_priority(&char::from(bytes[1]).to_string()).ok()
+}
+
+/// Removes a leading `2026-10-14 ` from `rest`, as local midnight.
+fn take_date(rest: &mut &str) -> Option<DateTime<Local>> {
+    let (word, after) = rest.split_once(' ').unwrap_or((rest, ""));
+    let date = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+    let midnight = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()?;
+    *rest = after;
+    Some(midnight)
+}
+
+/// The todo.txt line for `task`. todo.txt keeps dates only, and tags and
+/// extensions are written after the title.
+pub fn format_line(task: &Task) -> String {
+    let date = |time: DateTime<Local>| time.format("%Y-%m-%d").to_string();
+    let mut words = Vec::new();
+    if task.completed {
+        words.push("x".to_string());
+        // A creation date on its own would be read back as the completion date
+        if let Some(completed_at) = task.completed_at {
+            words.push(date(completed_at));
+            words.extend(task.created.map(date));
+        }
+    } else {
+        words.extend(task.priority.map(|priority| format!("({})", priority)));
+        words.extend(task.created.map(date));
+    }
+    if !task.title.is_empty() {
+        words.push(task.title.clone());
+    }
+    words.extend(task.projects.iter().map(|project| format!("+{}", project)));
+    words.extend(task.contexts.iter().map(|context| format!("@{}", context)));
+    words.extend(task.due.map(|due| format!("due:{}", due.to_stored())));
+    words.extend(task.recur.map(|recur| format!("rec:{}", recur.to_stored())));
+    words.extend(task.recur_day.map(|day| format!("rec-day:{}", day)));
+    words.extend(task.parent.iter().map(|id| format!("parent:{}", id)));
+    words.extend(
+        task.blocked_by
+            .iter()
+            .map(|id| format!("blocked-by:{}", id)),
+    );
+    if task.completed {
+        words.extend(task.priority.map(|priority| format!("pri:{}", priority)));
+    }
+    words.extend(
+        task.extensions
+            .iter()
+            .map(|(key, value)| format!("{}:{}", key, value)),
+    );
+    if !task.id.is_empty() {
+        words.push(format!("{}:{}", ID_KEY, task.id));
+    }
+    words.join(" ")
+}
+
+pub fn render(tasks: &[Task]) -> String {
+    tasks.iter().map(|task| format_line(task) + "\n").collect()
+}
+
+/// Merges todo.txt `contents` into `tasks`: lines carrying the ID of an
+/// existing task replace it, the rest are added. Returns `(added, updated)`.
+pub fn import(tasks: &mut Vec<Task>, contents: &str) -> (usize, usize) {
+    let (mut added, mut updated) = (0, 0);
+    for mut imported in parse(contents) {
+        match tasks
+            .iter()
+            .position(|task| !imported.id.is_empty() && task.id == imported.id)
+        {
+            Some(index) => {
+                tasks[index] = imported;
+                updated += 1;
+            }
+            None => {
+                if imported.id.is_empty() {
+                    imported.id = task::new_id(tasks);
+                }
+                tasks.push(imported);
+                added += 1;
+            }
+        }
+    }
+    (added, updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::Scratch;
+    use std::fs;
+
+    #[test]
+    fn round_trips_todo_txt_lines() {
+        let lines = [
+            "(A) 2026-10-01 Call Mom +family @phone due:2026-10-20 rec:+1w tid:3f2a01",
+            "x 2026-10-14 2026-10-01 Pay rent +home due:2026-10-13T17:00 pri:B tid:9c0000",
+            "x Post letter parent:3f2a01 blocked-by:9c0000 blocked-by:77 tid:ab12",
+            "2026-10-05 Review PR see https://x.org/1 at 10:30 +work sprint:12 owner:kim tid:77",
+            "Water plants rec:4d rec:daily tid:b0",
+            "(F) not a priority we keep tid:1",
+            "Pay rent due:2027-02-28 rec:+1m rec-day:31 tid:c4",
+        ];
+        for line in lines {
+            assert_eq!(format_line(&parse_line(line)), line);
+        }
+
+        let task = parse_line(lines[1]);
+        assert!(task.completed);
+        assert_eq!(task.priority, Some('B'));
+        assert_eq!(task.id, "9c0000");
+        assert_eq!(task.title, "Pay rent");
+        assert_eq!(
+            task.completed_at.map(|time| time.date_naive().to_string()),
+            Some("2026-10-14".to_string())
+        );
+        let task = parse_line(lines[3]);
+        assert_eq!(task.title, "Review PR see https://x.org/1 at 10:30");
+        assert_eq!(task.extensions.len(), 2);
+        assert_eq!(parse_line(lines[6]).recur_day, Some(31));
+
+        // Other clients may put tags anywhere; they come back at the end
+        let task = parse_line("(B) Call +home plumber @phone due:tomorrow");
+        assert_eq!(task.title, "Call plumber");
+        assert_eq!(task.due, None);
+        assert_eq!(
+            format_line(&task),
+            "(B) Call plumber +home @phone due:tomorrow"
+        );
+        assert_eq!(parse_line(&format_line(&task)), task);
+    }
+
+    #[test]
+    fn imports_and_operates_on_todo_txt_files() {
+        let mut tasks = parse("Known task tid:abc\n");
+        let (added, updated) = import(&mut tasks, "x Known task tid:abc\n\nNew one\n");
+        assert_eq!((added, updated), (1, 1));
+        assert!(tasks[0].completed);
+        assert!(!tasks[1].id.is_empty());
+
+        assert!(is_todo_txt("/home/kim/todo.txt"));
+        assert!(is_todo_txt("work.todo.txt"));
+        assert!(!is_todo_txt("tasks.txt"));
+
+        // Lines without IDs get one written back on first load
+        let dir = Scratch::new();
+        let path = format!("{}.todo.txt", dir.path());
+        fs::write(&path, "(A) Call Mom\nCall Mom\n").unwrap();
+        let loaded = storage::load_tasks(&path).unwrap();
+        assert_ne!(loaded[0].id, loaded[1].id);
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(&format!("(A) Call Mom tid:{}\n", loaded[0].id)));
+        assert_eq!(storage::load_tasks(&path).unwrap(), loaded);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(format!("{}.bak", path)).unwrap();
+    }
+}